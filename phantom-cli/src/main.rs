@@ -1,7 +1,8 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use clap::{command, Parser};
-use log::{error, info};
+use log::{debug, error, info};
 use phantom_rs::PhantomOpts;
 use simplelog::{ColorChoice, LevelFilter, TermLogger, TerminalMode};
 
@@ -9,8 +10,13 @@ use simplelog::{ColorChoice, LevelFilter, TermLogger, TerminalMode};
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Bedrock/MCPE server IP address and port (ex: 1.2.3.4:19132)
-    #[arg(short, long)]
-    server: String,
+    #[arg(short, long, required_unless_present = "config")]
+    server: Option<String>,
+
+    /// Reads options from a TOML or JSON file (by extension) instead of the
+    /// command line. Only `server` is required in it.
+    #[arg(short, long, conflicts_with = "server")]
+    config: Option<PathBuf>,
 
     /// IP address to listen on. Defaults to all interfaces.
     #[arg(long, default_value = "0.0.0.0")]
@@ -33,6 +39,10 @@ struct Args {
     /// Enables IPv6 support on port 19133 (experimental)
     #[arg(short = '6', long, default_value_t = false)]
     ipv6: bool,
+
+    /// Pings the server, prints its status, and exits without proxying
+    #[arg(long, default_value_t = false)]
+    ping: bool,
 }
 
 #[tokio::main]
@@ -40,13 +50,22 @@ async fn main() {
     let args = Args::parse();
     println!("Args: {:?}", args);
 
-    let opts = PhantomOpts {
-        server: args.server.clone(),
-        bind: args.bind.clone(),
-        bind_port: args.bind_port,
-        timeout: args.timeout,
-        debug: args.debug,
-        ipv6: args.ipv6,
+    let opts = match &args.config {
+        Some(path) => match read_config(path) {
+            Ok(opts) => opts,
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => PhantomOpts {
+            server: args.server.clone().unwrap_or_default(),
+            bind: args.bind.clone(),
+            bind_port: args.bind_port,
+            timeout: args.timeout,
+            debug: args.debug,
+            ipv6: args.ipv6,
+        },
     };
 
     let log_level = if opts.debug {
@@ -62,6 +81,20 @@ async fn main() {
         ColorChoice::Always,
     );
 
+    if args.ping {
+        if let Err(e) = ping(&opts.server).await {
+            error!("Failed to ping {}: {}", opts.server, e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let build = phantom_rs::version();
+    info!(
+        "phantom-rs {} ({})",
+        build.version,
+        build.git_hash.as_deref().unwrap_or("unknown commit")
+    );
     info!("Starting Phantom with options: {:?}", opts);
     let phantom = Arc::new(
         phantom_rs::new_with_current_runtime(opts).expect("Failed to create Phantom instance"),
@@ -73,6 +106,24 @@ async fn main() {
         loop {
             let _ = tokio::signal::ctrl_c().await;
             info!("Ctrl-C received, stopping Phantom...");
+            for task in phantom_for_shutdown.tasks() {
+                debug!(
+                    "Running task {}: {:?} for {:?}",
+                    task.name.as_deref().unwrap_or("unnamed"),
+                    task.status,
+                    task.age
+                );
+            }
+            let metrics = phantom_for_shutdown.metrics();
+            info!(
+                "Forwarded {} packets ({} bytes) from clients and {} packets ({} bytes) to clients; {} clients in total, {} errors",
+                metrics.packets_from_clients,
+                metrics.bytes_from_clients,
+                metrics.packets_to_clients,
+                metrics.bytes_to_clients,
+                metrics.lifetime_clients,
+                metrics.errors
+            );
             phantom_for_shutdown
                 .stop()
                 .await
@@ -80,10 +131,44 @@ async fn main() {
         }
     });
 
-    if let Err(e) = phantom.start().await {
-        error!("Failed to start Phantom: {}", e);
-        return;
+    match phantom.start().await {
+        Ok(bound) => info!(
+            "Phantom listening on {}:{}",
+            bound.proxy_address, bound.proxy_port
+        ),
+        Err(e) => {
+            error!("Failed to start Phantom: {}", e);
+            return;
+        }
+    }
+
+    if let Err(e) = phantom.wait_until_stopped().await {
+        error!("Failed waiting for Phantom to stop: {}", e);
     }
 
     info!("Phantom shut down");
 }
+
+async fn ping(server: &str) -> Result<(), phantom_rs::client::ClientError> {
+    let client = phantom_rs::client::Client::new().await?;
+    let pong = client.ping(server.to_string()).await?;
+    println!("{} ({})", pong.motd, pong.sub_motd);
+    println!(
+        "Version {} (protocol {}, {})",
+        pong.version,
+        pong.protocol_version,
+        pong.game_version.as_deref().unwrap_or("unknown release")
+    );
+    println!("{}/{} players", pong.players, pong.max_players);
+    println!("{} ms", pong.latency_ms);
+    Ok(())
+}
+
+fn read_config(path: &PathBuf) -> Result<PhantomOpts, Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(path)?;
+    let opts = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => PhantomOpts::from_json(&contents)?,
+        _ => PhantomOpts::from_toml(&contents)?,
+    };
+    Ok(opts)
+}
@@ -0,0 +1,32 @@
+use std::process::Command;
+
+fn main() {
+    // `version()` reports the commit the library was built from. Builds from a
+    // source tarball have no git, and report none; neither do copies vendored
+    // into another repository, whose HEAD isn't this crate's commit.
+    if git(&["ls-files", "--error-unmatch", "Cargo.toml"]).is_none() {
+        return;
+    }
+    if let Some(hash) = git(&["rev-parse", "--short=12", "HEAD"]) {
+        println!("cargo:rustc-env=PHANTOM_GIT_HASH={hash}");
+    }
+
+    // Rebuild when HEAD moves, whether by checkout or by commit
+    if let Some(head) = git(&["rev-parse", "--git-path", "HEAD"]) {
+        println!("cargo:rerun-if-changed={head}");
+    }
+    if let Some(branch) = git(&["symbolic-ref", "-q", "HEAD"]) {
+        if let Some(reference) = git(&["rev-parse", "--git-path", &branch]) {
+            println!("cargo:rerun-if-changed={reference}");
+        }
+    }
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    Some(stdout.trim().to_string())
+}
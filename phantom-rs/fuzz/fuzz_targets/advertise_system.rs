@@ -0,0 +1,12 @@
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use phantom_rs::proto::advertise_system::AdvertiseSystem;
+use phantom_rs::proto::MagicMode;
+
+fuzz_target!(|data: &[u8]| {
+    let data = Bytes::copy_from_slice(data);
+    let _ = AdvertiseSystem::from_bytes(data.clone());
+    let _ = AdvertiseSystem::from_bytes_with_mode(data, MagicMode::Strict);
+});
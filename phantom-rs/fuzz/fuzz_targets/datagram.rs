@@ -0,0 +1,10 @@
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use phantom_rs::proto::datagram::Datagram;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Datagram::is_datagram(data);
+    let _ = Datagram::from_bytes(Bytes::copy_from_slice(data));
+});
@@ -0,0 +1,17 @@
+#![no_main]
+
+use bytes::{Bytes, BytesMut};
+use libfuzzer_sys::fuzz_target;
+use phantom_rs::proto::java::{decode_frame, read_varint, JavaPong, StatusResponse};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = read_varint(&mut Bytes::copy_from_slice(data), "fuzz");
+
+    let mut buf = BytesMut::from(data);
+    while let Ok(Some(packet)) = decode_frame(&mut buf) {
+        if let Ok(response) = StatusResponse::from_bytes(packet.clone()) {
+            let _ = response.status().map(|status| status.description_text());
+        }
+        let _ = JavaPong::from_bytes(packet);
+    }
+});
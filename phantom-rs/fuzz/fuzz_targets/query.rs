@@ -0,0 +1,14 @@
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use phantom_rs::proto::query::{BasicStat, FullStat, QueryHandshakeResponse};
+
+fuzz_target!(|data: &[u8]| {
+    let data = Bytes::copy_from_slice(data);
+    let _ = QueryHandshakeResponse::from_bytes(data.clone());
+    let _ = BasicStat::from_bytes(data.clone());
+    if let Ok(stat) = FullStat::from_bytes(data) {
+        let _ = stat.plugins();
+    }
+});
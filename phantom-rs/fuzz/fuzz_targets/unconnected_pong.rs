@@ -0,0 +1,27 @@
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use phantom_rs::proto::unconnected_pong::{PongData, UnconnectedPong, UnconnectedPongRef};
+use phantom_rs::proto::MagicMode;
+
+fuzz_target!(|data: &[u8]| {
+    let data = Bytes::copy_from_slice(data);
+    let _ = UnconnectedPong::from_bytes_with_mode(data.clone(), MagicMode::Strict);
+
+    // Anything that parses must re-encode and parse back to the same payload
+    if let Ok(pong) = UnconnectedPong::from_bytes(data.clone()) {
+        let rebuilt =
+            UnconnectedPong::from_bytes(pong.build().expect("parsed pong must re-encode"))
+                .expect("re-encoded pong must parse");
+        assert_eq!(rebuilt.server_guid, pong.server_guid);
+    }
+
+    if let Ok(pong_ref) = UnconnectedPongRef::from_bytes(data.clone()) {
+        let _ = pong_ref.payload_str();
+    }
+
+    if let Ok(s) = std::str::from_utf8(&data) {
+        let _ = PongData::from_string(s);
+    }
+});
@@ -0,0 +1,89 @@
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use log::warn;
+use tokio::sync::mpsc;
+
+/// Why a message never reached its actor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadLetterReason {
+    /// The actor had already stopped, or stopped with the message still queued
+    Stopped,
+    /// A bounded mailbox was full and rejected it
+    MailboxFull,
+    /// A `DropOldest` mailbox pushed it out to make room
+    Evicted,
+    /// The handler panicked while handling it. The handler had already taken
+    /// the message, so `message` is `None`.
+    HandlerPanicked,
+}
+
+/// A message that couldn't be delivered, with enough context to tell where it
+/// was going
+pub struct DeadLetter {
+    /// Name of the intended recipient, if it has one
+    pub actor: Option<String>,
+    /// Type name of the actor's message enum
+    pub message_type: &'static str,
+    pub reason: DeadLetterReason,
+    /// The message itself; downcast to the actor's message type to inspect
+    /// it. `None` if a panicking handler had already taken it.
+    pub message: Option<Box<dyn Any + Send>>,
+    /// The message formatted by the actor's `MessageFormatter`, when one is
+    /// set and the message went undelivered because of a panic
+    pub description: Option<String>,
+}
+
+impl fmt::Debug for DeadLetter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeadLetter")
+            .field("actor", &self.actor)
+            .field("message_type", &self.message_type)
+            .field("reason", &self.reason)
+            .field("description", &self.description)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Where an actor's undeliverable messages go. The default logs a warning.
+#[derive(Clone)]
+pub struct DeadLetterSink(Arc<dyn Fn(DeadLetter) + Send + Sync>);
+
+impl DeadLetterSink {
+    pub fn new(handler: impl Fn(DeadLetter) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(handler))
+    }
+
+    /// A sink that forwards dead letters to the returned receiver
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<DeadLetter>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let sink = Self::new(move |letter| {
+            let _ = sender.send(letter);
+        });
+        (sink, receiver)
+    }
+
+    pub(crate) fn deliver(&self, letter: DeadLetter) {
+        (self.0)(letter)
+    }
+}
+
+impl Default for DeadLetterSink {
+    fn default() -> Self {
+        Self::new(|letter| {
+            warn!(
+                "[actor {}] dropped {} message: {:?}",
+                letter.actor.as_deref().unwrap_or("unnamed"),
+                letter.message_type,
+                letter.reason
+            )
+        })
+    }
+}
+
+impl fmt::Debug for DeadLetterSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DeadLetterSink")
+    }
+}
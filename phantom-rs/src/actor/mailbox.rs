@@ -0,0 +1,339 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Notify;
+
+/// How many messages an actor may have queued
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MailboxConfig {
+    /// No limit. Fine for actors whose senders are naturally rate limited.
+    #[default]
+    Unbounded,
+    /// At most `capacity` messages; `overflow` decides what happens beyond that
+    Bounded {
+        capacity: usize,
+        overflow: OverflowPolicy,
+    },
+}
+
+/// What a full bounded mailbox does with a new message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Reject the new message
+    DropNewest,
+    /// Evict the oldest queued message to make room
+    DropOldest,
+    /// Make the sender wait for room. Only `ActorRef::send_async` waits;
+    /// `ActorRef::send` rejects the message instead.
+    Block,
+}
+
+pub(crate) enum PushError<T> {
+    Full(T),
+    Closed(T),
+}
+
+struct Entry<T> {
+    item: T,
+    /// Control signals in the regular lane never count against capacity and
+    /// are never evicted
+    counted: bool,
+}
+
+struct Queue<T> {
+    entries: VecDeque<Entry<T>>,
+    /// The priority lane, always emptied before `entries`
+    urgent: VecDeque<T>,
+    counted: usize,
+    closed: bool,
+}
+
+/// A multi-producer, single-consumer queue with the overflow behavior tokio's
+/// channels lack
+pub(crate) struct Mailbox<T> {
+    queue: Mutex<Queue<T>>,
+    config: MailboxConfig,
+    /// Wakes the actor when something is queued
+    item_ready: Notify,
+    /// Wakes blocked senders when room frees up or the mailbox closes
+    space_ready: Notify,
+}
+
+impl<T> fmt::Debug for Mailbox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mailbox")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Mailbox<T> {
+    pub(crate) fn new(config: MailboxConfig) -> Arc<Self> {
+        Arc::new(Self {
+            queue: Mutex::new(Queue {
+                entries: VecDeque::new(),
+                urgent: VecDeque::new(),
+                counted: 0,
+                closed: false,
+            }),
+            config,
+            item_ready: Notify::new(),
+            space_ready: Notify::new(),
+        })
+    }
+
+    /// Queues a message, applying the overflow policy if the mailbox is full.
+    /// Returns the message evicted to make room, if any.
+    pub(crate) fn try_push(&self, item: T) -> Result<Option<T>, PushError<T>> {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.closed {
+            return Err(PushError::Closed(item));
+        }
+
+        let mut evicted = None;
+        if let MailboxConfig::Bounded { capacity, overflow } = self.config {
+            if queue.counted >= capacity {
+                match overflow {
+                    OverflowPolicy::DropNewest | OverflowPolicy::Block => {
+                        return Err(PushError::Full(item));
+                    }
+                    OverflowPolicy::DropOldest => {
+                        let oldest = queue.entries.iter().position(|entry| entry.counted);
+                        match oldest {
+                            Some(index) => {
+                                evicted = queue.entries.remove(index).map(|entry| entry.item);
+                                queue.counted -= 1;
+                            }
+                            // Only possible with a capacity of zero
+                            None => return Err(PushError::Full(item)),
+                        }
+                    }
+                }
+            }
+        }
+
+        queue.entries.push_back(Entry {
+            item,
+            counted: true,
+        });
+        queue.counted += 1;
+        drop(queue);
+
+        self.item_ready.notify_one();
+        Ok(evicted)
+    }
+
+    /// Queues a message, waiting for room if the mailbox is full
+    pub(crate) async fn push(&self, mut item: T) -> Result<Option<T>, PushError<T>> {
+        loop {
+            // Registered before trying, so room freed in between isn't missed
+            let space = self.space_ready.notified();
+            tokio::pin!(space);
+            space.as_mut().enable();
+
+            match self.try_push(item) {
+                Err(PushError::Full(rejected)) => item = rejected,
+                result => return result,
+            }
+            space.await;
+        }
+    }
+
+    /// Queues a control signal in order with messages, bypassing the capacity
+    /// limit
+    pub(crate) fn push_control(&self, item: T) -> Result<(), PushError<T>> {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.closed {
+            return Err(PushError::Closed(item));
+        }
+        queue.entries.push_back(Entry {
+            item,
+            counted: false,
+        });
+        drop(queue);
+
+        self.item_ready.notify_one();
+        Ok(())
+    }
+
+    /// Queues a control signal ahead of everything in the regular lane, so it
+    /// isn't stuck behind a deep backlog
+    pub(crate) fn push_urgent(&self, item: T) -> Result<(), PushError<T>> {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.closed {
+            return Err(PushError::Closed(item));
+        }
+        queue.urgent.push_back(item);
+        drop(queue);
+
+        self.item_ready.notify_one();
+        Ok(())
+    }
+
+    /// Takes the next item, waiting until there is one
+    pub(crate) async fn pop(&self) -> T {
+        loop {
+            if let Some(entry) = self.try_pop() {
+                return entry;
+            }
+            // Only one consumer, so a stored permit can't be stolen
+            self.item_ready.notified().await;
+        }
+    }
+
+    /// Pops the next item only if `accept` takes it, leaving it queued
+    /// otherwise
+    pub(crate) fn try_pop_if(&self, accept: impl FnOnce(&T) -> bool) -> Option<T> {
+        let next = {
+            let queue = self.queue.lock().unwrap();
+            match queue.urgent.front() {
+                Some(item) => accept(item),
+                None => queue
+                    .entries
+                    .front()
+                    .is_some_and(|entry| accept(&entry.item)),
+            }
+        };
+        // Only the consumer pops, so the front can't change in between
+        if next {
+            self.try_pop()
+        } else {
+            None
+        }
+    }
+
+    fn try_pop(&self) -> Option<T> {
+        let mut queue = self.queue.lock().unwrap();
+        if let Some(item) = queue.urgent.pop_front() {
+            return Some(item);
+        }
+
+        let entry = queue.entries.pop_front()?;
+        if entry.counted {
+            queue.counted -= 1;
+            self.space_ready.notify_one();
+        }
+        Some(entry.item)
+    }
+
+    /// Number of queued messages, not counting control signals
+    pub(crate) fn len(&self) -> usize {
+        self.queue.lock().unwrap().counted
+    }
+
+    /// Takes everything still queued, control signals included
+    pub(crate) fn drain(&self) -> Vec<T> {
+        let mut queue = self.queue.lock().unwrap();
+        queue.counted = 0;
+        let mut items: Vec<T> = queue.urgent.drain(..).collect();
+        items.extend(queue.entries.drain(..).map(|entry| entry.item));
+        items
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.queue.lock().unwrap().closed
+    }
+
+    /// Rejects all further items and wakes any blocked senders
+    pub(crate) fn close(&self) {
+        self.queue.lock().unwrap().closed = true;
+        self.space_ready.notify_waiters();
+    }
+}
+
+/// The consuming end of a mailbox. Closes it when dropped, so senders find out
+/// the actor is gone even if it panicked.
+pub(crate) struct MailboxReceiver<T>(pub(crate) Arc<Mailbox<T>>);
+
+impl<T> MailboxReceiver<T> {
+    pub(crate) async fn recv(&self) -> T {
+        self.0.pop().await
+    }
+}
+
+impl<T> Drop for MailboxReceiver<T> {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounded(capacity: usize, overflow: OverflowPolicy) -> Arc<Mailbox<u32>> {
+        Mailbox::new(MailboxConfig::Bounded { capacity, overflow })
+    }
+
+    #[tokio::test]
+    async fn test_drop_newest() {
+        let mailbox = bounded(2, OverflowPolicy::DropNewest);
+        assert!(mailbox.try_push(1).is_ok());
+        assert!(mailbox.try_push(2).is_ok());
+        assert!(matches!(mailbox.try_push(3), Err(PushError::Full(3))));
+
+        // Control signals still get through
+        assert!(mailbox.push_control(100).is_ok());
+
+        assert_eq!(mailbox.pop().await, 1);
+        assert_eq!(mailbox.pop().await, 2);
+        assert_eq!(mailbox.pop().await, 100);
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_keeps_control_signals() {
+        let mailbox = bounded(2, OverflowPolicy::DropOldest);
+        mailbox.push_control(100).ok();
+        mailbox.try_push(1).ok();
+        mailbox.try_push(2).ok();
+        assert!(matches!(mailbox.try_push(3), Ok(Some(1))));
+
+        assert_eq!(mailbox.len(), 2);
+        assert_eq!(mailbox.pop().await, 100);
+        assert_eq!(mailbox.pop().await, 2);
+        assert_eq!(mailbox.pop().await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_urgent_lane_goes_first() {
+        let mailbox = bounded(2, OverflowPolicy::DropNewest);
+        mailbox.try_push(1).ok();
+        mailbox.push_control(100).ok();
+        mailbox.try_push(2).ok();
+        mailbox.push_urgent(200).ok();
+        mailbox.push_urgent(201).ok();
+
+        for expected in [200, 201, 1, 100, 2] {
+            assert_eq!(mailbox.pop().await, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_room() {
+        let mailbox = bounded(1, OverflowPolicy::Block);
+        mailbox.try_push(1).ok();
+
+        let sender = mailbox.clone();
+        let blocked = tokio::spawn(async move { sender.push(2).await.is_ok() });
+        tokio::task::yield_now().await;
+        assert!(!blocked.is_finished());
+
+        assert_eq!(mailbox.pop().await, 1);
+        assert!(blocked.await.unwrap());
+        assert_eq!(mailbox.pop().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_close_releases_blocked_senders() {
+        let mailbox = bounded(1, OverflowPolicy::Block);
+        mailbox.try_push(1).ok();
+
+        let sender = mailbox.clone();
+        let blocked = tokio::spawn(async move { sender.push(2).await });
+        tokio::task::yield_now().await;
+
+        drop(MailboxReceiver(mailbox.clone()));
+        assert!(matches!(blocked.await.unwrap(), Err(PushError::Closed(2))));
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Counters updated by the run loop after every handled message
+#[derive(Debug)]
+pub(crate) struct ActorMetrics {
+    started: Instant,
+    processed: AtomicU64,
+    handler_nanos: AtomicU64,
+    max_handler_nanos: AtomicU64,
+    panics: AtomicU64,
+    overruns: AtomicU64,
+}
+
+impl ActorMetrics {
+    pub(crate) fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            processed: AtomicU64::new(0),
+            handler_nanos: AtomicU64::new(0),
+            max_handler_nanos: AtomicU64::new(0),
+            panics: AtomicU64::new(0),
+            overruns: AtomicU64::new(0),
+        }
+    }
+
+    /// Records one handler call that took `messages` off the mailbox
+    pub(crate) fn record(&self, messages: usize, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.processed.fetch_add(messages as u64, Ordering::Relaxed);
+        self.handler_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_handler_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_panic(&self) {
+        self.panics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_overrun(&self) {
+        self.overruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self, path: &str, queued: usize) -> ActorMetricsSnapshot {
+        let processed = self.processed.load(Ordering::Relaxed);
+        let handler_nanos = self.handler_nanos.load(Ordering::Relaxed);
+        let uptime = self.started.elapsed();
+
+        ActorMetricsSnapshot {
+            path: path.to_string(),
+            queued,
+            processed,
+            messages_per_second: processed as f64 / uptime.as_secs_f64().max(f64::EPSILON),
+            avg_handler_time: Duration::from_nanos(
+                handler_nanos.checked_div(processed).unwrap_or(0),
+            ),
+            max_handler_time: Duration::from_nanos(self.max_handler_nanos.load(Ordering::Relaxed)),
+            panics: self.panics.load(Ordering::Relaxed),
+            overruns: self.overruns.load(Ordering::Relaxed),
+            uptime,
+        }
+    }
+}
+
+/// A point-in-time view of an actor's load, from `ActorRef::metrics`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActorMetricsSnapshot {
+    /// The actor's `ActorRef::path`
+    pub path: String,
+    /// Messages waiting in the mailbox
+    pub queued: usize,
+    /// Messages handled since the actor started
+    pub processed: u64,
+    /// Average throughput since the actor started. Diff `processed` between
+    /// two snapshots for a recent rate.
+    pub messages_per_second: f64,
+    /// Handler time spread over `processed`
+    pub avg_handler_time: Duration,
+    /// Longest single handler call, which may cover a whole batch
+    pub max_handler_time: Duration,
+    /// Handler panics caught so far
+    pub panics: u64,
+    /// Handler calls that ran past the actor's `handler_deadline`
+    pub overruns: u64,
+    pub uptime: Duration,
+}
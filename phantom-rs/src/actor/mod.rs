@@ -1,21 +1,76 @@
-use log::debug;
+mod dead_letter;
+mod mailbox;
+mod metrics;
+mod registry;
+mod supervision;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
+mod timer;
+mod watch;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use log::{debug, error, warn};
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
 use std::future::Future;
+use std::ops::ControlFlow;
 use std::ops::Deref;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio::sync::mpsc;
-use tokio::task::JoinHandle;
+use tokio::sync::oneshot;
+use tokio::task::{AbortHandle, JoinHandle};
+use tracing::{debug_span, field, Instrument, Span};
 
 use crate::task::CancellableTask;
+use mailbox::{Mailbox, MailboxReceiver, PushError};
+use metrics::ActorMetrics;
+use watch::Watchers;
+
+pub use dead_letter::{DeadLetter, DeadLetterReason, DeadLetterSink};
+pub use mailbox::{MailboxConfig, OverflowPolicy};
+pub use metrics::ActorMetricsSnapshot;
+pub use registry::ActorRegistry;
+pub use supervision::{RestartPolicy, SupervisionStrategy};
+pub use timer::TimerHandle;
+pub use watch::{HandlerPanic, StopReason, Terminated};
 
 /// Trait for async behavior that can process messages by mutating state
-pub trait AsyncBehavior<Message: Send + 'static, State>: Send + Sync {
-    fn handle(
-        &self,
+pub trait AsyncBehavior<Message: Send + 'static, State: Send>: Send + Sync {
+    fn handle<'a>(
+        &'a self,
         self_ref: ActorRef<Message>,
         message: Message,
-        state: State,
-    ) -> Pin<Box<dyn Future<Output = State> + Send>>;
+        state: &'a mut State,
+    ) -> BoxFuture<'a, ()>;
+
+    /// Whether `handle_batch` should be given whole batches. Otherwise the
+    /// actor calls `handle` for each queued message itself, so a panic only
+    /// loses the message being handled.
+    fn takes_batches(&self) -> bool {
+        false
+    }
+
+    /// Handles several queued messages in one call when the actor has a
+    /// `max_batch` above 1 and `takes_batches`. Defaults to `handle` for each
+    /// in order.
+    fn handle_batch<'a>(
+        &'a self,
+        self_ref: ActorRef<Message>,
+        messages: Vec<Message>,
+        state: &'a mut State,
+    ) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            for message in messages {
+                self.handle(self_ref.clone(), message, state).await;
+            }
+        })
+    }
 }
 
 /// A simple wrapper that implements AsyncBehavior for a function
@@ -23,45 +78,228 @@ pub struct SimpleBehavior<F> {
     handler: F,
 }
 
-impl<Message, State, F, Fut> AsyncBehavior<Message, State> for SimpleBehavior<F>
+impl<Message, State, F> AsyncBehavior<Message, State> for SimpleBehavior<F>
 where
     Message: Send + 'static,
-    State: Clone + Send + Sync + 'static,
-    F: Fn(ActorRef<Message>, Message, State) -> Fut + Send + Sync + 'static,
-    Fut: Future<Output = State> + Send + 'static,
+    State: Send + 'static,
+    F: for<'a> Fn(ActorRef<Message>, Message, &'a mut State) -> BoxFuture<'a, ()>
+        + Send
+        + Sync
+        + 'static,
 {
-    fn handle(
-        &self,
+    fn handle<'a>(
+        &'a self,
         self_ref: ActorRef<Message>,
         message: Message,
-        state: State,
-    ) -> Pin<Box<dyn Future<Output = State> + Send>> {
-        Box::pin((self.handler)(self_ref, message, state))
+        state: &'a mut State,
+    ) -> BoxFuture<'a, ()> {
+        (self.handler)(self_ref, message, state)
+    }
+}
+
+/// Wraps a function that takes a batch of messages, see `batch_behavior`
+pub struct BatchBehavior<F> {
+    handler: F,
+}
+
+impl<Message, State, F> AsyncBehavior<Message, State> for BatchBehavior<F>
+where
+    Message: Send + 'static,
+    State: Send + 'static,
+    F: for<'a> Fn(ActorRef<Message>, Vec<Message>, &'a mut State) -> BoxFuture<'a, ()>
+        + Send
+        + Sync
+        + 'static,
+{
+    fn handle<'a>(
+        &'a self,
+        self_ref: ActorRef<Message>,
+        message: Message,
+        state: &'a mut State,
+    ) -> BoxFuture<'a, ()> {
+        (self.handler)(self_ref, vec![message], state)
+    }
+
+    fn takes_batches(&self) -> bool {
+        true
+    }
+
+    fn handle_batch<'a>(
+        &'a self,
+        self_ref: ActorRef<Message>,
+        messages: Vec<Message>,
+        state: &'a mut State,
+    ) -> BoxFuture<'a, ()> {
+        (self.handler)(self_ref, messages, state)
     }
 }
 
 /// The behavior function type that processes messages by mutating state asynchronously
 pub type BehaviorFn<Message, State> = Box<dyn AsyncBehavior<Message, State>>;
 
-/// Helper function to create a behavior from an async closure
-pub fn behavior<Message, State, F, Fut>(handler: F) -> BehaviorFn<Message, State>
+/// Helper function to create a behavior from a closure returning a boxed
+/// future, e.g. `behavior(|_, message, state| Box::pin(async move { ... }))`.
+/// The state is borrowed mutably for the duration of the handler, so it is
+/// never cloned.
+pub fn behavior<Message, State, F>(handler: F) -> BehaviorFn<Message, State>
 where
     Message: Send + 'static,
-    State: Clone + Send + Sync + 'static,
-    F: Fn(ActorRef<Message>, Message, State) -> Fut + Send + Sync + 'static,
-    Fut: Future<Output = State> + Send + 'static,
+    State: Send + 'static,
+    F: for<'a> Fn(ActorRef<Message>, Message, &'a mut State) -> BoxFuture<'a, ()>
+        + Send
+        + Sync
+        + 'static,
 {
     Box::new(SimpleBehavior { handler })
 }
-pub struct Actor<Message: Send + 'static, State: Clone + Send + 'static> {
+
+/// Like `behavior`, but the closure is given every message taken in one
+/// wakeup, up to the actor's `max_batch`. Lets a busy actor pay for its
+/// per-call setup once per batch instead of once per message.
+pub fn batch_behavior<Message, State, F>(handler: F) -> BehaviorFn<Message, State>
+where
+    Message: Send + 'static,
+    State: Send + 'static,
+    F: for<'a> Fn(ActorRef<Message>, Vec<Message>, &'a mut State) -> BoxFuture<'a, ()>
+        + Send
+        + Sync
+        + 'static,
+{
+    Box::new(BatchBehavior { handler })
+}
+pub struct Actor<Message: Send + 'static, State: Send + 'static> {
     behavior: BehaviorFn<Message, State>,
-    sender: mpsc::UnboundedSender<ActorSignal<Message>>,
-    receiver: mpsc::UnboundedReceiver<ActorSignal<Message>>,
+    on_panic: PanicPolicy,
+    describe_messages: Option<MessageFormatter>,
+    max_batch: usize,
+    handler_deadline: Option<Duration>,
+    actor_ref: ActorRef<Message>,
+    receiver: MailboxReceiver<ActorSignal<Message>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct ActorRef<Message: Send + 'static> {
-    sender: mpsc::UnboundedSender<ActorSignal<Message>>,
+    cell: Arc<ActorCell<Message>>,
+}
+
+impl<Message: Send + 'static> Clone for ActorRef<Message> {
+    fn clone(&self) -> Self {
+        Self {
+            cell: self.cell.clone(),
+        }
+    }
+}
+
+/// An `ActorRef` that doesn't keep the actor's mailbox alive, for children
+/// that need to reach back to their parent without forming a cycle
+#[derive(Debug)]
+pub struct WeakActorRef<Message: Send + 'static> {
+    cell: Weak<ActorCell<Message>>,
+}
+
+impl<Message: Send + 'static> WeakActorRef<Message> {
+    /// The actor, if it is still running
+    pub fn upgrade(&self) -> Option<ActorRef<Message>> {
+        let actor = ActorRef {
+            cell: self.cell.upgrade()?,
+        };
+        (!actor.is_stopped()).then_some(actor)
+    }
+}
+
+impl<Message: Send + 'static> Clone for WeakActorRef<Message> {
+    fn clone(&self) -> Self {
+        Self {
+            cell: self.cell.clone(),
+        }
+    }
+}
+
+/// Everything an actor shares with its refs
+#[derive(Debug)]
+struct ActorCell<Message: Send + 'static> {
+    mailbox: Arc<Mailbox<ActorSignal<Message>>>,
+    name: Option<String>,
+    dead_letters: DeadLetterSink,
+    metrics: ActorMetrics,
+    /// Set by `stop_graceful`: when to give up on the queued messages
+    stop_deadline: Mutex<Option<Instant>>,
+    next_child_id: AtomicU64,
+    id: u64,
+    path: String,
+    watchers: Mutex<Watchers>,
+}
+
+/// Source of `ActorRef::id`, unique across the process
+static NEXT_ACTOR_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Spawn options for `Actor::run_with_config`
+#[derive(Debug, Clone, Default)]
+pub struct ActorConfig {
+    /// Shown in logs and used as the default registry name
+    pub name: Option<String>,
+    /// Path of the actor this one belongs under, e.g. "proxy". Set by
+    /// `run_child`.
+    pub parent_path: Option<String>,
+    pub mailbox: MailboxConfig,
+    /// Receives messages that are rejected, evicted or left queued at shutdown
+    pub dead_letters: DeadLetterSink,
+    pub on_panic: PanicPolicy,
+    /// Formats messages for panic logs, dead letters and `HandlerPanic`s.
+    /// Off by default, since every message is formatted before it's handled
+    /// in case the handler panics.
+    pub describe_messages: Option<MessageFormatter>,
+    /// Most queued messages taken per wakeup, and handed to
+    /// `AsyncBehavior::handle_batch` if the behavior `takes_batches`. 0 and 1
+    /// take them one at a time.
+    pub max_batch: usize,
+    /// How long a handler may run before a warning is logged. It is left to
+    /// finish either way.
+    pub handler_deadline: Option<Duration>,
+}
+
+/// What an actor does when its handler panics. Either way the message goes to
+/// the dead letter sink and panic watchers are told. With `Stop`, the rest of
+/// the batch goes to the sink as well, as `DeadLetterReason::Stopped`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicPolicy {
+    /// Drop the message and carry on. The state is not rolled back: it is
+    /// whatever the handler left it as, possibly partway through an update.
+    #[default]
+    Continue,
+    /// Shut down, as if `shutdown` had been called. Run the actor under
+    /// `supervise_child` to restart it with fresh state.
+    Stop,
+}
+
+/// Turns an actor's messages into text, see `ActorConfig::describe_messages`.
+/// Type-erased so one `ActorConfig` type serves every actor.
+#[derive(Clone)]
+pub struct MessageFormatter(Arc<Describe>);
+
+/// Formats a message given as `Any`, or `None` if it's not the expected type
+type Describe = dyn Fn(&dyn Any) -> Option<String> + Send + Sync;
+
+impl MessageFormatter {
+    /// Formats messages of type `Message` with their `Debug` impl
+    pub fn debug<Message: fmt::Debug + 'static>() -> Self {
+        Self(Arc::new(|message| {
+            message
+                .downcast_ref::<Message>()
+                .map(|message| format!("{:?}", message))
+        }))
+    }
+
+    /// Falls back to the type name for messages it wasn't made for
+    fn describe<Message: 'static>(&self, message: &Message) -> String {
+        (self.0)(message).unwrap_or_else(|| std::any::type_name::<Message>().to_string())
+    }
+}
+
+impl fmt::Debug for MessageFormatter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MessageFormatter")
+    }
 }
 
 #[derive(Debug, Error)]
@@ -71,50 +309,346 @@ pub enum ActorError {
 
     #[error("Failed to send message: {0}")]
     FailedToSend(String),
+
+    #[error("Actor mailbox is full")]
+    MailboxFull,
+
+    #[error("Actor dropped the request without replying")]
+    NoReply,
+
+    #[error("An actor named {0} is already registered")]
+    NameTaken(String),
+
+    #[error("Actor has no name to register under")]
+    Unnamed,
+
+    #[error("Actor state is not a {0}")]
+    WrongStateType(&'static str),
+}
+
+/// The answering end of an `ActorRef::ask`, carried inside the request message
+#[derive(Debug)]
+pub struct Reply<R>(oneshot::Sender<R>);
+
+impl<R> Reply<R> {
+    /// Answers the request. A caller that gave up waiting is ignored.
+    pub fn send(self, value: R) {
+        let _ = self.0.send(value);
+    }
 }
 
 impl<Message: Send + 'static> ActorRef<Message> {
+    /// Queues a message without waiting. Fails with `MailboxFull` if a bounded
+    /// mailbox has no room and its policy doesn't evict.
     pub fn send(&self, message: Message) -> Result<(), ActorError> {
-        self.sender
-            .send(ActorSignal::Message(message))
-            .map_err(|e| ActorError::FailedToSend(e.to_string()))
+        let result = self
+            .cell
+            .mailbox
+            .try_push(ActorSignal::Message(message, Span::current()));
+        self.settle(result)
+    }
+
+    /// Queues a message, waiting for room if the mailbox is bounded with
+    /// `OverflowPolicy::Block`. Otherwise the same as `send`.
+    pub async fn send_async(&self, message: Message) -> Result<(), ActorError> {
+        let result = self
+            .cell
+            .mailbox
+            .push(ActorSignal::Message(message, Span::current()))
+            .await;
+        self.settle(result)
+    }
+
+    /// Turns a push result into the caller's result, handing whatever didn't
+    /// make it into the mailbox to the dead letter sink
+    fn settle(
+        &self,
+        result: Result<Option<ActorSignal<Message>>, PushError<ActorSignal<Message>>>,
+    ) -> Result<(), ActorError> {
+        match result {
+            Ok(None) => Ok(()),
+            Ok(Some(evicted)) => {
+                self.dead_letter(evicted, DeadLetterReason::Evicted);
+                Ok(())
+            }
+            Err(PushError::Full(rejected)) => {
+                self.dead_letter(rejected, DeadLetterReason::MailboxFull);
+                Err(ActorError::MailboxFull)
+            }
+            Err(PushError::Closed(rejected)) => {
+                self.dead_letter(rejected, DeadLetterReason::Stopped);
+                Err(ActorError::FailedToSend("actor has stopped".to_string()))
+            }
+        }
+    }
+
+    fn dead_letter(&self, signal: ActorSignal<Message>, reason: DeadLetterReason) {
+        if let ActorSignal::Message(message, _) = signal {
+            self.cell.dead_letters.deliver(DeadLetter {
+                actor: self.cell.name.clone(),
+                message_type: std::any::type_name::<Message>(),
+                reason,
+                message: Some(Box::new(message)),
+                description: None,
+            });
+        }
     }
 
+    /// Sends a request built around a `Reply` and waits for the actor to answer
+    /// it, e.g. `actor.ask(|reply| Message::GetCount { reply })`
+    pub async fn ask<R>(&self, request: impl FnOnce(Reply<R>) -> Message) -> Result<R, ActorError> {
+        let (sender, receiver) = oneshot::channel();
+        self.send_async(request(Reply(sender))).await?;
+        receiver.await.map_err(|_| ActorError::NoReply)
+    }
+
+    /// A clone of the actor's current state. `State` must be the type the
+    /// actor was started with.
+    pub async fn snapshot<State>(&self) -> Result<State, ActorError>
+    where
+        State: Clone + Send + 'static,
+    {
+        self.inspect(State::clone).await
+    }
+
+    /// Runs `project` against the actor's current state between messages and
+    /// returns what it produces, for when a full clone would be wasteful
+    pub async fn inspect<State, R>(
+        &self,
+        project: impl FnOnce(&State) -> R + Send + 'static,
+    ) -> Result<R, ActorError>
+    where
+        State: 'static,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let inspect: Inspector = Box::new(move |state| {
+            let result = state
+                .downcast_ref::<State>()
+                .map(project)
+                .ok_or(ActorError::WrongStateType(std::any::type_name::<State>()));
+            let _ = sender.send(result);
+        });
+
+        self.cell
+            .mailbox
+            .push_control(ActorSignal::Inspect(inspect))
+            .map_err(|_| ActorError::FailedToSend("actor has stopped".to_string()))?;
+        receiver.await.map_err(|_| ActorError::NoReply)?
+    }
+
+    /// Number of messages waiting to be handled
+    pub fn queued(&self) -> usize {
+        self.cell.mailbox.len()
+    }
+
+    /// Mailbox depth, throughput and handler timings so far
+    pub fn metrics(&self) -> ActorMetricsSnapshot {
+        self.cell.metrics.snapshot(self.path(), self.queued())
+    }
+
+    /// The name the actor was spawned with, if any
+    pub fn name(&self) -> Option<&str> {
+        self.cell.name.as_deref()
+    }
+
+    /// A reference that doesn't keep this actor alive
+    pub fn downgrade(&self) -> WeakActorRef<Message> {
+        WeakActorRef {
+            cell: Arc::downgrade(&self.cell),
+        }
+    }
+
+    /// Process-unique id, assigned at spawn
+    pub fn id(&self) -> u64 {
+        self.cell.id
+    }
+
+    /// Where the actor sits in the hierarchy, e.g. "proxy/router". The last
+    /// segment is its name, or "actor-<id>" if it has none.
+    pub fn path(&self) -> &str {
+        &self.cell.path
+    }
+
+    /// Whether the actor has stopped taking messages
+    pub fn is_stopped(&self) -> bool {
+        self.cell.mailbox.is_closed()
+    }
+
+    /// Stops the actor after the message it is handling, however many are
+    /// queued. Those go to the dead letter sink.
     pub fn shutdown(&self) {
-        let _ = self.sender.send(ActorSignal::Shutdown);
+        self.stop_with(StopReason::Shutdown);
+    }
+
+    /// `shutdown`, reporting `reason` to watchers
+    pub(crate) fn stop_with(&self, reason: StopReason) {
+        let _ = self.cell.mailbox.push_urgent(ActorSignal::Shutdown(reason));
+    }
+
+    /// Stops taking new messages but handles everything already queued before
+    /// shutting down children. Whatever is still queued once `timeout` has
+    /// passed goes to the dead letter sink; `None` waits for all of it.
+    pub fn stop_graceful(&self, timeout: Option<Duration>) {
+        *self.cell.stop_deadline.lock().unwrap() = timeout.map(|timeout| Instant::now() + timeout);
+        // In the regular lane, so it lands behind everything already queued
+        let _ = self
+            .cell
+            .mailbox
+            .push_control(ActorSignal::Shutdown(StopReason::MailboxClosed));
+        self.cell.mailbox.close();
     }
 
     // Create a new Actor and attach it as a child by sending a message to the parent
-    pub fn run_child<State>(&self, initial_state: State, behavior: BehaviorFn<Message, State>)
+    pub fn run_child<State>(
+        &self,
+        initial_state: State,
+        behavior: BehaviorFn<Message, State>,
+    ) -> ChildId
     where
-        State: Send + Clone + 'static,
+        State: Send + 'static,
     {
-        let child_actor = Actor::run(initial_state, behavior);
-        self.attach_child(child_actor);
+        self.run_child_with_config(initial_state, behavior, ActorConfig::default())
     }
 
-    pub fn attach_child(&self, child: impl CancellableTask) {
-        self.sender
-            .send(ActorSignal::SpawnChild(Box::new(child)))
-            .unwrap_or_else(|e| {
-                debug!("[actor] failed to attach child task: {}", e);
-            });
+    /// Like `run_child`, with options from `config`. The child's path is
+    /// placed under this actor's.
+    pub fn run_child_with_config<ChildMessage, State>(
+        &self,
+        initial_state: State,
+        behavior: BehaviorFn<ChildMessage, State>,
+        config: ActorConfig,
+    ) -> ChildId
+    where
+        ChildMessage: Send + 'static,
+        State: Send + 'static,
+    {
+        let config = ActorConfig {
+            parent_path: Some(self.path().to_string()),
+            ..config
+        };
+        let child_actor = Actor::run_with_config(initial_state, behavior, config);
+        self.attach_child(child_actor)
+    }
+
+    /// Hands `child` to this actor, which stops it when shutting down. The
+    /// returned id can stop or release it earlier.
+    pub fn attach_child(&self, child: impl CancellableTask) -> ChildId {
+        let id = ChildId(self.cell.next_child_id.fetch_add(1, Ordering::Relaxed));
+        if self
+            .cell
+            .mailbox
+            .push_urgent(ActorSignal::SpawnChild(id, Box::new(child)))
+            .is_err()
+        {
+            debug!(
+                "[actor {}] failed to attach child task: actor has stopped",
+                self.path()
+            );
+            return id;
+        }
+
+        debug!("[actor {}] child task attached successfully", self.path());
+        id
+    }
+
+    /// Cancels a child now instead of at shutdown. Unknown ids are ignored.
+    pub fn cancel_child(&self, id: ChildId) {
+        let _ = self.cell.mailbox.push_urgent(ActorSignal::CancelChild(id));
+    }
 
-        debug!("[actor] child task attached successfully");
+    /// Lets a child keep running on its own; the actor forgets it
+    pub fn detach_child(&self, id: ChildId) {
+        let _ = self.cell.mailbox.push_urgent(ActorSignal::DetachChild(id));
+    }
+
+    /// Sends a message built by `message` to every child that is an actor
+    /// taking `ChildMessage`, e.g. to have them all flush or pause. Other
+    /// children are skipped.
+    pub fn broadcast_to_children<ChildMessage: Send + 'static>(
+        &self,
+        message: impl Fn() -> ChildMessage + Send + 'static,
+    ) {
+        let deliver: Broadcast = Box::new(move |child| {
+            if let Some(child) = child.downcast_ref::<RunningActor<ChildMessage>>() {
+                let _ = child.send(message());
+            }
+        });
+        let _ = self
+            .cell
+            .mailbox
+            .push_urgent(ActorSignal::Broadcast(deliver));
     }
 }
 
-struct ActorInternalState<State: Clone + Send + 'static> {
-    children: Vec<Box<dyn CancellableTask>>,
+/// Identifies a child attached to an actor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChildId(u64);
+
+struct ActorInternalState<State: Send + 'static> {
+    children: Vec<(ChildId, Box<dyn CancellableTask>)>,
     state: State,
 }
 
+impl<State: Send + 'static> ActorInternalState<State> {
+    fn take_child(&mut self, id: ChildId) -> Option<Box<dyn CancellableTask>> {
+        let index = self
+            .children
+            .iter()
+            .position(|(child_id, _)| *child_id == id)?;
+        Some(self.children.remove(index).1)
+    }
+
+    /// Applies a child signal. Returns the signal back if it's something else.
+    fn apply_child_signal<Message: Send + 'static>(
+        &mut self,
+        signal: ActorSignal<Message>,
+    ) -> Option<ActorSignal<Message>> {
+        match signal {
+            ActorSignal::SpawnChild(id, child) => {
+                // Timers and other short-lived children are dropped once they
+                // finish, so re-arming one doesn't pile up dead entries
+                self.children.retain(|(_, child)| !child.is_finished());
+                self.children.push((id, child));
+            }
+            ActorSignal::CancelChild(id) => {
+                if let Some(child) = self.take_child(id) {
+                    child.cancel();
+                    // Don't hold up the mailbox while it winds down
+                    tokio::spawn(child.join());
+                }
+            }
+            ActorSignal::DetachChild(id) => {
+                self.take_child(id);
+            }
+            ActorSignal::Broadcast(deliver) => {
+                for child in self.children.iter().filter_map(|(_, child)| child.as_any()) {
+                    deliver(child);
+                }
+            }
+            signal => return Some(signal),
+        }
+        None
+    }
+}
+
 enum ActorSignal<Message: Send + 'static> {
-    Message(Message),
-    SpawnChild(Box<dyn CancellableTask>),
-    Shutdown,
+    /// A message and the span it was sent from
+    Message(Message, Span),
+    SpawnChild(ChildId, Box<dyn CancellableTask>),
+    CancelChild(ChildId),
+    DetachChild(ChildId),
+    Inspect(Inspector),
+    Broadcast(Broadcast),
+    Shutdown(StopReason),
 }
 
+/// Type-erased `ActorRef::broadcast_to_children` request, called once per child
+type Broadcast = Box<dyn Fn(&dyn Any) + Send>;
+
+/// Type-erased `ActorRef::inspect` request, given the state as `Any`
+type Inspector = Box<dyn FnOnce(&dyn Any) + Send>;
+
 pub struct RunningActor<Message: Send + 'static> {
     actor_ref: ActorRef<Message>,
     join_handle: JoinHandle<()>,
@@ -133,6 +667,18 @@ impl<Message: Send + 'static> CancellableTask for RunningActor<Message> {
         self.actor_ref.shutdown();
     }
 
+    fn is_finished(&self) -> bool {
+        self.join_handle.is_finished()
+    }
+
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        Some(self.join_handle.abort_handle())
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+
     fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
         Box::pin(async move {
             let _ = self.join_handle.await;
@@ -140,22 +686,67 @@ impl<Message: Send + 'static> CancellableTask for RunningActor<Message> {
     }
 }
 
-impl<Message: Send + 'static, State: Clone + Send + 'static> Actor<Message, State> {
+impl<Message: Send + 'static, State: Send + 'static> Actor<Message, State> {
     /// Create a new Actor with initial state and behavior
     pub fn run(
         initial_state: State,
         behavior: BehaviorFn<Message, State>,
     ) -> RunningActor<Message> {
-        let (sender, receiver) = mpsc::unbounded_channel();
+        Self::run_with_mailbox(initial_state, behavior, MailboxConfig::Unbounded)
+    }
 
-        let actor = Self {
-            behavior,
-            sender,
-            receiver,
+    /// Like `run`, with a bounded mailbox if `mailbox` asks for one
+    pub fn run_with_mailbox(
+        initial_state: State,
+        behavior: BehaviorFn<Message, State>,
+        mailbox: MailboxConfig,
+    ) -> RunningActor<Message> {
+        let config = ActorConfig {
+            mailbox,
+            ..ActorConfig::default()
+        };
+        Self::run_with_config(initial_state, behavior, config)
+    }
+
+    /// Like `run`, with a name and mailbox from `config`
+    pub fn run_with_config(
+        initial_state: State,
+        behavior: BehaviorFn<Message, State>,
+        config: ActorConfig,
+    ) -> RunningActor<Message> {
+        let mailbox = Mailbox::new(config.mailbox);
+        let id = NEXT_ACTOR_ID.fetch_add(1, Ordering::Relaxed);
+        let segment = match &config.name {
+            Some(name) => name.clone(),
+            None => format!("actor-{}", id),
+        };
+        let path = match config.parent_path {
+            Some(parent) => format!("{}/{}", parent, segment),
+            None => segment,
         };
 
         let actor_ref = ActorRef {
-            sender: actor.sender.clone(),
+            cell: Arc::new(ActorCell {
+                mailbox: mailbox.clone(),
+                name: config.name,
+                dead_letters: config.dead_letters,
+                metrics: ActorMetrics::new(),
+                stop_deadline: Mutex::new(None),
+                next_child_id: AtomicU64::new(0),
+                id,
+                path,
+                watchers: Mutex::new(Watchers::default()),
+            }),
+        };
+
+        let actor = Self {
+            behavior,
+            on_panic: config.on_panic,
+            describe_messages: config.describe_messages,
+            max_batch: config.max_batch,
+            handler_deadline: config.handler_deadline,
+            receiver: MailboxReceiver(mailbox),
+            actor_ref: actor_ref.clone(),
         };
 
         let join_handle = tokio::spawn(async move {
@@ -168,31 +759,99 @@ impl<Message: Send + 'static, State: Clone + Send + 'static> Actor<Message, Stat
         }
     }
 
-    /// Process one message from the channel, waiting if necessary
-    async fn process_one(&mut self, internal_state: &mut ActorInternalState<State>) -> bool {
+    /// Process one message from the channel, waiting if necessary. Breaks
+    /// with the reason the actor should stop.
+    async fn process_one(
+        &mut self,
+        internal_state: &mut ActorInternalState<State>,
+    ) -> ControlFlow<StopReason> {
         let incoming = self.receiver.recv().await;
         match incoming {
-            Some(ActorSignal::Message(message)) => {
-                let new_state = self
-                    .behavior
-                    .handle(
-                        ActorRef {
-                            sender: self.sender.clone(),
-                        },
-                        message,
-                        internal_state.state.clone(),
-                    )
-                    .await;
-                internal_state.state = new_state;
-                true
+            ActorSignal::Message(message, sender_span) if self.past_stop_deadline() => {
+                debug!("[actor {}] ran out of time to drain mailbox", self.label());
+                self.actor_ref.dead_letter(
+                    ActorSignal::Message(message, sender_span),
+                    DeadLetterReason::Stopped,
+                );
+                ControlFlow::Break(StopReason::MailboxClosed)
+            }
+            ActorSignal::Message(message, sender_span) => {
+                // Nested under whatever span the sender was in
+                let span = debug_span!(
+                    parent: &sender_span,
+                    "actor_message",
+                    actor = self.label(),
+                    actor_id = self.actor_ref.id(),
+                    message = std::any::type_name::<Message>(),
+                    batch = field::Empty,
+                    duration_us = field::Empty,
+                );
+                let mut queue: VecDeque<Message> =
+                    std::iter::once(message).chain(self.take_batch()).collect();
+                let count = queue.len();
+                if count > 1 {
+                    span.record("batch", count);
+                }
+                // Taken now, since the handler consumes the messages
+                let descriptions: Option<Vec<String>> =
+                    self.describe_messages.as_ref().map(|formatter| {
+                        queue
+                            .iter()
+                            .map(|message| formatter.describe(message))
+                            .collect()
+                    });
+                let describe = |index: usize| descriptions.as_ref().map(|d| d[index].clone());
+                let started = Instant::now();
+
+                let flow = loop {
+                    let first = count - queue.len();
+                    let Err(panic) = self
+                        .run_handler(&mut queue, &span, &mut internal_state.state)
+                        .await
+                    else {
+                        break ControlFlow::Continue(());
+                    };
+
+                    // A batch handler took everything left; otherwise only the
+                    // message it was on is lost
+                    let taken = count - queue.len();
+                    let lost = if queue.is_empty() && taken - first > 1 {
+                        first..taken
+                    } else {
+                        taken - 1..taken
+                    };
+                    let flow = self.recover_from_panic(panic, lost.map(describe).collect());
+                    if flow.is_break() {
+                        for (index, message) in (taken..count).zip(queue.drain(..)) {
+                            self.actor_ref.cell.dead_letters.deliver(DeadLetter {
+                                actor: self.actor_ref.cell.name.clone(),
+                                message_type: std::any::type_name::<Message>(),
+                                reason: DeadLetterReason::Stopped,
+                                message: Some(Box::new(message)),
+                                description: describe(index),
+                            });
+                        }
+                    }
+                    if flow.is_break() || queue.is_empty() {
+                        break flow;
+                    }
+                };
+
+                let elapsed = started.elapsed();
+                span.record("duration_us", elapsed.as_micros() as u64);
+                self.actor_ref.cell.metrics.record(count, elapsed);
+                flow
+            }
+            ActorSignal::Inspect(inspect) => {
+                inspect(&internal_state.state);
+                ControlFlow::Continue(())
             }
-            Some(ActorSignal::SpawnChild(child_task)) => {
-                debug!("[actor] spawning child task");
-                internal_state.children.push(child_task);
-                true
+            ActorSignal::Shutdown(reason) => ControlFlow::Break(reason),
+            signal => {
+                debug!("[actor {}] updating children", self.label());
+                internal_state.apply_child_signal(signal);
+                ControlFlow::Continue(())
             }
-            Some(ActorSignal::Shutdown) => false,
-            None => false,
         }
     }
 
@@ -203,14 +862,568 @@ impl<Message: Send + 'static, State: Clone + Send + 'static> Actor<Message, Stat
             children: Vec::new(),
         };
 
-        while self.process_one(&mut state).await {}
-        debug!("[actor] shutting down children");
+        let reason = loop {
+            if let ControlFlow::Break(reason) = self.process_one(&mut state).await {
+                break reason;
+            }
+        };
+        self.actor_ref.cell.mailbox.close();
 
-        for child in state.children {
+        for signal in self.actor_ref.cell.mailbox.drain() {
+            // Children attached after the shutdown was queued are still ours
+            // to stop
+            if let Some(signal) = state.apply_child_signal(signal) {
+                self.actor_ref
+                    .dead_letter(signal, DeadLetterReason::Stopped);
+            }
+        }
+        debug!("[actor {}] shutting down children", self.label());
+
+        for (_, child) in state.children {
             child.cancel();
             child.join().await;
         }
 
-        debug!("[actor] shut down gracefully");
+        debug!("[actor {}] stopped: {:?}", self.label(), reason);
+        self.actor_ref
+            .cell
+            .watchers
+            .lock()
+            .unwrap()
+            .notify(Terminated {
+                id: self.actor_ref.id(),
+                path: self.label().to_string(),
+                reason,
+            });
+    }
+
+    /// Hands the queued messages to the behavior: all at once if it takes
+    /// batches, otherwise one by one, so that after a panic `queue` still
+    /// holds the ones it never got to
+    async fn run_handler(
+        &self,
+        queue: &mut VecDeque<Message>,
+        span: &Span,
+        state: &mut State,
+    ) -> Result<(), Box<dyn Any + Send>> {
+        // The handler is called inside the future so a panic while building
+        // it is caught too
+        let behavior = &self.behavior;
+        let self_ref = &self.actor_ref;
+        let handled = AssertUnwindSafe(async move {
+            if queue.len() > 1 && behavior.takes_batches() {
+                let messages = queue.drain(..).collect();
+                behavior
+                    .handle_batch(self_ref.clone(), messages, state)
+                    .await;
+            } else {
+                while let Some(message) = queue.pop_front() {
+                    behavior.handle(self_ref.clone(), message, state).await;
+                }
+            }
+        })
+        .catch_unwind()
+        .instrument(span.clone());
+
+        let Some(deadline) = self.handler_deadline else {
+            return handled.await;
+        };
+        let mut handled = std::pin::pin!(handled);
+        match tokio::time::timeout(deadline, &mut handled).await {
+            Ok(handled) => handled,
+            Err(_) => {
+                warn!(
+                    "[actor {}] {} handler still running after {:?}",
+                    self.label(),
+                    std::any::type_name::<Message>(),
+                    deadline
+                );
+                self.actor_ref.cell.metrics.record_overrun();
+                handled.await
+            }
+        }
+    }
+
+    /// Reports the panic and applies the panic policy. `lost` describes the
+    /// messages the handler had taken, if the actor has a `MessageFormatter`.
+    /// Breaks if the actor should stop.
+    fn recover_from_panic(
+        &self,
+        panic: Box<dyn Any + Send>,
+        lost: Vec<Option<String>>,
+    ) -> ControlFlow<StopReason> {
+        let reason = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        let messages: Vec<String> = lost
+            .iter()
+            .map(|description| {
+                description
+                    .clone()
+                    .unwrap_or_else(|| std::any::type_name::<Message>().to_string())
+            })
+            .collect();
+        error!(
+            "[actor {}] handler panicked on {}: {} ({:?})",
+            self.label(),
+            messages.join(", "),
+            reason,
+            self.on_panic
+        );
+        self.actor_ref.cell.metrics.record_panic();
+
+        for description in lost {
+            self.actor_ref.cell.dead_letters.deliver(DeadLetter {
+                actor: self.actor_ref.cell.name.clone(),
+                message_type: std::any::type_name::<Message>(),
+                reason: DeadLetterReason::HandlerPanicked,
+                message: None,
+                description,
+            });
+        }
+
+        let watchers = self
+            .actor_ref
+            .cell
+            .watchers
+            .lock()
+            .unwrap()
+            .panic_watchers();
+        let panic = HandlerPanic {
+            id: self.actor_ref.id(),
+            path: self.label().to_string(),
+            reason: reason.to_string(),
+            messages,
+            policy: self.on_panic,
+        };
+        for notify in watchers {
+            notify(panic.clone());
+        }
+
+        match self.on_panic {
+            PanicPolicy::Continue => ControlFlow::Continue(()),
+            PanicPolicy::Stop => ControlFlow::Break(StopReason::Panicked(reason.to_string())),
+        }
+    }
+
+    /// Takes queued messages to handle along with the one just received, up
+    /// to `max_batch` in all. Stops at the first signal so it is still
+    /// handled in order.
+    fn take_batch(&self) -> Vec<Message> {
+        let mut batch = Vec::new();
+        while batch.len() + 1 < self.max_batch && !self.past_stop_deadline() {
+            let next = self
+                .receiver
+                .0
+                .try_pop_if(|signal| matches!(signal, ActorSignal::Message(..)));
+            match next {
+                Some(ActorSignal::Message(message, _)) => batch.push(message),
+                _ => break,
+            }
+        }
+        batch
+    }
+
+    fn past_stop_deadline(&self) -> bool {
+        let deadline = *self.actor_ref.cell.stop_deadline.lock().unwrap();
+        deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn label(&self) -> &str {
+        self.actor_ref.path()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    enum CounterMessage {
+        Add(u32),
+        Get(Reply<u32>),
+        Ignore(#[allow(dead_code)] Reply<u32>),
+        Panic,
+    }
+
+    fn counter() -> RunningActor<CounterMessage> {
+        counter_with_config(ActorConfig::default())
+    }
+
+    fn counter_with_config(config: ActorConfig) -> RunningActor<CounterMessage> {
+        Actor::run_with_config(
+            0u32,
+            behavior(|_, message, count: &mut u32| {
+                Box::pin(async move {
+                    match message {
+                        CounterMessage::Add(n) => *count += n,
+                        CounterMessage::Get(reply) => reply.send(*count),
+                        CounterMessage::Ignore(_) => {}
+                        CounterMessage::Panic => panic!("counter panicked"),
+                    }
+                })
+            }),
+            config,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_ask() {
+        let actor = counter();
+        actor.send(CounterMessage::Add(2)).unwrap();
+        actor.send(CounterMessage::Add(3)).unwrap();
+
+        assert_eq!(actor.ask(CounterMessage::Get).await.unwrap(), 5);
+        assert!(matches!(
+            actor.ask(CounterMessage::Ignore).await,
+            Err(ActorError::NoReply)
+        ));
+
+        let metrics = actor.metrics();
+        assert_eq!(metrics.processed, 4);
+        assert_eq!(metrics.queued, 0);
+        assert!(metrics.max_handler_time >= metrics.avg_handler_time);
+
+        actor.shutdown();
+        Box::new(actor).join().await;
+    }
+
+    #[tokio::test]
+    async fn test_dead_letters() {
+        let (sink, mut dead_letters) = DeadLetterSink::channel();
+        let actor = counter_with_config(ActorConfig {
+            name: Some("counter".to_string()),
+            dead_letters: sink,
+            ..ActorConfig::default()
+        });
+
+        // Shutdown skips ahead of queued messages, so this is never handled
+        actor.send(CounterMessage::Add(1)).unwrap();
+        actor.shutdown();
+        let actor_ref = (*actor).clone();
+        Box::new(actor).join().await;
+
+        assert!(actor_ref.send(CounterMessage::Add(2)).is_err());
+
+        for expected in [1, 2] {
+            let letter = dead_letters.recv().await.unwrap();
+            assert_eq!(letter.actor.as_deref(), Some("counter"));
+            assert_eq!(letter.reason, DeadLetterReason::Stopped);
+            assert!(matches!(
+                letter.message.as_ref().and_then(|m| m.downcast_ref::<CounterMessage>()),
+                Some(CounterMessage::Add(n)) if *n == expected
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop_graceful() {
+        for (timeout, expected_dead) in [(None, vec![3]), (Some(Duration::ZERO), vec![1, 2, 3])] {
+            let (sink, mut dead_letters) = DeadLetterSink::channel();
+            let actor = counter_with_config(ActorConfig {
+                dead_letters: sink,
+                ..ActorConfig::default()
+            });
+
+            actor.send(CounterMessage::Add(1)).unwrap();
+            actor.send(CounterMessage::Add(2)).unwrap();
+            actor.stop_graceful(timeout);
+            assert!(actor.send(CounterMessage::Add(3)).is_err());
+            Box::new(actor).join().await;
+
+            let mut dead = Vec::new();
+            while let Ok(letter) = dead_letters.try_recv() {
+                if let Some(CounterMessage::Add(n)) =
+                    letter.message.as_ref().and_then(|m| m.downcast_ref())
+                {
+                    dead.push(*n);
+                }
+            }
+            dead.sort();
+            assert_eq!(dead, expected_dead);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_and_detach_child() {
+        use crate::task::TokioTask;
+        use std::sync::atomic::AtomicBool;
+
+        // Flags the child as stopped when its future is dropped
+        struct StopFlag(Arc<AtomicBool>);
+
+        impl Drop for StopFlag {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        fn child(stopped: &Arc<AtomicBool>) -> TokioTask {
+            let flag = StopFlag(stopped.clone());
+            TokioTask::spawn(|_| async move {
+                let _flag = flag;
+                std::future::pending::<()>().await;
+            })
+        }
+
+        let actor = counter();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let detached = Arc::new(AtomicBool::new(false));
+        let kept = Arc::new(AtomicBool::new(false));
+
+        let cancelled_id = actor.attach_child(child(&cancelled));
+        let detached_id = actor.attach_child(child(&detached));
+        actor.attach_child(child(&kept));
+        assert_ne!(cancelled_id, detached_id);
+
+        actor.cancel_child(cancelled_id);
+        actor.detach_child(detached_id);
+        // Signals are handled in order, so this returns after both
+        actor.ask(CounterMessage::Get).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(cancelled.load(Ordering::SeqCst));
+        assert!(!kept.load(Ordering::SeqCst));
+
+        actor.shutdown();
+        Box::new(actor).join().await;
+        assert!(kept.load(Ordering::SeqCst));
+        assert!(!detached.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_panic_policy() {
+        let (sink, mut dead_letters) = DeadLetterSink::channel();
+        let actor = counter_with_config(ActorConfig {
+            on_panic: PanicPolicy::Continue,
+            dead_letters: sink,
+            describe_messages: Some(MessageFormatter::debug::<CounterMessage>()),
+            max_batch: 8,
+            ..ActorConfig::default()
+        });
+        let mut panics = actor.watch_panics();
+        // Handled as one batch; the messages around the panic still count
+        actor.send(CounterMessage::Add(2)).unwrap();
+        actor.send(CounterMessage::Panic).unwrap();
+        actor.send(CounterMessage::Add(3)).unwrap();
+        assert_eq!(actor.ask(CounterMessage::Get).await.unwrap(), 5);
+        assert_eq!(actor.metrics().panics, 1);
+
+        let letter = dead_letters.recv().await.unwrap();
+        assert_eq!(letter.reason, DeadLetterReason::HandlerPanicked);
+        assert!(letter.message.is_none());
+        assert_eq!(letter.description.as_deref(), Some("Panic"));
+        assert!(dead_letters.try_recv().is_err());
+        let panic = panics.recv().await.unwrap();
+        assert_eq!(panic.path, actor.path());
+        assert_eq!(panic.reason, "counter panicked");
+        assert_eq!(panic.messages, ["Panic"]);
+        assert_eq!(panic.policy, PanicPolicy::Continue);
+
+        actor.shutdown();
+        Box::new(actor).join().await;
+
+        let (sink, mut dead_letters) = DeadLetterSink::channel();
+        let actor = counter_with_config(ActorConfig {
+            on_panic: PanicPolicy::Stop,
+            dead_letters: sink,
+            max_batch: 8,
+            ..ActorConfig::default()
+        });
+        let mut panics = actor.watch_panics();
+        actor.send(CounterMessage::Panic).unwrap();
+        actor.send(CounterMessage::Add(4)).unwrap();
+        let actor_ref = (*actor).clone();
+        Box::new(actor).join().await;
+        assert!(actor_ref.ask(CounterMessage::Get).await.is_err());
+
+        // The message after the panic is returned whole
+        let letter = dead_letters.recv().await.unwrap();
+        assert_eq!(letter.reason, DeadLetterReason::HandlerPanicked);
+        assert!(letter.message.is_none());
+        let letter = dead_letters.recv().await.unwrap();
+        assert_eq!(letter.reason, DeadLetterReason::Stopped);
+        assert!(matches!(
+            letter
+                .message
+                .as_ref()
+                .and_then(|m| m.downcast_ref::<CounterMessage>()),
+            Some(CounterMessage::Add(4))
+        ));
+
+        // Without a formatter only the type is known
+        let panic = panics.recv().await.unwrap();
+        assert_eq!(panic.messages, [std::any::type_name::<CounterMessage>()]);
+        assert_eq!(panic.policy, PanicPolicy::Stop);
+        // The watcher is dropped once the actor has stopped
+        assert!(panics.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_and_inspect() {
+        let actor = counter();
+        actor.send(CounterMessage::Add(4)).unwrap();
+
+        assert_eq!(actor.snapshot::<u32>().await.unwrap(), 4);
+        assert!(actor.inspect(|count: &u32| *count > 3).await.unwrap());
+        assert!(matches!(
+            actor.snapshot::<String>().await,
+            Err(ActorError::WrongStateType(_))
+        ));
+
+        actor.shutdown();
+        Box::new(actor).join().await;
+    }
+
+    #[tokio::test]
+    async fn test_finished_children_are_reaped() {
+        let mut state = ActorInternalState {
+            children: Vec::new(),
+            state: (),
+        };
+        for id in 0..10 {
+            let child = crate::task::TokioTask::spawn(|_| async {});
+            while !child.is_finished() {
+                tokio::task::yield_now().await;
+            }
+            state.apply_child_signal::<()>(ActorSignal::SpawnChild(ChildId(id), Box::new(child)));
+        }
+
+        assert_eq!(state.children.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_to_children() {
+        let parent = Actor::run((), behavior(|_, _: (), _| Box::pin(async {})));
+        let children: Vec<_> = (0..3).map(|_| counter()).collect();
+        let refs: Vec<_> = children.iter().map(|child| (**child).clone()).collect();
+        for child in children {
+            parent.attach_child(child);
+        }
+        // Not an actor, so skipped
+        parent.attach_child(crate::task::TokioTask::spawn(|token| async move {
+            token.cancelled().await
+        }));
+
+        parent.broadcast_to_children(|| CounterMessage::Add(5));
+        // Returns once the parent has handled the broadcast
+        parent.snapshot::<()>().await.unwrap();
+        for child in &refs {
+            assert_eq!(child.ask(CounterMessage::Get).await.unwrap(), 5);
+        }
+
+        parent.shutdown();
+        Box::new(parent).join().await;
+    }
+
+    #[tokio::test]
+    async fn test_identity_and_path() {
+        let parent = counter_with_config(ActorConfig {
+            name: Some("router".to_string()),
+            parent_path: Some("proxy".to_string()),
+            ..ActorConfig::default()
+        });
+        assert_eq!(parent.path(), "proxy/router");
+        assert_eq!(parent.metrics().path, "proxy/router");
+
+        let anonymous = counter();
+        assert_ne!(anonymous.id(), parent.id());
+        assert_eq!(anonymous.path(), format!("actor-{}", anonymous.id()));
+
+        let (tx, rx) = oneshot::channel();
+        let config = ActorConfig {
+            name: Some("client-3".to_string()),
+            ..ActorConfig::default()
+        };
+        let handler = behavior(
+            |self_ref: ActorRef<()>, _, reply: &mut Option<oneshot::Sender<String>>| {
+                let reply = reply.take();
+                Box::pin(async move {
+                    if let Some(reply) = reply {
+                        let _ = reply.send(self_ref.path().to_string());
+                    }
+                })
+            },
+        );
+        parent.run_child_with_config(Some(tx), handler, config);
+        parent.broadcast_to_children(|| ());
+
+        assert_eq!(rx.await.unwrap(), "proxy/router/client-3");
+
+        parent.shutdown();
+        Box::new(parent).join().await;
+    }
+
+    #[tokio::test]
+    async fn test_batch_behavior() {
+        let config = ActorConfig {
+            max_batch: 3,
+            ..ActorConfig::default()
+        };
+        let handler = batch_behavior(
+            |_, messages: Vec<CounterMessage>, batches: &mut Vec<usize>| {
+                batches.push(messages.len());
+                for message in messages {
+                    if let CounterMessage::Get(reply) = message {
+                        reply.send(0);
+                    }
+                }
+                Box::pin(async {})
+            },
+        );
+        let actor = Actor::run_with_config(Vec::new(), handler, config);
+
+        // The test runtime is single threaded, so everything is queued before
+        // the actor first runs
+        for n in 0..7 {
+            actor.send(CounterMessage::Add(n)).unwrap();
+        }
+        actor.ask(CounterMessage::Get).await.unwrap();
+
+        let batches = actor.snapshot::<Vec<usize>>().await.unwrap();
+        assert_eq!(batches, vec![3, 3, 2]);
+        assert_eq!(actor.metrics().processed, 8);
+
+        actor.shutdown();
+        Box::new(actor).join().await;
+    }
+
+    #[tokio::test]
+    async fn test_weak_ref() {
+        let actor = counter();
+        let weak = actor.downgrade();
+        weak.upgrade()
+            .unwrap()
+            .send(CounterMessage::Add(2))
+            .unwrap();
+        assert_eq!(actor.ask(CounterMessage::Get).await.unwrap(), 2);
+
+        actor.shutdown();
+        Box::new(actor).join().await;
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_handler_deadline() {
+        let config = ActorConfig {
+            handler_deadline: Some(Duration::from_secs(1)),
+            ..ActorConfig::default()
+        };
+        let handler = behavior(|_, delay: Duration, _: &mut ()| {
+            Box::pin(async move { tokio::time::sleep(delay).await })
+        });
+        let actor = Actor::run_with_config((), handler, config);
+
+        actor.send(Duration::from_millis(10)).unwrap();
+        actor.send(Duration::from_secs(5)).unwrap();
+        actor.inspect(|_: &()| ()).await.unwrap();
+
+        let metrics = actor.metrics();
+        assert_eq!(metrics.processed, 2);
+        assert_eq!(metrics.overruns, 1);
+
+        actor.shutdown();
+        Box::new(actor).join().await;
     }
 }
@@ -0,0 +1,113 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::{ActorError, ActorRef};
+
+type Entry = (
+    Box<dyn Any + Send + Sync>,
+    Box<dyn Fn() -> bool + Send + Sync>,
+);
+
+/// Looks actors up by name, so diagnostics and admin code can reach
+/// "router" without every `ActorRef` being threaded to it. Stopped actors drop
+/// out on their own; their names can be registered again.
+#[derive(Default)]
+pub struct ActorRegistry {
+    actors: Mutex<HashMap<String, Entry>>,
+}
+
+impl ActorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `actor` under its spawn name. Fails with `NameTaken` if a
+    /// running actor already has the name.
+    pub fn register<Message: Send + 'static>(
+        &self,
+        actor: &ActorRef<Message>,
+    ) -> Result<(), ActorError> {
+        let name = actor.name().ok_or(ActorError::Unnamed)?;
+        self.register_as(name, actor)
+    }
+
+    /// Registers `actor` under `name`, regardless of its spawn name
+    pub fn register_as<Message: Send + 'static>(
+        &self,
+        name: &str,
+        actor: &ActorRef<Message>,
+    ) -> Result<(), ActorError> {
+        let mut actors = self.actors.lock().unwrap();
+        if actors.get(name).is_some_and(|(_, stopped)| !stopped()) {
+            return Err(ActorError::NameTaken(name.to_string()));
+        }
+
+        let stopped = {
+            let actor = actor.clone();
+            Box::new(move || actor.is_stopped())
+        };
+        actors.insert(name.to_string(), (Box::new(actor.clone()), stopped));
+        Ok(())
+    }
+
+    /// The running actor registered as `name`. `None` if there isn't one or
+    /// it takes a different message type.
+    pub fn lookup<Message: Send + 'static>(&self, name: &str) -> Option<ActorRef<Message>> {
+        let actors = self.actors.lock().unwrap();
+        let actor = actors.get(name)?.0.downcast_ref::<ActorRef<Message>>()?;
+        (!actor.is_stopped()).then(|| actor.clone())
+    }
+
+    pub fn unregister(&self, name: &str) {
+        self.actors.lock().unwrap().remove(name);
+    }
+
+    /// Names of all running registered actors, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut actors = self.actors.lock().unwrap();
+        actors.retain(|_, (_, stopped)| !stopped());
+
+        let mut names: Vec<_> = actors.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::{behavior, Actor, ActorConfig};
+    use crate::task::CancellableTask;
+
+    #[tokio::test]
+    async fn test_register_and_lookup() {
+        let registry = ActorRegistry::new();
+        let config = ActorConfig {
+            name: Some("router".to_string()),
+            ..ActorConfig::default()
+        };
+        let actor = Actor::run_with_config(
+            (),
+            behavior(|_, _: u32, _: &mut ()| Box::pin(async {})),
+            config,
+        );
+
+        registry.register(&actor).unwrap();
+        assert!(matches!(
+            registry.register(&actor),
+            Err(ActorError::NameTaken(_))
+        ));
+        assert_eq!(registry.names(), vec!["router".to_string()]);
+
+        let found = registry.lookup::<u32>("router").unwrap();
+        assert_eq!(found.name(), Some("router"));
+        assert!(registry.lookup::<String>("router").is_none());
+        assert!(registry.lookup::<u32>("missing").is_none());
+
+        actor.shutdown();
+        Box::new(actor).join().await;
+        assert!(registry.lookup::<u32>("router").is_none());
+        assert!(registry.names().is_empty());
+    }
+}
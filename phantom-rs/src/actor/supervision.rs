@@ -0,0 +1,285 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use log::{debug, warn};
+use tokio::task::{AbortHandle, JoinError, JoinHandle};
+use tokio::time::{sleep, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use super::{ActorRef, ChildId, StopReason};
+use crate::task::CancellableTask;
+
+/// What a parent does when a supervised child stops on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupervisionStrategy {
+    /// Log it and carry on without the child
+    Ignore,
+    /// Start a fresh child after a backoff
+    Restart(RestartPolicy),
+    /// Shut the parent down too
+    Escalate,
+}
+
+/// Backoff for `SupervisionStrategy::Restart`. The delay doubles after each
+/// restart, up to `max_backoff`. A run that lasts longer than `max_backoff`
+/// counts as healthy and starts the count over, so failures spread over a
+/// long uptime never use up the budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Restarts allowed before the child is given up on; `None` never gives up
+    pub max_restarts: Option<u32>,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RestartPolicy {
+    /// How long to wait before restart number `restarts + 1`
+    pub fn backoff(&self, restarts: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(restarts))
+            .min(self.max_backoff)
+    }
+
+    /// Whether `restarts` restarts have used up the budget
+    pub fn exhausted(&self, restarts: u32) -> bool {
+        self.max_restarts.is_some_and(|max| restarts >= max)
+    }
+
+    /// Whether a run that lasted `ran_for` was healthy enough to reset the
+    /// restart count
+    pub fn healthy(&self, ran_for: Duration) -> bool {
+        ran_for > self.max_backoff
+    }
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: Some(5),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl<Message: Send + 'static> ActorRef<Message> {
+    /// Runs a child made by `factory` and applies `strategy` whenever it exits
+    /// or panics without having been cancelled. The factory is called again
+    /// for each restart, with a token that is cancelled when the parent shuts
+    /// down.
+    pub fn supervise_child<F, Fut>(&self, factory: F, strategy: SupervisionStrategy) -> ChildId
+    where
+        F: FnMut(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let token = CancellationToken::new();
+        let handle = tokio::spawn(supervise(factory, strategy, self.clone(), token.clone()));
+        self.attach_child(Supervisor { token, handle })
+    }
+}
+
+struct Supervisor {
+    token: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+impl CancellableTask for Supervisor {
+    fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        Some(self.handle.abort_handle())
+    }
+
+    fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(async move {
+            let _ = self.handle.await;
+        })
+    }
+}
+
+async fn supervise<Message, F, Fut>(
+    mut factory: F,
+    strategy: SupervisionStrategy,
+    parent: ActorRef<Message>,
+    token: CancellationToken,
+) where
+    Message: Send + 'static,
+    F: FnMut(CancellationToken) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut restarts = 0;
+
+    loop {
+        // Spawned separately so a panic is caught by its JoinHandle
+        let started = Instant::now();
+        let mut child = tokio::spawn(factory(token.child_token()));
+
+        let result = tokio::select! {
+            _ = token.cancelled() => {
+                let _ = child.await;
+                return;
+            }
+            result = &mut child => result,
+        };
+        log_exit(parent.path(), &result);
+
+        match strategy {
+            SupervisionStrategy::Ignore => return,
+            SupervisionStrategy::Escalate => {
+                warn!("[supervisor {}] escalating child failure", parent.path());
+                parent.stop_with(StopReason::Escalated);
+                return;
+            }
+            SupervisionStrategy::Restart(policy) => {
+                if policy.healthy(started.elapsed()) {
+                    restarts = 0;
+                }
+                if policy.exhausted(restarts) {
+                    warn!(
+                        "[supervisor {}] giving up after {} restarts",
+                        parent.path(),
+                        restarts
+                    );
+                    return;
+                }
+
+                let backoff = policy.backoff(restarts);
+                restarts += 1;
+                debug!(
+                    "[supervisor {}] restarting child in {:?}",
+                    parent.path(),
+                    backoff
+                );
+
+                tokio::select! {
+                    _ = token.cancelled() => return,
+                    _ = sleep(backoff) => {}
+                }
+            }
+        }
+    }
+}
+
+fn log_exit(parent_path: &str, result: &Result<(), JoinError>) {
+    match result {
+        Ok(()) => debug!("[supervisor {}] child exited", parent_path),
+        Err(e) if e.is_panic() => warn!("[supervisor {}] child panicked", parent_path),
+        Err(e) => debug!("[supervisor {}] child stopped: {}", parent_path, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::{behavior, Actor};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    fn parent() -> crate::actor::RunningActor<()> {
+        Actor::run((), behavior(|_, _, _| Box::pin(async {})))
+    }
+
+    #[tokio::test]
+    async fn test_restart_until_limit() {
+        let parent = parent();
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let counter = runs.clone();
+        let policy = RestartPolicy {
+            max_restarts: Some(2),
+            initial_backoff: Duration::from_millis(1),
+            // Long enough that no run here counts as healthy
+            max_backoff: Duration::from_secs(1),
+        };
+        parent.supervise_child(
+            move |_| {
+                let counter = counter.clone();
+                async move {
+                    if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                        panic!("child failure");
+                    }
+                }
+            },
+            SupervisionStrategy::Restart(policy),
+        );
+
+        // The panic hook may capture a backtrace, so allow plenty of time
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while runs.load(Ordering::SeqCst) < 3 {
+                sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("child should be restarted twice");
+
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+
+        parent.shutdown();
+        Box::new(parent).join().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_healthy_run_resets_budget() {
+        let parent = parent();
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let counter = runs.clone();
+        let policy = RestartPolicy {
+            max_restarts: Some(1),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(2),
+        };
+        parent.supervise_child(
+            move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                sleep(Duration::from_secs(3))
+            },
+            SupervisionStrategy::Restart(policy),
+        );
+
+        sleep(Duration::from_secs(60)).await;
+        assert!(runs.load(Ordering::SeqCst) > 10);
+
+        parent.shutdown();
+        Box::new(parent).join().await;
+    }
+
+    #[tokio::test]
+    async fn test_escalate_stops_parent() {
+        let parent = parent();
+        parent.supervise_child(|_| async {}, SupervisionStrategy::Escalate);
+        let terminated = parent.watch();
+
+        tokio::time::timeout(Duration::from_secs(1), Box::new(parent).join())
+            .await
+            .expect("parent should shut down");
+        assert_eq!(terminated.await.unwrap().reason, StopReason::Escalated);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_child_is_not_restarted() {
+        let parent = parent();
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let counter = runs.clone();
+        parent.supervise_child(
+            move |token| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async move { token.cancelled().await }
+            },
+            SupervisionStrategy::Restart(RestartPolicy::default()),
+        );
+        sleep(Duration::from_millis(20)).await;
+
+        parent.shutdown();
+        Box::new(parent).join().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+}
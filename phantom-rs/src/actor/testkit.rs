@@ -0,0 +1,117 @@
+//! Helpers for unit testing behaviors without real sockets or sleeps.
+//!
+//! A `Probe` stands in for the actor under test's peers and collects what it
+//! is sent. Call `pause_time` first (or use `#[tokio::test(start_paused =
+//! true)]`) to run on virtual time: timers fire as soon as the runtime is
+//! idle, so `expect_no_message` and `send_after` cost no real time.
+
+use std::fmt::Debug;
+use std::ops::Deref;
+
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Duration};
+
+use super::{behavior, Actor, ActorRef, RunningActor};
+use crate::task::CancellableTask;
+
+/// How long `expect_message` waits by default
+pub const DEFAULT_EXPECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// An actor that records every message it is sent. Derefs to its `ActorRef`,
+/// so it can be handed to the behavior under test.
+pub struct Probe<Message: Send + 'static> {
+    actor: RunningActor<Message>,
+    received: mpsc::UnboundedReceiver<Message>,
+}
+
+impl<Message: Debug + Send + 'static> Probe<Message> {
+    pub fn new() -> Self {
+        let (tx, received) = mpsc::unbounded_channel();
+        let actor = Actor::run(
+            tx,
+            behavior(|_, message, tx: &mut mpsc::UnboundedSender<Message>| {
+                let _ = tx.send(message);
+                Box::pin(async {})
+            }),
+        );
+        Self { actor, received }
+    }
+
+    pub fn actor_ref(&self) -> ActorRef<Message> {
+        (*self.actor).clone()
+    }
+
+    /// The next message, waiting up to `DEFAULT_EXPECT_TIMEOUT`
+    pub async fn expect_message(&mut self) -> Message {
+        self.expect_message_within(DEFAULT_EXPECT_TIMEOUT).await
+    }
+
+    /// The next message. Panics if none arrives within `within`.
+    pub async fn expect_message_within(&mut self, within: Duration) -> Message {
+        match timeout(within, self.received.recv()).await {
+            Ok(Some(message)) => message,
+            Ok(None) => panic!("probe stopped while waiting for a message"),
+            Err(_) => panic!("no message received within {:?}", within),
+        }
+    }
+
+    /// Panics if a message arrives within `within`
+    pub async fn expect_no_message(&mut self, within: Duration) {
+        if let Ok(Some(message)) = timeout(within, self.received.recv()).await {
+            panic!("expected no message, received {:?}", message);
+        }
+    }
+
+    /// Stops the probe's actor
+    pub async fn stop(self) {
+        self.actor.shutdown();
+        Box::new(self.actor).join().await;
+    }
+}
+
+impl<Message: Debug + Send + 'static> Default for Probe<Message> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Message: Send + 'static> Deref for Probe<Message> {
+    type Target = ActorRef<Message>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.actor
+    }
+}
+
+/// Switches the current runtime to virtual time. Needs a current-thread
+/// runtime, which `#[tokio::test]` is by default.
+pub fn pause_time() {
+    tokio::time::pause();
+}
+
+/// Moves virtual time forward by `by`, firing any timers that fall due
+pub async fn advance(by: Duration) {
+    tokio::time::advance(by).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_probe_on_virtual_time() {
+        pause_time();
+        let mut probe = Probe::<u32>::new();
+
+        probe.send_after(Duration::from_secs(60), 7);
+        probe.expect_no_message(Duration::from_secs(59)).await;
+        advance(Duration::from_secs(1)).await;
+        assert_eq!(probe.expect_message().await, 7);
+
+        probe.send(8).unwrap();
+        assert_eq!(probe.expect_message().await, 8);
+        probe.expect_no_message(Duration::from_secs(3600)).await;
+
+        probe.stop().await;
+    }
+}
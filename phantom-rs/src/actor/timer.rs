@@ -0,0 +1,104 @@
+use tokio::time::{interval_at, sleep, Duration, Instant, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+
+use super::ActorRef;
+use crate::task::TokioTask;
+
+/// Stops a scheduled message. Dropping the handle leaves the timer running;
+/// it also stops when the actor shuts down.
+#[derive(Debug, Clone)]
+pub struct TimerHandle(CancellationToken);
+
+impl TimerHandle {
+    pub fn cancel(&self) {
+        self.0.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+}
+
+impl<Message: Send + 'static> ActorRef<Message> {
+    /// Delivers `message` to this actor once `delay` has passed
+    pub fn send_after(&self, delay: Duration, message: Message) -> TimerHandle {
+        let actor = self.clone();
+        self.schedule(move |_| async move {
+            sleep(delay).await;
+            let _ = actor.send_async(message).await;
+        })
+    }
+
+    /// Delivers a message built by `message` every `period`, starting one
+    /// period from now. Ticks missed while the mailbox was full are not
+    /// made up.
+    pub fn send_interval(
+        &self,
+        period: Duration,
+        mut message: impl FnMut() -> Message + Send + 'static,
+    ) -> TimerHandle {
+        let actor = self.clone();
+        self.schedule(move |_| async move {
+            let mut ticker = interval_at(Instant::now() + period, period);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                if actor.send_async(message()).await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// Runs a timer as a child, so it ends with the actor
+    fn schedule<F, Fut>(&self, timer: F) -> TimerHandle
+    where
+        F: FnOnce(CancellationToken) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let task = TokioTask::spawn_named("timer", timer);
+        let handle = TimerHandle(task.cancellation_token());
+        self.attach_child(task);
+        handle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::actor::testkit::{pause_time, Probe};
+    use tokio::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_send_after_and_interval() {
+        pause_time();
+        let mut probe = Probe::<u32>::new();
+
+        probe.send_after(Duration::from_millis(10), 1);
+        let cancelled = probe.send_after(Duration::from_millis(10), 2);
+        cancelled.cancel();
+        assert_eq!(probe.expect_message().await, 1);
+        probe.expect_no_message(Duration::from_millis(50)).await;
+
+        let mut ticks = 0;
+        let started = Instant::now();
+        let interval = probe.send_interval(Duration::from_millis(10), move || {
+            ticks += 1;
+            ticks
+        });
+        for tick in 1..=5 {
+            assert_eq!(probe.expect_message().await, tick);
+        }
+        // Five periods, give or take the timer wheel's millisecond rounding
+        let elapsed = started.elapsed();
+        assert!(
+            (Duration::from_millis(50)..Duration::from_millis(55)).contains(&elapsed),
+            "ticks took {:?}",
+            elapsed
+        );
+
+        interval.cancel();
+        probe.expect_no_message(Duration::from_secs(1)).await;
+
+        probe.stop().await;
+    }
+}
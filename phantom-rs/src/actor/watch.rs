@@ -0,0 +1,198 @@
+use std::fmt;
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, oneshot};
+
+use super::{ActorRef, PanicPolicy};
+
+/// Why an actor's run loop ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// `shutdown` was called, directly or by a parent shutting down
+    Shutdown,
+    /// `stop_graceful` closed the mailbox and the queue was drained or timed out
+    MailboxClosed,
+    /// The handler panicked under `PanicPolicy::Stop`, with the panic message
+    Panicked(String),
+    /// A child run with `SupervisionStrategy::Escalate` exited
+    Escalated,
+}
+
+/// Delivered to watchers once an actor has stopped and its children are done
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Terminated {
+    pub id: u64,
+    pub path: String,
+    pub reason: StopReason,
+}
+
+/// Delivered to panic watchers each time the actor's handler panics, under
+/// either `PanicPolicy`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandlerPanic {
+    pub id: u64,
+    pub path: String,
+    /// The panic message
+    pub reason: String,
+    /// The messages the panic lost: the one being handled, or a whole batch
+    /// for a behavior that `takes_batches`. Formatted by the actor's
+    /// `MessageFormatter`, or just their type name without one.
+    pub messages: Vec<String>,
+    /// What the actor does next
+    pub policy: PanicPolicy,
+}
+
+type Notify = Box<dyn FnOnce(Terminated) + Send>;
+type NotifyPanic = Arc<dyn Fn(HandlerPanic) + Send + Sync>;
+
+/// Who to tell when the actor stops, or how it stopped if it already has,
+/// and who to tell about each panic until then
+#[derive(Default)]
+pub(crate) struct Watchers {
+    pending: Vec<Notify>,
+    terminated: Option<Terminated>,
+    panics: Vec<NotifyPanic>,
+}
+
+impl Watchers {
+    fn add(&mut self, notify: Notify) {
+        match &self.terminated {
+            Some(terminated) => notify(terminated.clone()),
+            None => self.pending.push(notify),
+        }
+    }
+
+    pub(crate) fn notify(&mut self, terminated: Terminated) {
+        for notify in self.pending.drain(..) {
+            notify(terminated.clone());
+        }
+        self.terminated = Some(terminated);
+        self.panics.clear();
+    }
+
+    /// The panic watchers, to call once the lock is released
+    pub(crate) fn panic_watchers(&self) -> Vec<NotifyPanic> {
+        self.panics.clone()
+    }
+}
+
+impl fmt::Debug for Watchers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchers")
+            .field("pending", &self.pending.len())
+            .field("terminated", &self.terminated)
+            .field("panics", &self.panics.len())
+            .finish()
+    }
+}
+
+impl<Message: Send + 'static> ActorRef<Message> {
+    /// Resolves once the actor has stopped. Resolves straight away if it
+    /// already has.
+    pub fn watch(&self) -> oneshot::Receiver<Terminated> {
+        let (tx, rx) = oneshot::channel();
+        self.on_terminated(move |terminated| {
+            let _ = tx.send(terminated);
+        });
+        rx
+    }
+
+    /// Sends `watcher` the message built by `message` once this actor has
+    /// stopped, so a parent can react in its own handler
+    pub fn watch_from<Watcher: Send + 'static>(
+        &self,
+        watcher: &ActorRef<Watcher>,
+        message: impl FnOnce(Terminated) -> Watcher + Send + 'static,
+    ) {
+        let watcher = watcher.clone();
+        self.on_terminated(move |terminated| {
+            let _ = watcher.send(message(terminated));
+        });
+    }
+
+    fn on_terminated(&self, notify: impl FnOnce(Terminated) + Send + 'static) {
+        self.cell.watchers.lock().unwrap().add(Box::new(notify));
+    }
+
+    /// Receives a `HandlerPanic` each time the handler panics, until the
+    /// actor stops
+    pub fn watch_panics(&self) -> mpsc::UnboundedReceiver<HandlerPanic> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.on_panic(move |panic| {
+            let _ = tx.send(panic);
+        });
+        rx
+    }
+
+    /// Sends `watcher` the message built by `message` each time this actor's
+    /// handler panics, so a parent can react even when the actor carries on
+    pub fn watch_panics_from<Watcher: Send + 'static>(
+        &self,
+        watcher: &ActorRef<Watcher>,
+        message: impl Fn(HandlerPanic) -> Watcher + Send + Sync + 'static,
+    ) {
+        let watcher = watcher.clone();
+        self.on_panic(move |panic| {
+            let _ = watcher.send(message(panic));
+        });
+    }
+
+    fn on_panic(&self, notify: impl Fn(HandlerPanic) + Send + Sync + 'static) {
+        let mut watchers = self.cell.watchers.lock().unwrap();
+        // Nothing more to report once it has stopped
+        if watchers.terminated.is_none() {
+            watchers.panics.push(Arc::new(notify));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::{behavior, Actor, ActorConfig, PanicPolicy};
+    use crate::task::CancellableTask;
+
+    #[tokio::test]
+    async fn test_watch_reports_reason() {
+        let actor = Actor::run((), behavior(|_, _: (), _: &mut ()| Box::pin(async {})));
+        let terminated = actor.watch();
+        actor.stop_graceful(None);
+        let terminated = terminated.await.unwrap();
+        assert_eq!(terminated.id, actor.id());
+        assert_eq!(terminated.reason, StopReason::MailboxClosed);
+
+        // Watching a stopped actor still gets an answer
+        assert_eq!(
+            actor.watch().await.unwrap().reason,
+            StopReason::MailboxClosed
+        );
+        Box::new(actor).join().await;
+
+        let config = ActorConfig {
+            on_panic: PanicPolicy::Stop,
+            ..ActorConfig::default()
+        };
+        let actor = Actor::run_with_config(
+            (),
+            behavior(|_, _: (), _: &mut ()| Box::pin(async { panic!("boom") })),
+            config,
+        );
+        let (parent_tx, mut parent_rx) = tokio::sync::mpsc::unbounded_channel();
+        let parent = Actor::run(
+            (),
+            behavior(move |_, terminated: Terminated, _: &mut ()| {
+                let _ = parent_tx.send(terminated);
+                Box::pin(async {})
+            }),
+        );
+        actor.watch_from(&parent, |terminated| terminated);
+        actor.send(()).unwrap();
+
+        let terminated = parent_rx.recv().await.unwrap();
+        assert_eq!(terminated.path, actor.path());
+        assert_eq!(terminated.reason, StopReason::Panicked("boom".to_string()));
+
+        parent.shutdown();
+        Box::new(parent).join().await;
+    }
+}
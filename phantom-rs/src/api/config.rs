@@ -0,0 +1,110 @@
+//! Reading and writing `PhantomOpts`, so the CLI's config files and apps'
+//! saved profiles use the same format
+
+use super::opts::{DEFAULT_BIND, DEFAULT_TIMEOUT};
+use super::{PhantomError, PhantomOpts};
+
+pub(crate) fn default_bind() -> String {
+    DEFAULT_BIND.to_string()
+}
+
+pub(crate) fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT
+}
+
+fn invalid_config(error: impl std::fmt::Display) -> PhantomError {
+    PhantomError::InvalidConfig {
+        message: error.to_string(),
+    }
+}
+
+impl PhantomOpts {
+    /// Parses and validates options saved with `to_json`
+    pub fn from_json(json: &str) -> Result<Self, PhantomError> {
+        let opts: PhantomOpts = serde_json::from_str(json).map_err(invalid_config)?;
+        opts.validate()?;
+        Ok(opts)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("options always serialize")
+    }
+
+    /// Parses and validates options from a TOML document such as a config
+    /// file
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, PhantomError> {
+        let opts: PhantomOpts = toml::from_str(toml).map_err(invalid_config)?;
+        opts.validate()?;
+        Ok(opts)
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("options always serialize")
+    }
+}
+
+/// `PhantomOpts::from_json`
+#[uniffi::export]
+pub fn phantom_opts_from_json(json: String) -> Result<PhantomOpts, PhantomError> {
+    PhantomOpts::from_json(&json)
+}
+
+/// `PhantomOpts::to_json`
+#[uniffi::export]
+pub fn phantom_opts_to_json(opts: PhantomOpts) -> String {
+    opts.to_json()
+}
+
+/// `PhantomOpts::from_toml`
+#[cfg(feature = "toml")]
+#[uniffi::export]
+pub fn phantom_opts_from_toml(toml: String) -> Result<PhantomOpts, PhantomError> {
+    PhantomOpts::from_toml(&toml)
+}
+
+/// `PhantomOpts::to_toml`
+#[cfg(feature = "toml")]
+#[uniffi::export]
+pub fn phantom_opts_to_toml(opts: PhantomOpts) -> String {
+    opts.to_toml()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let opts = PhantomOpts::builder("play.example.com:19132")
+            .bind_port(19134)
+            .build()
+            .unwrap();
+        let parsed = PhantomOpts::from_json(&opts.to_json()).unwrap();
+        assert!(parsed.changed_fields(&opts).is_empty());
+
+        // Only the server is required
+        let parsed = PhantomOpts::from_json(r#"{"server": "1.2.3.4:19132"}"#).unwrap();
+        assert_eq!(parsed.bind, DEFAULT_BIND);
+        assert_eq!(parsed.timeout, DEFAULT_TIMEOUT);
+
+        assert!(matches!(
+            PhantomOpts::from_json("{"),
+            Err(PhantomError::InvalidConfig { .. })
+        ));
+        assert!(matches!(
+            PhantomOpts::from_json(r#"{"server": "nope"}"#),
+            Err(PhantomError::InvalidOptions { .. })
+        ));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_round_trip() {
+        let parsed = PhantomOpts::from_toml("server = \"1.2.3.4:19132\"\nipv6 = true\n").unwrap();
+        assert!(parsed.ipv6);
+        let again = PhantomOpts::from_toml(&parsed.to_toml()).unwrap();
+        assert!(again.changed_fields(&parsed).is_empty());
+    }
+}
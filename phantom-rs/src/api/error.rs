@@ -0,0 +1,208 @@
+use std::fmt::Display;
+use std::io;
+
+use super::InvalidOption;
+
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum PhantomError {
+    #[error("Phantom encountered an error: {message}")]
+    UnknownError { message: String },
+
+    #[error("Failed to bind to {address}: {message}")]
+    FailedToBind {
+        address: String,
+        kind: IoErrorKind,
+        message: String,
+    },
+
+    #[error("Phantom failed to start: {message}")]
+    FailedToStart { message: String },
+
+    #[error("Phantom encountered an IO error: {message}")]
+    IoError { kind: IoErrorKind, message: String },
+
+    #[error("Unable to resolve remote address {address}: {message}")]
+    InvalidAddress { address: String, message: String },
+
+    #[error("Phantom is already running")]
+    AlreadyRunning,
+
+    #[error("Unable to configure Phantom logger: {message}")]
+    LoggerSetupFailed { message: String },
+
+    #[error("Port {port} is already used by another Phantom instance")]
+    PortInUse { port: u16 },
+
+    #[error("Invalid options: {}", join_invalid(.invalid))]
+    InvalidOptions { invalid: Vec<InvalidOption> },
+
+    #[error("Unable to read options: {message}")]
+    InvalidConfig { message: String },
+}
+
+/// Identifies an error variant for apps that show their own localized
+/// messages. Codes and keys never change once released; new variants get new
+/// ones. `PhantomError` codes are in the 100s and `ClientError` in the 200s.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct ErrorCode {
+    pub code: u32,
+    /// Dotted name, e.g. `phantom.port_in_use`, usable as a string resource key
+    pub key: String,
+}
+
+impl ErrorCode {
+    pub(crate) fn new(code: u32, key: &str) -> Self {
+        ErrorCode {
+            code,
+            key: key.to_string(),
+        }
+    }
+}
+
+/// The stable code for `error`
+#[uniffi::export]
+pub fn phantom_error_code(error: PhantomError) -> ErrorCode {
+    error.code()
+}
+
+impl PhantomError {
+    pub fn code(&self) -> ErrorCode {
+        let (code, key) = match self {
+            PhantomError::UnknownError { .. } => (100, "phantom.unknown"),
+            PhantomError::FailedToBind { .. } => (101, "phantom.failed_to_bind"),
+            PhantomError::FailedToStart { .. } => (102, "phantom.failed_to_start"),
+            PhantomError::IoError { .. } => (103, "phantom.io"),
+            PhantomError::InvalidAddress { .. } => (104, "phantom.invalid_address"),
+            PhantomError::AlreadyRunning => (105, "phantom.already_running"),
+            PhantomError::LoggerSetupFailed { .. } => (106, "phantom.logger_setup_failed"),
+            PhantomError::PortInUse { .. } => (107, "phantom.port_in_use"),
+            PhantomError::InvalidOptions { .. } => (108, "phantom.invalid_options"),
+            PhantomError::InvalidConfig { .. } => (109, "phantom.invalid_config"),
+        };
+        ErrorCode::new(code, key)
+    }
+
+    /// Binding `address` failed with `error`
+    pub(crate) fn bind(address: impl Display, error: io::Error) -> Self {
+        PhantomError::FailedToBind {
+            address: address.to_string(),
+            kind: error.kind().into(),
+            message: error.to_string(),
+        }
+    }
+}
+
+fn join_invalid(invalid: &[InvalidOption]) -> String {
+    invalid
+        .iter()
+        .map(InvalidOption::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn unknown_error(error: impl std::error::Error) -> PhantomError {
+    PhantomError::UnknownError {
+        message: error.to_string(),
+    }
+}
+
+/// The kind of an OS-level IO failure, so callers can tell e.g. a port that's
+/// taken from one they may not use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum IoErrorKind {
+    AddressInUse,
+    AddressNotAvailable,
+    PermissionDenied,
+    ConnectionRefused,
+    ConnectionReset,
+    HostUnreachable,
+    NetworkUnreachable,
+    TimedOut,
+    Other,
+}
+
+impl From<io::ErrorKind> for IoErrorKind {
+    fn from(kind: io::ErrorKind) -> Self {
+        match kind {
+            io::ErrorKind::AddrInUse => IoErrorKind::AddressInUse,
+            io::ErrorKind::AddrNotAvailable => IoErrorKind::AddressNotAvailable,
+            io::ErrorKind::PermissionDenied => IoErrorKind::PermissionDenied,
+            io::ErrorKind::ConnectionRefused => IoErrorKind::ConnectionRefused,
+            io::ErrorKind::ConnectionReset => IoErrorKind::ConnectionReset,
+            io::ErrorKind::HostUnreachable => IoErrorKind::HostUnreachable,
+            io::ErrorKind::NetworkUnreachable => IoErrorKind::NetworkUnreachable,
+            io::ErrorKind::TimedOut => IoErrorKind::TimedOut,
+            _ => IoErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{client_error_code, ClientError};
+
+    #[test]
+    fn test_error_codes_are_unique() {
+        let phantom = [
+            unknown_error(io::Error::other("x")),
+            PhantomError::bind("0.0.0.0:1", io::ErrorKind::AddrInUse.into()),
+            PhantomError::FailedToStart {
+                message: "x".into(),
+            },
+            PhantomError::IoError {
+                kind: IoErrorKind::Other,
+                message: "x".into(),
+            },
+            PhantomError::InvalidAddress {
+                address: "x".into(),
+                message: "x".into(),
+            },
+            PhantomError::AlreadyRunning,
+            PhantomError::LoggerSetupFailed {
+                message: "x".into(),
+            },
+            PhantomError::PortInUse { port: 1 },
+            PhantomError::InvalidOptions { invalid: vec![] },
+            PhantomError::InvalidConfig {
+                message: "x".into(),
+            },
+        ];
+        let client = [
+            ClientError::IoError {
+                kind: IoErrorKind::Other,
+                message: "x".into(),
+            },
+            ClientError::Timeout,
+            ClientError::invalid_address("x", "x"),
+            ClientError::invalid_response("x"),
+            ClientError::Unreachable {
+                message: "x".into(),
+            },
+            ClientError::refused("x"),
+            ClientError::truncated("x"),
+            ClientError::Cancelled,
+            ClientError::TaskFailed {
+                message: "x".into(),
+            },
+        ];
+
+        let codes: Vec<ErrorCode> = phantom
+            .iter()
+            .map(PhantomError::code)
+            .chain(client.iter().map(ClientError::code))
+            .collect();
+        for (i, code) in codes.iter().enumerate() {
+            for other in &codes[i + 1..] {
+                assert_ne!(code.code, other.code);
+                assert_ne!(code.key, other.key);
+            }
+        }
+
+        assert_eq!(
+            phantom_error_code(PhantomError::PortInUse { port: 1 }),
+            ErrorCode::new(107, "phantom.port_in_use")
+        );
+        assert_eq!(client_error_code(ClientError::Timeout).code, 201);
+    }
+}
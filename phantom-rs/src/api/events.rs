@@ -0,0 +1,205 @@
+use log::warn;
+use parking_lot::RwLock;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+
+#[uniffi::export(callback_interface)]
+pub trait PhantomEventListener: Send + Sync {
+    fn on_event(&self, event: PhantomEvent);
+}
+
+/// A `PhantomEventListener` whose handler can await. Events are delivered one
+/// at a time, in order, from a task of their own, so a slow handler holds up
+/// later events but never the proxy.
+#[uniffi::export(callback_interface)]
+#[async_trait::async_trait]
+pub trait AsyncPhantomEventListener: Send + Sync {
+    async fn on_event(&self, event: PhantomEvent);
+}
+
+/// Something that happened in the proxy, passed to the `PhantomEventListener`
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum PhantomEvent {
+    /// A client sent its first packet and got a session to the server
+    ClientConnected {
+        client_address: String,
+        client_port: u16,
+    },
+    /// A client's session ended, by a disconnect from either side or the
+    /// server connection failing
+    ClientDisconnected {
+        client_address: String,
+        client_port: u16,
+    },
+    /// The server answered a health check ping after being down, or for the
+    /// first time
+    UpstreamUp { server: String, latency_ms: u64 },
+    /// The server stopped answering health check pings
+    UpstreamDown { server: String, reason: String },
+    /// The proxy moved to another lifecycle state
+    StateChanged { state: PhantomState },
+}
+
+/// Where the proxy is in its lifecycle, as reported by `Phantom::state` and
+/// `PhantomEvent::StateChanged`
+#[derive(Debug, Clone, Default, PartialEq, Eq, uniffi::Enum)]
+pub enum PhantomState {
+    /// Not started yet, or stopped
+    #[default]
+    Idle,
+    /// Binding the listeners and resolving the server
+    Starting,
+    /// Listening and forwarding
+    Running,
+    /// Listeners closed; packets already received are still being forwarded
+    Draining,
+    /// Stopping what's left, such as the health check
+    Stopping,
+    /// Stopped by `Phantom::suspend`, waiting for `resume`
+    Suspended,
+    /// The last start failed; starting again is allowed
+    Failed { message: String },
+}
+
+impl PhantomEvent {
+    pub(crate) fn client_connected(client_addr: SocketAddr) -> Self {
+        PhantomEvent::ClientConnected {
+            client_address: client_addr.ip().to_string(),
+            client_port: client_addr.port(),
+        }
+    }
+
+    pub(crate) fn client_disconnected(client_addr: SocketAddr) -> Self {
+        PhantomEvent::ClientDisconnected {
+            client_address: client_addr.ip().to_string(),
+            client_port: client_addr.port(),
+        }
+    }
+}
+
+/// Where the proxy sends its events. Clones share the listener, which can be
+/// set or replaced while the proxy runs.
+#[derive(Clone, Default)]
+pub(crate) struct EventSink {
+    listener: Arc<RwLock<Option<Listener>>>,
+}
+
+enum Listener {
+    /// Shared so `emit` can call it without holding the lock
+    Sync(Arc<dyn PhantomEventListener>),
+    /// Queue of the task running the async listener. The task ends once this
+    /// is dropped and the queue drained.
+    Async(mpsc::UnboundedSender<PhantomEvent>),
+}
+
+impl EventSink {
+    pub(crate) fn set_listener(&self, listener: Option<Box<dyn PhantomEventListener>>) {
+        *self.listener.write() = listener.map(|listener| Listener::Sync(Arc::from(listener)));
+    }
+
+    /// Runs `listener` on `rt`, replacing any listener set before
+    pub(crate) fn set_async_listener(
+        &self,
+        listener: Box<dyn AsyncPhantomEventListener>,
+        rt: &Handle,
+    ) {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        rt.spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                listener.on_event(event).await;
+            }
+        });
+        *self.listener.write() = Some(Listener::Async(sender));
+    }
+
+    pub(crate) fn emit(&self, event: PhantomEvent) {
+        let listener = match &*self.listener.read() {
+            Some(Listener::Sync(listener)) => listener.clone(),
+            Some(Listener::Async(queue)) => {
+                if queue.send(event).is_err() {
+                    warn!("Async event listener has stopped, dropping event");
+                }
+                return;
+            }
+            None => return,
+        };
+        // Outside the lock, so the listener can replace itself
+        listener.on_event(event);
+    }
+}
+
+impl std::fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSink")
+            .field("listener", &self.listener.read().is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    struct Forward(mpsc::UnboundedSender<PhantomEvent>);
+
+    #[async_trait::async_trait]
+    impl AsyncPhantomEventListener for Forward {
+        async fn on_event(&self, event: PhantomEvent) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            let _ = self.0.send(event);
+        }
+    }
+
+    /// Clears the sink's listener from inside its own callback
+    struct Unsubscribe(EventSink, std::sync::mpsc::Sender<()>);
+
+    impl PhantomEventListener for Unsubscribe {
+        fn on_event(&self, _: PhantomEvent) {
+            self.0.set_listener(None);
+            let _ = self.1.send(());
+        }
+    }
+
+    #[test]
+    fn test_listener_can_replace_itself() {
+        let sink = EventSink::default();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        sink.set_listener(Some(Box::new(Unsubscribe(sink.clone(), sender))));
+
+        let emitter = sink.clone();
+        std::thread::spawn(move || {
+            emitter.emit(PhantomEvent::client_connected(
+                "127.0.0.1:1000".parse().unwrap(),
+            ))
+        });
+        receiver
+            .recv_timeout(Duration::from_secs(5))
+            .expect("listener deadlocked");
+        assert!(sink.listener.read().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_listener_keeps_order() {
+        let sink = EventSink::default();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        sink.set_async_listener(Box::new(Forward(sender)), &Handle::current());
+
+        let first: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let second: SocketAddr = "127.0.0.1:1001".parse().unwrap();
+        // Returns without waiting for the handler
+        sink.emit(PhantomEvent::client_connected(first));
+        sink.emit(PhantomEvent::client_connected(second));
+
+        assert_eq!(
+            receiver.recv().await,
+            Some(PhantomEvent::client_connected(first))
+        );
+        assert_eq!(
+            receiver.recv().await,
+            Some(PhantomEvent::client_connected(second))
+        );
+    }
+}
@@ -1,27 +1,307 @@
+use parking_lot::RwLock;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 #[uniffi::export(callback_interface)]
 pub trait PhantomLogger: Send + Sync {
+    /// A formatted line, `[LEVEL] message`
     fn log_string(&self, str: String);
 }
 
+/// Receives log records as separate fields, for filtering and styling by
+/// level or module. Set with `Phantom::set_record_logger`, alongside or
+/// instead of a `PhantomLogger`.
+#[uniffi::export(callback_interface)]
+pub trait PhantomRecordLogger: Send + Sync {
+    fn log_record(&self, record: LogRecord);
+}
+
+/// One log record, passed to `PhantomRecordLogger::log_record`
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    /// The module that logged it, e.g. `phantom_rs::proxy::router`
+    pub target: String,
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    pub message: String,
+}
+
+impl LogRecord {
+    fn new(level: log::Level, target: &str, args: &fmt::Arguments) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        LogRecord {
+            level: level.into(),
+            target: target.to_string(),
+            timestamp_ms,
+            message: args.to_string(),
+        }
+    }
+}
+
+/// The most verbose level a `PhantomLogger` is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, uniffi::Enum)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    #[default]
+    Debug,
+    Trace,
+}
+
+impl From<log::Level> for LogLevel {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Error => LogLevel::Error,
+            log::Level::Warn => LogLevel::Warn,
+            log::Level::Info => LogLevel::Info,
+            log::Level::Debug => LogLevel::Debug,
+            log::Level::Trace => LogLevel::Trace,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&log::LevelFilter::from(*self), f)
+    }
+}
+
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => log::LevelFilter::Off,
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+/// Adapts a `PhantomLogger` to the `log` crate, for hosts that want it to
+/// receive every log record in the process
 pub struct PhantomLoggerConfig {
     logger: Box<dyn PhantomLogger>,
+    level: log::LevelFilter,
 }
 
 impl PhantomLoggerConfig {
+    /// Passes on records of every level
     pub fn new(logger: Box<dyn PhantomLogger>) -> Self {
-        PhantomLoggerConfig { logger }
+        Self::with_level(logger, LogLevel::Trace)
+    }
+
+    /// Passes on records at `level` or more severe
+    pub fn with_level(logger: Box<dyn PhantomLogger>, level: LogLevel) -> Self {
+        PhantomLoggerConfig {
+            logger,
+            level: level.into(),
+        }
     }
 }
 
 impl log::Log for PhantomLoggerConfig {
-    fn enabled(&self, _: &log::Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level
     }
 
     fn log(&self, record: &log::Record) {
-        let message = format!("[{}] {}", record.level(), record.args());
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = format_line(record.level(), record.args());
         self.logger.log_string(message);
     }
 
     fn flush(&self) {}
 }
+
+fn format_line(level: log::Level, args: &fmt::Arguments) -> String {
+    format!("[{}] {}", level, args)
+}
+
+/// One instance's log output. Everything logged through it goes to the `log`
+/// crate as usual, so a host's own logger still sees it, and to the
+/// instance's `PhantomLogger` and `PhantomRecordLogger` if they're set and
+/// `level` allows. Clones share the loggers, level and debug flag.
+#[derive(Clone)]
+pub(crate) struct LogSink {
+    /// Shared so `log` can call it without holding the lock
+    logger: Arc<RwLock<Option<Arc<dyn PhantomLogger>>>>,
+    record_logger: Arc<RwLock<Option<Arc<dyn PhantomRecordLogger>>>>,
+    /// A `log::LevelFilter` as `usize`
+    level: Arc<AtomicUsize>,
+    /// `PhantomOpts::debug`: logs every packet and lets debug records through
+    /// whatever `level` says
+    debug: Arc<AtomicBool>,
+}
+
+impl Default for LogSink {
+    fn default() -> Self {
+        Self {
+            logger: Arc::default(),
+            record_logger: Arc::default(),
+            level: Arc::new(AtomicUsize::new(
+                log::LevelFilter::from(LogLevel::default()) as usize,
+            )),
+            debug: Arc::default(),
+        }
+    }
+}
+
+impl LogSink {
+    pub(crate) fn set_logger(&self, logger: Option<Box<dyn PhantomLogger>>) {
+        *self.logger.write() = logger.map(Arc::from);
+    }
+
+    pub(crate) fn set_record_logger(&self, logger: Option<Box<dyn PhantomRecordLogger>>) {
+        *self.record_logger.write() = logger.map(Arc::from);
+    }
+
+    pub(crate) fn set_level(&self, level: LogLevel) {
+        self.level
+            .store(log::LevelFilter::from(level) as usize, Ordering::Relaxed);
+    }
+
+    pub(crate) fn set_debug(&self, enabled: bool) {
+        self.debug.store(enabled, Ordering::Relaxed);
+    }
+
+    pub(crate) fn is_debug(&self) -> bool {
+        self.debug.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn log(&self, target: &str, level: log::Level, args: fmt::Arguments) {
+        log::log!(target: target, level, "{}", args);
+        let mut max_level = self.level.load(Ordering::Relaxed);
+        if self.is_debug() {
+            max_level = max_level.max(log::LevelFilter::Debug as usize);
+        }
+        if level as usize > max_level {
+            return;
+        }
+        // Outside the locks, so the loggers can replace themselves
+        let logger = self.logger.read().clone();
+        if let Some(logger) = logger {
+            logger.log_string(format_line(level, &args));
+        }
+        let record_logger = self.record_logger.read().clone();
+        if let Some(record_logger) = record_logger {
+            record_logger.log_record(LogRecord::new(level, target, &args));
+        }
+    }
+}
+
+impl fmt::Debug for LogSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LogSink")
+            .field("logger", &self.logger.read().is_some())
+            .field("record_logger", &self.record_logger.read().is_some())
+            .field("debug", &self.is_debug())
+            .finish()
+    }
+}
+
+/// Logs to a `LogSink`, e.g. `log_to!(self.log, Info, "listening on {}", addr)`
+macro_rules! log_to {
+    ($sink:expr, $level:ident, $($arg:tt)+) => {
+        $sink.log(module_path!(), log::Level::$level, format_args!($($arg)+))
+    };
+}
+pub(crate) use log_to;
+
+/// Logs one packet at debug level, only while the sink's debug flag is on, so
+/// busy proxies don't pay for formatting otherwise
+macro_rules! log_packet {
+    ($sink:expr, $($arg:tt)+) => {
+        if $sink.is_debug() {
+            $crate::api::log_to!($sink, Debug, $($arg)+);
+        }
+    };
+}
+pub(crate) use log_packet;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl PhantomLogger for Recorder {
+        fn log_string(&self, line: String) {
+            self.0.lock().push(line);
+        }
+    }
+
+    #[test]
+    fn test_sink_level() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = LogSink::default();
+        sink.set_logger(Some(Box::new(Recorder(lines.clone()))));
+
+        log_to!(sink, Debug, "first");
+        log_to!(sink, Trace, "dropped");
+        sink.set_level(LogLevel::Info);
+        log_to!(sink, Debug, "dropped");
+        log_to!(sink, Warn, "second");
+
+        assert_eq!(*lines.lock(), ["[DEBUG] first", "[WARN] second"]);
+    }
+
+    #[test]
+    fn test_sink_debug() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = LogSink::default();
+        sink.set_logger(Some(Box::new(Recorder(lines.clone()))));
+        sink.set_level(LogLevel::Info);
+
+        log_packet!(sink, "dropped");
+        log_to!(sink, Debug, "dropped");
+        sink.set_debug(true);
+        log_packet!(sink, "first");
+        log_to!(sink, Debug, "second");
+        log_to!(sink, Trace, "dropped");
+        sink.set_debug(false);
+        log_packet!(sink, "dropped");
+
+        assert_eq!(*lines.lock(), ["[DEBUG] first", "[DEBUG] second"]);
+    }
+
+    struct RecordRecorder(Arc<Mutex<Vec<LogRecord>>>);
+
+    impl PhantomRecordLogger for RecordRecorder {
+        fn log_record(&self, record: LogRecord) {
+            self.0.lock().push(record);
+        }
+    }
+
+    #[test]
+    fn test_structured_records() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = LogSink::default();
+        sink.set_logger(Some(Box::new(Recorder(lines.clone()))));
+        sink.set_record_logger(Some(Box::new(RecordRecorder(records.clone()))));
+
+        log_to!(sink, Info, "listening on {}", 19132);
+
+        // The string logger still gets its line
+        assert_eq!(*lines.lock(), ["[INFO] listening on 19132"]);
+
+        let record = records.lock().pop().unwrap();
+        assert_eq!(record.level, LogLevel::Info);
+        assert_eq!(record.target, module_path!());
+        assert_eq!(record.message, "listening on 19132");
+        assert!(record.timestamp_ms > 0);
+    }
+}
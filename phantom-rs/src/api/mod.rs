@@ -1,17 +1,41 @@
+#[cfg(feature = "serde")]
+mod config;
+mod error;
+mod events;
 mod logger;
+mod opts;
+mod runtime;
+mod version;
 
-use log::debug;
-use logger::{PhantomLogger, PhantomLoggerConfig};
+pub use error::{phantom_error_code, unknown_error, ErrorCode, IoErrorKind, PhantomError};
+pub(crate) use events::EventSink;
+pub use events::{AsyncPhantomEventListener, PhantomEvent, PhantomEventListener, PhantomState};
+pub(crate) use logger::{log_packet, log_to, LogSink};
+pub use logger::{LogLevel, LogRecord, PhantomLogger, PhantomLoggerConfig, PhantomRecordLogger};
 use once_cell::sync::Lazy;
+pub use opts::{InvalidOption, OptsUpdate, PhantomOpts, PhantomOptsBuilder, BROADCAST_PORT};
+pub use runtime::{PhantomRuntime, RuntimeConfig};
+use std::future::Future;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::runtime::{Handle, Runtime};
+use tokio::sync::OnceCell;
+pub use version::{version, BuildInfo};
 
+use crate::client::{Client, ClientError, Pong};
 use crate::proxy::ProxyInstance;
+use crate::task::TaskInfo;
 
 #[derive(uniffi::Object)]
 pub struct Phantom {
     instance: Arc<ProxyInstance>,
     rt: Handle,
+    /// Kept alive while this instance uses it, unless the runtime is global
+    /// or belongs to the caller
+    _runtime: Option<Arc<PhantomRuntime>>,
+    /// Created by the first `ping`
+    client: OnceCell<Client>,
 }
 
 pub fn new_with_current_runtime(opts: PhantomOpts) -> Result<Phantom, PhantomError> {
@@ -24,6 +48,8 @@ pub fn new_with_runtime(opts: PhantomOpts, rt: &Handle) -> Result<Phantom, Phant
     Ok(Phantom {
         instance,
         rt: rt.clone(),
+        _runtime: None,
+        client: OnceCell::new(),
     })
 }
 
@@ -41,33 +67,80 @@ impl Phantom {
         new_with_runtime(opts, RUNTIME.handle())
     }
 
-    pub async fn start(&self) -> Result<(), PhantomError> {
-        if self.instance.is_running() {
-            debug!("Phantom instance is already running");
-            return Ok(());
-        }
+    /// Runs the instance on a runtime of its own, built from `config`,
+    /// rather than the global one `new` uses
+    #[uniffi::constructor]
+    pub fn new_with_runtime_config(
+        opts: PhantomOpts,
+        config: RuntimeConfig,
+    ) -> Result<Self, PhantomError> {
+        Self::new_with_runtime(opts, PhantomRuntime::new(config)?)
+    }
 
-        debug!("Starting Phantom instance...");
+    /// Runs the instance on `runtime`, which other instances may share. From
+    /// Rust, `phantom_rs::new_with_runtime` takes any tokio runtime's handle.
+    #[uniffi::constructor]
+    pub fn new_with_runtime(
+        opts: PhantomOpts,
+        runtime: Arc<PhantomRuntime>,
+    ) -> Result<Self, PhantomError> {
+        let phantom = new_with_runtime(opts, runtime.handle())?;
+        Ok(Phantom {
+            _runtime: Some(runtime),
+            ..phantom
+        })
+    }
+
+    /// Starts the proxy, returning where it's listening once both listeners
+    /// are bound. Fails straight away if they can't be. Returns the current
+    /// addresses if it's already running. Use `wait_until_stopped` to wait
+    /// for it to stop.
+    pub async fn start(&self) -> Result<BoundAddresses, PhantomError> {
+        log_to!(self.instance.log(), Debug, "Starting Phantom instance...");
 
         let instance = self.instance.clone();
 
         self.rt
             .spawn(async move {
-                instance.listen().await?;
-                instance.join().await;
-                Ok(())
+                match instance.listen().await {
+                    Ok(()) => {}
+                    Err(PhantomError::AlreadyRunning) => {
+                        log_to!(instance.log(), Debug, "Phantom instance is already running");
+                    }
+                    Err(e) => return Err(e),
+                }
+                instance
+                    .bound_addresses()
+                    .ok_or_else(|| PhantomError::FailedToStart {
+                        message: "Stopped while starting".to_string(),
+                    })
             })
             .await
             .map_err(unknown_error)?
     }
 
+    /// Waits until the proxy is stopped by `stop` or a failed restart.
+    /// Returns straight away if it isn't running.
+    pub async fn wait_until_stopped(&self) -> Result<(), PhantomError> {
+        let instance = self.instance.clone();
+
+        self.rt
+            .spawn(async move { instance.join().await })
+            .await
+            .map_err(unknown_error)
+    }
+
     pub async fn stop(&self) -> Result<(), PhantomError> {
-        if !self.instance.is_running() {
-            debug!("Phantom instance is not running, nothing to stop");
+        if !self.instance.is_running() && !self.instance.is_suspended() {
+            log_to!(
+                self.instance.log(),
+                Debug,
+                "Phantom instance is not running, nothing to stop"
+            );
             return Ok(());
         }
 
-        debug!("Stopping Phantom instance...");
+        log_to!(self.instance.log(), Debug, "Stopping Phantom instance...");
 
         let instance = self.instance.clone();
 
@@ -80,52 +153,415 @@ impl Phantom {
             .map_err(unknown_error)?
     }
 
-    pub fn set_logger(&self, logger: Box<dyn PhantomLogger>) -> Result<(), PhantomError> {
-        let config = PhantomLoggerConfig::new(logger);
+    /// `start` for callers without async support, blocking the calling thread
+    /// on the instance's runtime until the listeners are bound. Fails instead
+    /// of blocking if called from inside an async runtime.
+    pub fn start_blocking(&self) -> Result<BoundAddresses, PhantomError> {
+        self.block_on(self.start())?
+    }
 
-        log::set_boxed_logger(Box::new(config))
-            .map_err(|e| PhantomError::LoggerSetupFailed(e.to_string()))?;
+    /// `stop` for callers without async support, blocking the calling thread
+    /// until the proxy has stopped. Fails instead of blocking if called from
+    /// inside an async runtime.
+    pub fn stop_blocking(&self) -> Result<(), PhantomError> {
+        self.block_on(self.stop())?
+    }
+
+    /// Closes the proxy's sockets and stops its tasks, e.g. when an iOS app
+    /// goes to the background and may not keep them open. Options, listeners
+    /// and the proxy port are remembered for `resume`; client sessions can't
+    /// be and are dropped. `wait_until_stopped` keeps waiting. Does nothing
+    /// if the proxy isn't running.
+    pub async fn suspend(&self) -> Result<(), PhantomError> {
+        let instance = self.instance.clone();
 
-        log::set_max_level(log::LevelFilter::Debug);
+        self.rt
+            .spawn(async move { instance.suspend().await })
+            .await
+            .map_err(unknown_error)?
+    }
 
+    /// Listens again after `suspend`, on the same port if it's still free,
+    /// returning where. `start` does the same. Returns `None` if the proxy
+    /// wasn't suspended. If it can't start, it's left stopped.
+    pub async fn resume(&self) -> Result<Option<BoundAddresses>, PhantomError> {
+        let instance = self.instance.clone();
+
+        self.rt
+            .spawn(async move {
+                let resumed = instance.resume().await?;
+                Ok(if resumed {
+                    instance.bound_addresses()
+                } else {
+                    None
+                })
+            })
+            .await
+            .map_err(unknown_error)?
+    }
+
+    /// Stops and starts the proxy again with the same options, without
+    /// another `start` or `stop` getting in between. Starts it if it wasn't
+    /// running. Returns once it's listening again; `wait_until_stopped`
+    /// keeps waiting throughout.
+    pub async fn restart(&self) -> Result<(), PhantomError> {
+        log_to!(self.instance.log(), Debug, "Restarting Phantom instance...");
+
+        let instance = self.instance.clone();
+
+        self.rt
+            .spawn(async move { instance.restart().await })
+            .await
+            .map_err(unknown_error)?
+    }
+
+    /// Switches to new options while running. A new server or listening
+    /// address restarts the listeners, which drops connected clients; other
+    /// changes apply without interrupting anyone. Returns what changed.
+    pub async fn update_opts(&self, opts: PhantomOpts) -> Result<OptsUpdate, PhantomError> {
+        let instance = self.instance.clone();
+
+        self.rt
+            .spawn(async move { instance.update_opts(opts).await })
+            .await
+            .map_err(unknown_error)?
+    }
+
+    /// Where the proxy is in its lifecycle. Each change is also sent to the
+    /// event listener as `PhantomEvent::StateChanged`.
+    pub fn state(&self) -> PhantomState {
+        self.instance.state()
+    }
+
+    /// Whether the proxy is running, where it's listening and how many
+    /// clients are connected
+    pub async fn status(&self) -> Result<PhantomStatus, PhantomError> {
+        let instance = self.instance.clone();
+
+        self.rt
+            .spawn(async move { instance.status().await })
+            .await
+            .map_err(unknown_error)
+    }
+
+    /// Packet, byte and client totals since this instance was created,
+    /// including time spent stopped. Cheap enough to poll for a stats display.
+    pub fn metrics(&self) -> PhantomMetrics {
+        self.instance.metrics()
+    }
+
+    /// The clients with an open session through the proxy, with when they
+    /// connected, when they last sent or received anything and how much.
+    /// Empty while stopped.
+    pub async fn list_clients(&self) -> Result<Vec<ClientSession>, PhantomError> {
+        let instance = self.instance.clone();
+
+        self.rt
+            .spawn(async move { instance.list_clients().await })
+            .await
+            .map_err(unknown_error)
+    }
+
+    /// Kicks a client, given as `ip:port` the way `list_clients` reports it
+    /// (IPv6 in brackets). The server stops hearing from it, so the client
+    /// times out and has to join again. Returns whether it was connected.
+    pub async fn disconnect_client(&self, address: String) -> Result<bool, PhantomError> {
+        let client_addr =
+            address
+                .parse::<SocketAddr>()
+                .map_err(|e| PhantomError::InvalidAddress {
+                    address: address.clone(),
+                    message: e.to_string(),
+                })?;
+        let instance = self.instance.clone();
+
+        self.rt
+            .spawn(async move { instance.disconnect_client(client_addr).await })
+            .await
+            .map_err(unknown_error)
+    }
+
+    /// Pings the configured server directly, not through the proxy, e.g. to
+    /// show whether it's up before starting. The pong includes the round-trip
+    /// latency. Works whether or not the proxy is running.
+    pub async fn ping(&self) -> Result<Pong, ClientError> {
+        let client = self.client.get_or_try_init(Client::new).await?;
+        client.ping(self.instance.opts().server).await
+    }
+
+    /// Where the broadcast and proxy listeners are bound. Use this to learn
+    /// the proxy port when `bind_port` is 0. `None` while not listening.
+    pub fn bound_addresses(&self) -> Option<BoundAddresses> {
+        self.instance.bound_addresses()
+    }
+
+    /// The listeners, router and other tasks this instance is running
+    pub fn tasks(&self) -> Vec<TaskInfo> {
+        self.instance.tasks()
+    }
+
+    /// Sends proxy events (clients connecting, the server going down, ...) to
+    /// `listener`, replacing any listener set before
+    pub fn set_event_listener(&self, listener: Box<dyn PhantomEventListener>) {
+        self.instance.set_event_listener(listener);
+    }
+
+    /// Like `set_event_listener`, for a listener that awaits in its handler.
+    /// It runs on this instance's runtime, one event at a time.
+    pub fn set_async_event_listener(&self, listener: Box<dyn AsyncPhantomEventListener>) {
+        self.instance.set_async_event_listener(listener, &self.rt);
+    }
+
+    /// Sends this instance's log output to `logger`. Each instance has its own
+    /// logger and the process-wide `log` logger is left alone, so this can be
+    /// called for several instances, or with a host logger installed. To send
+    /// every `log` record in the process to a `PhantomLogger`, install a
+    /// `PhantomLoggerConfig` with `log::set_boxed_logger` instead.
+    pub fn set_logger(&self, logger: Box<dyn PhantomLogger>) -> Result<(), PhantomError> {
+        self.instance.set_logger(logger);
         Ok(())
     }
+
+    /// Sends this instance's log records to `logger` as separate fields, in
+    /// addition to any `PhantomLogger`, which keeps getting formatted lines
+    pub fn set_record_logger(&self, logger: Box<dyn PhantomRecordLogger>) {
+        self.instance.set_record_logger(logger);
+    }
+
+    /// Changes how verbose this instance's logger is, taking effect
+    /// immediately. Defaults to `LogLevel::Debug`.
+    pub fn set_log_level(&self, level: LogLevel) {
+        self.instance.set_log_level(level);
+    }
+
+    /// Shows `motd` in players' server lists in place of the server's own,
+    /// e.g. "Restarting in 5 min", or the server's again with `None`. Takes
+    /// effect from the next pong without restarting. `motd` must pass
+    /// [`crate::proto::motd::validate`] and fit in a single-datagram pong; a
+    /// server pong with no room left for it keeps its own.
+    pub fn set_motd(&self, motd: Option<String>) -> Result<(), PhantomError> {
+        self.instance.set_motd(motd)
+    }
+
+    /// Turns `PhantomOpts::debug` on or off while running: every packet
+    /// forwarded is logged, and this instance's logger gets debug records
+    /// whatever `set_log_level` says. Handy for capturing a trace for a bug
+    /// report without restarting. Other instances and the global `log` level
+    /// are unaffected.
+    pub fn set_debug(&self, enabled: bool) {
+        self.instance.set_debug(enabled);
+    }
+
+    /// Saves battery on mobile hosts while nobody is using the proxy. With no
+    /// clients connected, the server is health checked every couple of
+    /// minutes instead of every few seconds, with wake-ups lined up across
+    /// instances. The first client to connect brings back full activity.
+    /// Off by default; can be changed while running.
+    pub fn set_low_power_mode(&self, enabled: bool) {
+        self.instance.set_low_power_mode(enabled);
+    }
+}
+
+impl Phantom {
+    /// Blocking on a runtime worker would stall it, and tokio panics on it
+    fn block_on<F: Future>(&self, future: F) -> Result<F::Output, PhantomError> {
+        if Handle::try_current().is_ok() {
+            return Err(PhantomError::UnknownError {
+                message: "Blocking calls can't be made from an async context".to_string(),
+            });
+        }
+        Ok(self.rt.block_on(future))
+    }
 }
 
-#[derive(Clone, Debug, uniffi::Record)]
-pub struct PhantomOpts {
-    pub server: String,
-    pub bind: String,
-    pub bind_port: u16,
-    pub timeout: u64,
-    pub debug: bool,
-    pub ipv6: bool,
+/// Returned by `Phantom::bound_addresses`
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Record)]
+pub struct BoundAddresses {
+    pub broadcast_address: String,
+    pub broadcast_port: u16,
+    pub proxy_address: String,
+    pub proxy_port: u16,
 }
 
-#[derive(Debug, thiserror::Error, uniffi::Error)]
-pub enum PhantomError {
-    #[error("Phantom encountered an error: {0}")]
-    UnknownError(String),
+/// Returned by `Phantom::metrics`. Counters only go up, apart from
+/// `active_clients`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, uniffi::Record)]
+pub struct PhantomMetrics {
+    /// Packets forwarded from clients to the server
+    pub packets_from_clients: u64,
+    pub bytes_from_clients: u64,
+    /// Packets forwarded from the server to clients
+    pub packets_to_clients: u64,
+    pub bytes_to_clients: u64,
+    /// Clients with an open session through the proxy
+    pub active_clients: u64,
+    /// Sessions opened since the instance was created
+    pub lifetime_clients: u64,
+    /// Server pongs rewritten to advertise the proxy port
+    pub pong_rewrites: u64,
+    /// Packets that couldn't be forwarded
+    pub errors: u64,
+}
 
-    #[error("Failed to bind to address: {0}")]
-    FailedToBind(String),
+/// One client session, as returned by `Phantom::list_clients`
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Record)]
+pub struct ClientSession {
+    pub client_address: String,
+    pub client_port: u16,
+    /// Local port of the socket the proxy talks to the server on for this
+    /// client, which is the port the server sees
+    pub upstream_port: u16,
+    pub connected_at: SystemTime,
+    /// The last packet forwarded either way
+    pub last_activity: SystemTime,
+    pub bytes_from_client: u64,
+    pub bytes_to_client: u64,
+}
 
-    #[error("Phantom failed to start: {0}")]
-    FailedToStart(String),
+/// Returned by `Phantom::status`. Addresses, ports and uptime are only set
+/// while the proxy is running.
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Record)]
+pub struct PhantomStatus {
+    pub running: bool,
+    /// IP the LAN broadcast listener is bound to
+    pub broadcast_address: Option<String>,
+    pub broadcast_port: Option<u16>,
+    /// IP the proxy listener is bound to
+    pub proxy_address: Option<String>,
+    /// The port clients connect to; the one the OS picked if `bind_port` was 0
+    pub proxy_port: Option<u16>,
+    /// Clients with an open session through the proxy
+    pub active_clients: u32,
+    /// Time since the listeners started
+    pub uptime: Option<Duration>,
+    /// Whether `Phantom::set_low_power_mode` is on
+    pub low_power: bool,
+    /// Stopped by `Phantom::suspend`, waiting to resume
+    pub suspended: bool,
+    pub state: PhantomState,
+}
 
-    #[error("Phantom encountered an IO error: {0}")]
-    IoError(String),
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    #[error("Unable to resolve remote address: {0}")]
-    InvalidAddress(String),
+    fn opts() -> PhantomOpts {
+        PhantomOpts::builder("127.0.0.1:19133")
+            .bind("127.0.0.1")
+            .build()
+            .unwrap()
+    }
 
-    #[error("Phantom is already running")]
-    AlreadyRunning,
+    #[tokio::test]
+    async fn test_ping() {
+        use crate::proto::unconnected_ping::UnconnectedPing;
+        use crate::proto::unconnected_pong::UnconnectedPong;
+        use crate::proto::ServerGuid;
+        use bytes::Bytes;
+        use tokio::net::UdpSocket;
 
-    #[error("Unable to configure Phantom logger: {0}")]
-    LoggerSetupFailed(String),
-}
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let phantom = new_with_current_runtime(PhantomOpts {
+            server: server.local_addr().unwrap().to_string(),
+            ..opts()
+        })
+        .unwrap();
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            for _ in 0..2 {
+                let (len, client_addr) = server.recv_from(&mut buf).await.unwrap();
+                let ping =
+                    UnconnectedPing::from_bytes(Bytes::copy_from_slice(&buf[..len])).unwrap();
+                let mut pong = UnconnectedPong::new();
+                pong.ping_time = ping.ping_time;
+                pong.set_server_guid(ServerGuid(42));
+                server
+                    .send_to(&pong.build().unwrap(), client_addr)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        // Not running, and the client is reused for the second ping
+        assert_eq!(phantom.ping().await.unwrap().server_guid, 42);
+        assert_eq!(phantom.ping().await.unwrap().server_guid, 42);
+    }
+
+    #[test]
+    fn test_start_stop_blocking() {
+        let phantom = Phantom::new(opts()).unwrap();
+
+        let bound = phantom.start_blocking().unwrap();
+        assert_eq!(phantom.bound_addresses(), Some(bound));
 
-pub fn unknown_error(error: impl std::error::Error) -> PhantomError {
-    PhantomError::UnknownError(error.to_string())
+        phantom.stop_blocking().unwrap();
+        assert!(phantom.bound_addresses().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_start_returns_once_bound() {
+        let phantom = Arc::new(new_with_current_runtime(opts()).unwrap());
+        // Not running, so nothing to wait for
+        phantom.wait_until_stopped().await.unwrap();
+
+        let bound = phantom.start().await.unwrap();
+        assert_ne!(bound.proxy_port, 0);
+        assert_eq!(phantom.start().await.unwrap(), bound);
+
+        let waiting = phantom.clone();
+        let wait = tokio::spawn(async move { waiting.wait_until_stopped().await });
+        tokio::task::yield_now().await;
+        assert!(!wait.is_finished());
+
+        phantom.stop().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), wait)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_start_fails_fast() {
+        let phantom = new_with_current_runtime(PhantomOpts {
+            bind: "192.0.2.1".to_string(),
+            ..opts()
+        })
+        .unwrap();
+        assert!(matches!(
+            phantom.start().await,
+            Err(PhantomError::FailedToBind { .. })
+        ));
+        assert!(phantom.bound_addresses().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_shared_runtime() {
+        let runtime = PhantomRuntime::new(RuntimeConfig {
+            worker_threads: Some(1),
+            thread_name: None,
+        })
+        .unwrap();
+        let first = Phantom::new_with_runtime(opts(), runtime.clone()).unwrap();
+        let second = Phantom::new_with_runtime(opts(), runtime).unwrap();
+
+        first.start().await.unwrap();
+        second.start().await.unwrap();
+        first.stop().await.unwrap();
+        second.stop().await.unwrap();
+
+        // The last instance stops the runtime without blocking this one
+        drop(first);
+        drop(second);
+    }
+
+    #[tokio::test]
+    async fn test_blocking_refused_in_async_context() {
+        let phantom = new_with_current_runtime(opts()).unwrap();
+        assert!(matches!(
+            phantom.start_blocking(),
+            Err(PhantomError::UnknownError { .. })
+        ));
+    }
 }
@@ -0,0 +1,247 @@
+use std::net::IpAddr;
+
+use super::PhantomError;
+
+/// The port phantom always listens on for LAN broadcast pings
+pub const BROADCAST_PORT: u16 = 19132;
+
+/// Defaults used by `PhantomOptsBuilder`
+pub const DEFAULT_BIND: &str = "0.0.0.0";
+pub const DEFAULT_TIMEOUT: u64 = 60;
+
+/// With the `serde` feature, only `server` is required when deserializing;
+/// missing fields get the builder's defaults
+#[derive(Clone, Debug, uniffi::Record)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhantomOpts {
+    pub server: String,
+    #[cfg_attr(feature = "serde", serde(default = "crate::api::config::default_bind"))]
+    pub bind: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bind_port: u16,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default = "crate::api::config::default_timeout")
+    )]
+    pub timeout: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub debug: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ipv6: bool,
+}
+
+/// One field of `PhantomOpts` that failed validation
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Record)]
+pub struct InvalidOption {
+    pub field: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for InvalidOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.reason)
+    }
+}
+
+impl PhantomOpts {
+    /// A builder for options proxying to `server`, with defaults for the rest
+    pub fn builder(server: impl Into<String>) -> PhantomOptsBuilder {
+        PhantomOptsBuilder::new(server)
+    }
+
+    /// Names of the fields that differ in `other`
+    pub fn changed_fields(&self, other: &PhantomOpts) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.server != other.server {
+            changed.push("server");
+        }
+        if self.bind != other.bind {
+            changed.push("bind");
+        }
+        if self.bind_port != other.bind_port {
+            changed.push("bind_port");
+        }
+        if self.timeout != other.timeout {
+            changed.push("timeout");
+        }
+        if self.debug != other.debug {
+            changed.push("debug");
+        }
+        if self.ipv6 != other.ipv6 {
+            changed.push("ipv6");
+        }
+        changed
+    }
+
+    /// Checks every field, returning `PhantomError::InvalidOptions` with all
+    /// the problems found rather than just the first
+    pub fn validate(&self) -> Result<(), PhantomError> {
+        let mut invalid = Vec::new();
+        let mut reject = |field: &str, reason: String| {
+            invalid.push(InvalidOption {
+                field: field.to_string(),
+                reason,
+            })
+        };
+
+        if let Err(reason) = validate_server(&self.server) {
+            reject("server", reason);
+        }
+        if self.bind.parse::<IpAddr>().is_err() {
+            reject(
+                "bind",
+                format!("{:?} is not an IP address", self.bind.as_str()),
+            );
+        }
+        if self.bind_port == BROADCAST_PORT {
+            reject(
+                "bind_port",
+                format!("{} is reserved for the broadcast listener", BROADCAST_PORT),
+            );
+        }
+        if self.timeout == 0 {
+            reject("timeout", "must be at least 1 second".to_string());
+        }
+
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(PhantomError::InvalidOptions { invalid })
+        }
+    }
+}
+
+/// Expects `host:port`, where the host is a name or IP address and IPv6
+/// addresses are bracketed. The host isn't resolved here.
+fn validate_server(server: &str) -> Result<(), String> {
+    let Some((host, port)) = server.rsplit_once(':') else {
+        return Err(format!("{:?} is missing a port (host:port)", server));
+    };
+    match host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+    {
+        Some(ipv6) if ipv6.parse::<IpAddr>().is_err() => {
+            return Err(format!("{:?} has an invalid IPv6 address", server));
+        }
+        Some(_) => {}
+        None if host.is_empty() => return Err(format!("{:?} is missing a host", server)),
+        None if host.contains(':') => {
+            return Err(format!(
+                "{:?} needs brackets around its IPv6 address",
+                server
+            ));
+        }
+        None => {}
+    }
+    match port.parse::<u16>() {
+        Ok(0) | Err(_) => Err(format!("{:?} is not a port between 1 and 65535", port)),
+        Ok(_) => Ok(()),
+    }
+}
+
+/// Returned by `Phantom::update_opts`
+#[derive(Clone, Debug, PartialEq, Eq, uniffi::Record)]
+pub struct OptsUpdate {
+    /// Names of the `PhantomOpts` fields that changed
+    pub changed: Vec<String>,
+    /// Whether the listeners were restarted to apply them
+    pub restarted: bool,
+}
+
+/// Builds validated `PhantomOpts`. Only the server is required; everything
+/// else defaults to what the CLI uses.
+#[derive(Clone, Debug)]
+pub struct PhantomOptsBuilder {
+    opts: PhantomOpts,
+}
+
+impl PhantomOptsBuilder {
+    pub fn new(server: impl Into<String>) -> Self {
+        Self {
+            opts: PhantomOpts {
+                server: server.into(),
+                bind: DEFAULT_BIND.to_string(),
+                bind_port: 0,
+                timeout: DEFAULT_TIMEOUT,
+                debug: false,
+                ipv6: false,
+            },
+        }
+    }
+
+    pub fn bind(mut self, bind: impl Into<String>) -> Self {
+        self.opts.bind = bind.into();
+        self
+    }
+
+    /// 0, the default, lets the OS pick a port
+    pub fn bind_port(mut self, bind_port: u16) -> Self {
+        self.opts.bind_port = bind_port;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: u64) -> Self {
+        self.opts.timeout = timeout;
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.opts.debug = debug;
+        self
+    }
+
+    pub fn ipv6(mut self, ipv6: bool) -> Self {
+        self.opts.ipv6 = ipv6;
+        self
+    }
+
+    pub fn build(self) -> Result<PhantomOpts, PhantomError> {
+        self.opts.validate()?;
+        Ok(self.opts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_defaults() {
+        let opts = PhantomOpts::builder("play.example.com:19132")
+            .build()
+            .unwrap();
+        assert_eq!(opts.bind, DEFAULT_BIND);
+        assert_eq!(opts.bind_port, 0);
+        assert_eq!(opts.timeout, DEFAULT_TIMEOUT);
+
+        assert!(PhantomOpts::builder("[::1]:19132").build().is_ok());
+        assert!(PhantomOpts::builder("10.0.0.2:19133")
+            .bind("127.0.0.1")
+            .bind_port(19134)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_reports_every_invalid_field() {
+        let result = PhantomOpts::builder("example.com")
+            .bind("localhost")
+            .bind_port(BROADCAST_PORT)
+            .timeout(0)
+            .build();
+        let Err(PhantomError::InvalidOptions { invalid }) = result else {
+            panic!("expected InvalidOptions, got {:?}", result);
+        };
+        let fields: Vec<_> = invalid.iter().map(|i| i.field.as_str()).collect();
+        assert_eq!(fields, ["server", "bind", "bind_port", "timeout"]);
+
+        for server in [":19132", "example.com:0", "example.com:99999", "::1:19132"] {
+            assert!(
+                validate_server(server).is_err(),
+                "{} should be rejected",
+                server
+            );
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use super::{InvalidOption, PhantomError};
+
+/// How to build the runtime Phantom runs on
+#[derive(Clone, Debug, Default, PartialEq, Eq, uniffi::Record)]
+pub struct RuntimeConfig {
+    /// Worker threads to start. Defaults to one per CPU core.
+    pub worker_threads: Option<u32>,
+    /// Name given to the runtime's threads. Defaults to tokio's.
+    pub thread_name: Option<String>,
+}
+
+/// A runtime several `Phantom` instances can share, e.g. to keep the thread
+/// count down on constrained devices. Stopped once every instance using it
+/// has been dropped.
+#[derive(Debug, uniffi::Object)]
+pub struct PhantomRuntime {
+    /// Only taken by `Drop`
+    runtime: Option<Runtime>,
+}
+
+#[uniffi::export]
+impl PhantomRuntime {
+    #[uniffi::constructor]
+    pub fn new(config: RuntimeConfig) -> Result<Arc<Self>, PhantomError> {
+        let mut builder = Builder::new_multi_thread();
+        builder.enable_all();
+        if let Some(worker_threads) = config.worker_threads {
+            if worker_threads == 0 {
+                return Err(PhantomError::InvalidOptions {
+                    invalid: vec![InvalidOption {
+                        field: "worker_threads".to_string(),
+                        reason: "must be at least 1".to_string(),
+                    }],
+                });
+            }
+            builder.worker_threads(worker_threads as usize);
+        }
+        if let Some(thread_name) = config.thread_name {
+            builder.thread_name(thread_name);
+        }
+
+        let runtime = builder.build().map_err(|e| PhantomError::IoError {
+            kind: e.kind().into(),
+            message: e.to_string(),
+        })?;
+        Ok(Arc::new(PhantomRuntime {
+            runtime: Some(runtime),
+        }))
+    }
+}
+
+impl PhantomRuntime {
+    pub fn handle(&self) -> &Handle {
+        self.runtime
+            .as_ref()
+            .expect("runtime is only taken when dropped")
+            .handle()
+    }
+}
+
+impl Drop for PhantomRuntime {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks, which tokio forbids inside another
+        // runtime, and the last instance may well be dropped in one
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_config() {
+        let runtime = PhantomRuntime::new(RuntimeConfig {
+            worker_threads: Some(1),
+            thread_name: Some("phantom-test".to_string()),
+        })
+        .unwrap();
+        let name = runtime.handle().block_on(async {
+            tokio::spawn(async { std::thread::current().name().map(str::to_string) })
+                .await
+                .unwrap()
+        });
+        assert_eq!(name.as_deref(), Some("phantom-test"));
+
+        assert!(matches!(
+            PhantomRuntime::new(RuntimeConfig {
+                worker_threads: Some(0),
+                thread_name: None,
+            }),
+            Err(PhantomError::InvalidOptions { .. })
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+/// Which build of the library is running, for bug reports and about screens
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct BuildInfo {
+    /// The crate version, e.g. `0.1.0`
+    pub version: String,
+    /// Abbreviated hash of the commit it was built from. `None` when built
+    /// outside a git checkout.
+    pub git_hash: Option<String>,
+    /// Cargo features compiled in
+    pub features: Vec<String>,
+}
+
+/// Features worth reporting, as named in `Cargo.toml`
+const FEATURES: [(&str, bool); 3] = [
+    ("serde", cfg!(feature = "serde")),
+    ("toml", cfg!(feature = "toml")),
+    ("testkit", cfg!(feature = "testkit")),
+];
+
+/// The version, commit and features of this build of phantom-rs
+#[uniffi::export]
+pub fn version() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_hash: option_env!("PHANTOM_GIT_HASH").map(str::to_string),
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| name.to_string())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version() {
+        let info = version();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            info.features.contains(&"serde".to_string()),
+            cfg!(feature = "serde")
+        );
+        if let Some(hash) = info.git_hash {
+            assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+        }
+    }
+}
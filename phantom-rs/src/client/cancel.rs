@@ -0,0 +1,128 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::task::AbortHandle;
+use tokio_util::sync::CancellationToken;
+
+use super::{Client, ClientError, DiscoveredServer, PingOptions, Pong};
+
+/// Lets a caller abort client operations that are still in flight. One token
+/// can be shared by several operations, e.g. every ping of a server list scan.
+#[derive(uniffi::Object)]
+pub struct CancelToken {
+    pub(super) token: CancellationToken,
+}
+
+#[uniffi::export]
+impl CancelToken {
+    #[uniffi::constructor]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            token: CancellationToken::new(),
+        })
+    }
+
+    /// Aborts every operation using this token. Their calls return
+    /// `ClientError::Cancelled`.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+#[uniffi::export]
+impl Client {
+    /// Like `ping_with_options`, but gives up as soon as `cancel` fires
+    pub async fn ping_cancellable(
+        &self,
+        addr: String,
+        options: PingOptions,
+        cancel: Arc<CancelToken>,
+    ) -> Result<Pong, ClientError> {
+        let task = self.ping_task(addr, options);
+        self.run(cancel.token.clone(), task).await
+    }
+
+    /// Like `discover`, but gives up as soon as `cancel` fires. Servers found
+    /// before that are discarded.
+    pub async fn discover_cancellable(
+        &self,
+        duration_ms: u64,
+        cancel: Arc<CancelToken>,
+    ) -> Result<Vec<DiscoveredServer>, ClientError> {
+        let task = self.discover_task(duration_ms);
+        self.run(cancel.token.clone(), task).await
+    }
+}
+
+impl Client {
+    /// Runs `task` on the client's runtime until it finishes or `cancel` fires.
+    /// Dropping the returned future aborts the task too, so a caller that
+    /// stops waiting doesn't leave it running until its timeout.
+    pub(super) async fn run<T: Send + 'static>(
+        &self,
+        cancel: CancellationToken,
+        task: impl Future<Output = Result<T, ClientError>> + Send + 'static,
+    ) -> Result<T, ClientError> {
+        let handle = self.runtime.spawn(async move {
+            tokio::select! {
+                _ = cancel.cancelled() => Err(ClientError::Cancelled),
+                result = task => result,
+            }
+        });
+
+        let _abort = AbortOnDrop(handle.abort_handle());
+        handle.await.map_err(ClientError::from)?
+    }
+}
+
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use tokio::net::UdpSocket;
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test]
+    async fn test_cancel_ping() {
+        let client = Client::new().await.expect("Failed to create client");
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = silent.local_addr().unwrap().to_string();
+
+        let cancel = CancelToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(50)).await;
+            canceller.cancel();
+        });
+
+        let started = Instant::now();
+        let result = client
+            .ping_cancellable(addr, PingOptions::default(), cancel)
+            .await;
+
+        assert!(matches!(result, Err(ClientError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_token_stops_discovery() {
+        let client = Client::new().await.expect("Failed to create client");
+        let cancel = CancelToken::new();
+        cancel.cancel();
+
+        let result = client.discover_cancellable(5000, cancel).await;
+        assert!(matches!(result, Err(ClientError::Cancelled)));
+    }
+}
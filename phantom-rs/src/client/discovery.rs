@@ -0,0 +1,222 @@
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use futures::stream::{self, Stream};
+use log::debug;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tokio::time::{timeout_at, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use uniffi::Record;
+
+use super::{CancelToken, Client, ClientError, PingSource, Pong};
+use crate::proto::mtu::RECV_BUFFER_SIZE;
+
+/// Ports Bedrock servers listen on for LAN pings (IPv4 and IPv6 defaults)
+const DISCOVERY_PORTS: [u16; 2] = [19132, 19133];
+
+/// Receives servers from a discovery or scan as they answer
+#[uniffi::export(callback_interface)]
+pub trait DiscoveryListener: Send + Sync {
+    /// Called for each server as soon as it answers
+    fn on_server(&self, server: DiscoveredServer);
+    /// Called once when the search is over, with the reason if it failed.
+    /// Not called when the search is cancelled.
+    fn on_finished(&self, error: Option<String>);
+}
+
+#[uniffi::export]
+impl Client {
+    /// Broadcasts a ping on the LAN and collects every server that answers
+    /// within `duration_ms`. Each responder address is listed once.
+    pub async fn discover(&self, duration_ms: u64) -> Result<Vec<DiscoveredServer>, ClientError> {
+        let task = self.discover_task(duration_ms);
+        self.run(CancellationToken::new(), task).await
+    }
+
+    /// Like `discover`, but reports each server to `listener` as soon as it
+    /// answers. Cancel the returned token to stop early.
+    pub fn discover_with_listener(
+        &self,
+        duration_ms: u64,
+        listener: Box<dyn DiscoveryListener>,
+    ) -> Arc<CancelToken> {
+        let cancel = CancelToken::new();
+        let token = cancel.token.clone();
+        let source = self.source;
+
+        self.runtime.spawn(async move {
+            let deadline = Instant::now() + Duration::from_millis(duration_ms);
+            let discovery = broadcast_ping(source, deadline, |server| listener.on_server(server));
+            tokio::select! {
+                _ = token.cancelled() => debug!("Cancelled discovery"),
+                result = discovery => listener.on_finished(result.err().map(|e| e.to_string())),
+            }
+        });
+
+        cancel
+    }
+}
+
+impl Client {
+    pub(super) fn discover_task(
+        &self,
+        duration_ms: u64,
+    ) -> impl Future<Output = Result<Vec<DiscoveredServer>, ClientError>> + Send + 'static {
+        let source = self.source;
+        async move {
+            let deadline = Instant::now() + Duration::from_millis(duration_ms);
+            let mut servers = Vec::new();
+            broadcast_ping(source, deadline, |server| servers.push(server)).await?;
+            Ok(servers)
+        }
+    }
+
+    /// Like `discover`, but yields each server as soon as it answers. A failed
+    /// discovery ends the stream with its error. Dropping the stream stops it.
+    pub fn discover_stream(
+        &self,
+        duration_ms: u64,
+    ) -> impl Stream<Item = Result<DiscoveredServer, ClientError>> {
+        let source = self.source;
+        self.stream(move |tx| async move {
+            let deadline = Instant::now() + Duration::from_millis(duration_ms);
+            let result = broadcast_ping(source, deadline, |server| {
+                let _ = tx.send(Ok(server));
+            })
+            .await;
+            if let Err(e) = result {
+                let _ = tx.send(Err(e));
+            }
+        })
+    }
+
+    /// Runs `task` on the client's runtime and streams whatever it sends. The
+    /// task is stopped once the stream is dropped.
+    pub(super) fn stream<T, F>(
+        &self,
+        task: impl FnOnce(mpsc::UnboundedSender<T>) -> F,
+    ) -> impl Stream<Item = T>
+    where
+        T: Send + 'static,
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let receiver_gone = tx.clone();
+        let task = task(tx);
+
+        self.runtime.spawn(async move {
+            tokio::select! {
+                _ = receiver_gone.closed() => {}
+                _ = task => {}
+            }
+        });
+
+        stream::poll_fn(move |cx| rx.poll_recv(cx))
+    }
+}
+
+async fn broadcast_ping(
+    source: PingSource,
+    deadline: Instant,
+    on_server: impl FnMut(DiscoveredServer),
+) -> Result<(), ClientError> {
+    let socket = source.bind().await?;
+
+    let ping_bytes = source.ping().build();
+    for port in DISCOVERY_PORTS {
+        let addr = SocketAddr::from((Ipv4Addr::BROADCAST, port));
+        debug!("Broadcasting ping to {}", addr);
+        socket.send_to(&ping_bytes, addr).await?;
+    }
+
+    receive_pongs(&socket, source, deadline, on_server).await
+}
+
+/// Reports each address that answers `socket` with a pong until `deadline`
+async fn receive_pongs(
+    socket: &UdpSocket,
+    source: PingSource,
+    deadline: Instant,
+    mut on_server: impl FnMut(DiscoveredServer),
+) -> Result<(), ClientError> {
+    let mut seen: Vec<SocketAddr> = Vec::new();
+    let mut buf = BytesMut::with_capacity(RECV_BUFFER_SIZE);
+
+    loop {
+        // Each pong splits off what it used, so top the capacity back up
+        buf.clear();
+        buf.reserve(RECV_BUFFER_SIZE);
+        let addr = match timeout_at(deadline, socket.recv_buf_from(&mut buf)).await {
+            Ok(Ok((_, addr))) => addr,
+            Ok(Err(e)) => return Err(e.into()),
+            Err(_) => break,
+        };
+
+        // Anything else on the port (stray traffic, malformed pongs) is skipped
+        let pong = match Pong::new(buf.split().freeze(), addr, source.start_time) {
+            Ok(pong) => pong,
+            Err(e) => {
+                debug!("Ignoring invalid discovery response from {}: {}", addr, e);
+                continue;
+            }
+        };
+
+        if !seen.contains(&addr) {
+            seen.push(addr);
+            on_server(DiscoveredServer {
+                addr: addr.to_string(),
+                pong,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// A server that answered a LAN discovery broadcast or subnet scan
+#[derive(Record)]
+pub struct DiscoveredServer {
+    /// Address the pong came from
+    pub addr: String,
+    pub pong: Pong,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::unconnected_pong::UnconnectedPong;
+
+    #[tokio::test]
+    async fn test_receive_more_pongs_than_fit_in_buffer() {
+        let client = Client::new().await.expect("Failed to create client");
+        let socket = client.source.bind().await.unwrap();
+        let to = SocketAddr::from((Ipv4Addr::LOCALHOST, socket.local_addr().unwrap().port()));
+
+        let mut pong = UnconnectedPong::new();
+        pong.pong.motd = "x".repeat(200);
+        let pong = pong.build().unwrap();
+
+        // Enough that together they'd overrun a single buffer several times
+        let responders = RECV_BUFFER_SIZE / pong.len() * 4;
+        let mut senders = Vec::new();
+        for _ in 0..responders {
+            let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            sender.send_to(&pong, to).await.unwrap();
+            senders.push(sender);
+        }
+
+        let mut servers = Vec::new();
+        let deadline = Instant::now() + Duration::from_millis(500);
+        receive_pongs(&socket, client.source, deadline, |server| {
+            servers.push(server)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(servers.len(), responders);
+        assert!(servers.iter().all(|server| server.pong.motd.len() == 200));
+    }
+}
@@ -0,0 +1,193 @@
+use std::net::SocketAddr;
+
+use bytes::{Bytes, BytesMut};
+use log::debug;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream};
+use tokio::time::{timeout, Duration};
+use uniffi::Record;
+
+use super::resolve::split_host_port;
+use super::{Client, ClientError, PingOptions, PingSource};
+use crate::proto::java::{
+    decode_frame, Handshake, JavaPing, JavaPong, StatusRequest, StatusResponse, DEFAULT_JAVA_PORT,
+};
+
+#[uniffi::export]
+impl Client {
+    /// Pings a Java Edition server with the Server List Ping protocol over TCP.
+    /// `addr` may omit the port, in which case 25565 is used.
+    pub async fn ping_java(&self, addr: String) -> Result<JavaServerStatus, ClientError> {
+        let source = self.source;
+        let timeout_duration = Duration::from_millis(PingOptions::default().timeout_ms);
+
+        self.runtime
+            .spawn(async move {
+                timeout(timeout_duration, java_status(source, addr))
+                    .await
+                    .map_err(|_| ClientError::Timeout)?
+            })
+            .await
+            .map_err(ClientError::from)?
+    }
+}
+
+async fn java_status(source: PingSource, addr: String) -> Result<JavaServerStatus, ClientError> {
+    let (host, port) = split_host_port(&addr)?;
+    let port = port.unwrap_or(DEFAULT_JAVA_PORT);
+    let server_addr = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|e| ClientError::invalid_address(&addr, e))?
+        .next()
+        .ok_or_else(|| ClientError::invalid_address(&addr, "no address found"))?;
+
+    debug!("Sending Java status request to {}", server_addr);
+
+    let mut stream = connect(source, server_addr).await?;
+    let mut buf = BytesMut::new();
+
+    let mut request = BytesMut::new();
+    Handshake::status(&host, port).encode_into(&mut request);
+    StatusRequest.encode_into(&mut request);
+    write(&mut stream, &request).await?;
+
+    let response = StatusResponse::from_bytes(read_packet(&mut stream, &mut buf).await?)?;
+    let status = response.status()?;
+
+    // The pong echoes our payload, so it doubles as the send timestamp
+    let payload = source.start_time.elapsed().as_millis() as i64;
+    write(&mut stream, &JavaPing { payload }.build()).await?;
+    let pong = JavaPong::from_bytes(read_packet(&mut stream, &mut buf).await?)?;
+    let latency_ms = (source.start_time.elapsed().as_millis() as i64 - pong.payload).max(0) as u64;
+
+    let players = status.players.as_ref();
+    Ok(JavaServerStatus {
+        motd: status.description_text(),
+        version: status.version.name.clone(),
+        protocol: status.version.protocol,
+        players_online: players.map(|p| p.online).unwrap_or_default(),
+        max_players: players.map(|p| p.max).unwrap_or_default(),
+        player_sample: players
+            .map(|p| p.sample.iter().map(|s| s.name.clone()).collect())
+            .unwrap_or_default(),
+        favicon: status.favicon,
+        latency_ms,
+        raw_json: response.json,
+    })
+}
+
+/// Connects from the client's bind address, if one was set
+async fn connect(source: PingSource, addr: SocketAddr) -> Result<TcpStream, ClientError> {
+    if source.bind_addr.ip().is_unspecified() {
+        return Ok(TcpStream::connect(addr).await?);
+    }
+
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4(),
+        SocketAddr::V6(_) => TcpSocket::new_v6(),
+    }?;
+    socket.bind(source.bind_addr)?;
+    Ok(socket.connect(addr).await?)
+}
+
+async fn write(stream: &mut TcpStream, data: &[u8]) -> Result<(), ClientError> {
+    Ok(stream.write_all(data).await?)
+}
+
+/// Reads from the stream until one full packet is buffered
+async fn read_packet(stream: &mut TcpStream, buf: &mut BytesMut) -> Result<Bytes, ClientError> {
+    loop {
+        if let Some(packet) = decode_frame(buf)? {
+            return Ok(packet);
+        }
+
+        let read = stream.read_buf(buf).await?;
+        if read == 0 {
+            return Err(ClientError::truncated(
+                "Connection closed before a full packet arrived",
+            ));
+        }
+    }
+}
+
+/// Status of a Java Edition server
+#[derive(Record)]
+pub struct JavaServerStatus {
+    /// Description with chat formatting flattened to plain text
+    pub motd: String,
+    pub version: String,
+    pub protocol: i32,
+    pub players_online: i64,
+    pub max_players: i64,
+    /// Names from the server's player sample, which may be partial or empty
+    pub player_sample: Vec<String>,
+    /// PNG data URI
+    pub favicon: Option<String>,
+    pub latency_ms: u64,
+    pub raw_json: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::java::{write_varint, PONG_ID, STATUS_RESPONSE_ID};
+    use bytes::BufMut;
+    use tokio::net::TcpListener;
+
+    fn framed(body: &[u8]) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        write_varint(&mut buf, body.len() as i32);
+        buf.put_slice(body);
+        buf.to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_ping_java_refused() {
+        // Nothing listens on a port that was just released
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let client = Client::new().await.expect("Failed to create client");
+        let result = client.ping_java(addr).await;
+        assert!(matches!(result, Err(ClientError::Refused { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_ping_java() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = BytesMut::new();
+
+            // Handshake, then status request
+            read_packet(&mut stream, &mut buf).await.unwrap();
+            read_packet(&mut stream, &mut buf).await.unwrap();
+
+            let json = r#"{"version":{"name":"1.21.4","protocol":769},"players":{"max":20,"online":1,"sample":[{"name":"jhead","id":"0"}]},"description":{"text":"A ","extra":[{"text":"Server"}]}}"#;
+            let mut body = BytesMut::new();
+            body.put_u8(STATUS_RESPONSE_ID);
+            write_varint(&mut body, json.len() as i32);
+            body.put_slice(json.as_bytes());
+            stream.write_all(&framed(&body)).await.unwrap();
+
+            // Echo the ping back as the pong
+            let mut ping = read_packet(&mut stream, &mut buf).await.unwrap().to_vec();
+            ping[0] = PONG_ID;
+            stream.write_all(&framed(&ping)).await.unwrap();
+        });
+
+        let client = Client::new().await.expect("Failed to create client");
+        let status = client.ping_java(addr).await.expect("Failed to ping");
+
+        assert_eq!(status.motd, "A Server");
+        assert_eq!(status.version, "1.21.4");
+        assert_eq!(status.protocol, 769);
+        assert_eq!(status.players_online, 1);
+        assert_eq!(status.max_players, 20);
+        assert_eq!(status.player_sample, vec!["jhead"]);
+        assert!(status.latency_ms < 1000);
+    }
+}
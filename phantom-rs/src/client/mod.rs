@@ -1,39 +1,201 @@
+mod cancel;
+mod discovery;
+mod java;
+mod proxied;
+mod query;
+mod resolve;
+mod scan;
+mod socket;
+mod socks;
+mod stats;
+mod watch;
+
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
 use std::time::Instant;
 
 use bytes::Bytes;
+use futures::stream::{self, StreamExt};
 use log::debug;
 use once_cell::sync::Lazy;
 use rand::Rng;
 use tokio::net::UdpSocket;
 use tokio::runtime::{Handle, Runtime};
 use tokio::time::{timeout, Duration};
+use tokio_util::sync::CancellationToken;
 use uniffi::Record;
 
+use crate::api::{ErrorCode, IoErrorKind};
 use crate::proto::unconnected_ping::UnconnectedPing;
-use crate::proto::unconnected_pong::{UnconnectedPong, UNCONNECTED_PONG_ID};
+use crate::proto::unconnected_pong::UnconnectedPongRef;
+use crate::proto::{versions, ProtoError};
+use socket::PingSockets;
+
+pub use cancel::CancelToken;
+pub use discovery::{DiscoveredServer, DiscoveryListener};
+pub use java::JavaServerStatus;
+pub use proxied::ProxiedPong;
+pub use query::{QueryBasic, QueryFull};
+pub use scan::ScanOptions;
+pub use stats::PingStats;
+pub use watch::{AsyncWatchListener, WatchEvent, WatchHandle, WatchListener};
+
+/// Most pings `ping_many` and `ping_stats` keep in flight at once
+pub(super) const MAX_CONCURRENT_PINGS: usize = 16;
 
 /// A simple client for pinging MCPE servers
 #[derive(uniffi::Object)]
 pub struct Client {
-    client_id: [u8; 8],
-    client_start_time: Instant,
+    source: PingSource,
+    sockets: Arc<PingSockets>,
     runtime: Handle,
 }
 
-#[derive(Debug, thiserror::Error, uniffi::Error)]
-#[uniffi(flat_error)]
+/// The parts of a `Client` that background tasks need, cheap to copy into them
+#[derive(Debug, Clone, Copy)]
+struct PingSource {
+    client_id: [u8; 8],
+    start_time: Instant,
+    bind_addr: SocketAddr,
+}
+
+impl PingSource {
+    /// Builds a ping stamped with the current time
+    fn ping(&self) -> UnconnectedPing {
+        UnconnectedPing::new(self.client_id, elapsed_millis_bytes(self.start_time))
+    }
+
+    /// Binds a broadcast-capable socket on the client's local address
+    async fn bind(&self) -> Result<UdpSocket, ClientError> {
+        let socket = UdpSocket::bind(self.bind_addr).await?;
+        socket.set_broadcast(true)?;
+        Ok(socket)
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error, uniffi::Error)]
 pub enum ClientError {
-    #[error("Client encountered an IO error: {0}")]
-    IoError(String),
+    #[error("Client encountered an IO error: {message}")]
+    IoError { kind: IoErrorKind, message: String },
 
     #[error("Client encountered a timeout while waiting for a ping response")]
     Timeout,
 
-    #[error("Unable to ping invalid address: {0}")]
-    InvalidAddress(String),
+    #[error("Unable to ping invalid address {address}: {reason}")]
+    InvalidAddress { address: String, reason: String },
 
-    #[error("Invalid response from server: {0}")]
-    InvalidResponse(String),
+    #[error("Invalid response from server: {message}")]
+    InvalidResponse { message: String },
+
+    #[error("Server is unreachable: {message}")]
+    Unreachable { message: String },
+
+    #[error("Server refused the connection: {message}")]
+    Refused { message: String },
+
+    #[error("Response from server was cut short: {message}")]
+    Truncated { message: String },
+
+    #[error("Client operation was cancelled")]
+    Cancelled,
+
+    #[error("Client task failed: {message}")]
+    TaskFailed { message: String },
+}
+
+/// The stable code for `error`
+#[uniffi::export]
+pub fn client_error_code(error: ClientError) -> ErrorCode {
+    error.code()
+}
+
+impl ClientError {
+    /// See `ErrorCode`
+    pub fn code(&self) -> ErrorCode {
+        let (code, key) = match self {
+            ClientError::IoError { .. } => (200, "client.io"),
+            ClientError::Timeout => (201, "client.timeout"),
+            ClientError::InvalidAddress { .. } => (202, "client.invalid_address"),
+            ClientError::InvalidResponse { .. } => (203, "client.invalid_response"),
+            ClientError::Unreachable { .. } => (204, "client.unreachable"),
+            ClientError::Refused { .. } => (205, "client.refused"),
+            ClientError::Truncated { .. } => (206, "client.truncated"),
+            ClientError::Cancelled => (207, "client.cancelled"),
+            ClientError::TaskFailed { .. } => (208, "client.task_failed"),
+        };
+        ErrorCode::new(code, key)
+    }
+
+    pub(crate) fn invalid_address(address: impl ToString, reason: impl ToString) -> Self {
+        ClientError::InvalidAddress {
+            address: address.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub(crate) fn truncated(message: impl ToString) -> Self {
+        ClientError::Truncated {
+            message: message.to_string(),
+        }
+    }
+
+    pub(crate) fn refused(message: impl ToString) -> Self {
+        ClientError::Refused {
+            message: message.to_string(),
+        }
+    }
+
+    pub(crate) fn invalid_response(message: impl ToString) -> Self {
+        ClientError::InvalidResponse {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<ProtoError> for ClientError {
+    fn from(error: ProtoError) -> Self {
+        match error {
+            ProtoError::TooShort { .. } | ProtoError::TruncatedField { .. } => {
+                ClientError::truncated(error)
+            }
+            _ => ClientError::invalid_response(error),
+        }
+    }
+}
+
+impl From<std::io::Error> for ClientError {
+    fn from(error: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+
+        // ICMP errors come back as these kinds where the OS reports them: port
+        // unreachable as a refused or reset connection, no route as unreachable
+        match error.kind() {
+            ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset => {
+                ClientError::refused(error)
+            }
+            ErrorKind::HostUnreachable | ErrorKind::NetworkUnreachable => {
+                ClientError::Unreachable {
+                    message: error.to_string(),
+                }
+            }
+            ErrorKind::TimedOut => ClientError::Timeout,
+            ErrorKind::UnexpectedEof => ClientError::truncated(error),
+            kind => ClientError::IoError {
+                kind: kind.into(),
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+/// A task the client spawned on its runtime panicked or was cancelled
+impl From<tokio::task::JoinError> for ClientError {
+    fn from(error: tokio::task::JoinError) -> Self {
+        ClientError::TaskFailed {
+            message: error.to_string(),
+        }
+    }
 }
 
 #[uniffi::export]
@@ -41,6 +203,105 @@ impl Client {
     /// Creates a new client bound to a random port
     #[uniffi::constructor]
     pub async fn new() -> Result<Self, ClientError> {
+        Self::with_config(ClientConfig::default()).await
+    }
+
+    /// Creates a client with the given settings. Anything left unset gets the
+    /// same default as `new`.
+    #[uniffi::constructor]
+    pub async fn with_config(config: ClientConfig) -> Result<Self, ClientError> {
+        let bind_addr = match &config.bind_address {
+            Some(bind_address) => parse_bind_address(bind_address)?,
+            None => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        };
+        let client_id = config
+            .client_guid
+            .unwrap_or_else(|| rand::rng().random())
+            .to_be_bytes();
+
+        let runtime = Self::runtime();
+        let sockets = match config.socks5_proxy {
+            Some(proxy) => {
+                let relay = runtime
+                    .spawn(async move { socks::associate(&proxy).await })
+                    .await
+                    .map_err(ClientError::from)??;
+                PingSockets::relayed(bind_addr, relay, &runtime)?
+            }
+            None => PingSockets::bind(bind_addr, &runtime)?,
+        };
+
+        Ok(Self {
+            source: PingSource {
+                client_id,
+                start_time: Instant::now(),
+                bind_addr,
+            },
+            sockets: Arc::new(sockets),
+            runtime,
+        })
+    }
+
+    /// Creates a client whose sockets bind to `bind_address`, so pings leave
+    /// through that interface on multi-homed machines. Accepts an IP address,
+    /// with or without a port; without one a random port is used.
+    #[uniffi::constructor]
+    pub async fn with_bind_address(bind_address: String) -> Result<Self, ClientError> {
+        Self::with_config(ClientConfig {
+            bind_address: Some(bind_address),
+            ..ClientConfig::default()
+        })
+        .await
+    }
+
+    /// The RakNet client GUID this client sends in its pings
+    pub fn client_guid(&self) -> u64 {
+        u64::from_be_bytes(self.source.client_id)
+    }
+}
+
+/// Settings for `Client::with_config`
+#[derive(Debug, Clone, Default, Record)]
+pub struct ClientConfig {
+    /// Local IP address, with or without a port, that the client's sockets
+    /// bind to. Unset binds to all interfaces on a random port.
+    #[uniffi(default = None)]
+    pub bind_address: Option<String>,
+    /// RakNet client GUID to send in pings. Some servers and anti-bot plugins
+    /// track or whitelist it, so tooling may need a stable one. Unset picks a
+    /// random GUID for each client.
+    #[uniffi(default = None)]
+    pub client_guid: Option<u64>,
+    /// "host:port" of a SOCKS5 proxy to tunnel pings through with UDP
+    /// ASSOCIATE, e.g. an SSH dynamic forward to a jump host. The proxy must
+    /// allow unauthenticated access. LAN discovery broadcasts always stay local.
+    #[uniffi(default = None)]
+    pub socks5_proxy: Option<String>,
+}
+
+fn parse_bind_address(bind_address: &str) -> Result<SocketAddr, ClientError> {
+    bind_address
+        .parse::<SocketAddr>()
+        .or_else(|_| {
+            bind_address
+                .parse::<IpAddr>()
+                .map(|ip| SocketAddr::new(ip, 0))
+        })
+        .map_err(|e| ClientError::invalid_address(bind_address, e))
+}
+
+impl Client {
+    fn ping_task(
+        &self,
+        addr: String,
+        options: PingOptions,
+    ) -> impl Future<Output = Result<Pong, ClientError>> + Send + 'static {
+        let source = self.source;
+        let sockets = self.sockets.clone();
+        async move { send_ping(&sockets, source, addr, options).await }
+    }
+
+    fn runtime() -> Handle {
         static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
             tokio::runtime::Builder::new_multi_thread()
                 .enable_all()
@@ -48,24 +309,108 @@ impl Client {
                 .unwrap()
         });
 
-        let client_id = rand::rng().random::<[u8; 8]>();
-
-        Ok(Self {
-            client_id,
-            client_start_time: Instant::now(),
-            runtime: RUNTIME.handle().clone(),
-        })
+        RUNTIME.handle().clone()
     }
+}
 
-    /// Pings a server and returns the pong response
+#[uniffi::export]
+impl Client {
+    /// Pings a server and returns the pong response. `addr` may leave out the
+    /// port, in which case a `_minecraft._udp` SRV record or port 19132 is used.
     pub async fn ping(&self, addr: String) -> Result<Pong, ClientError> {
-        let ping_time = elapsed_millis_bytes(self.client_start_time);
-        let client_id = self.client_id;
+        self.ping_with_options(addr, PingOptions::default()).await
+    }
 
-        self.runtime
-            .spawn(async move { send_ping(client_id, ping_time, addr).await })
-            .await
-            .map_err(|e| ClientError::IoError(e.to_string()))?
+    /// Pings a server, resending the ping per `options` until a pong arrives
+    pub async fn ping_with_options(
+        &self,
+        addr: String,
+        options: PingOptions,
+    ) -> Result<Pong, ClientError> {
+        let task = self.ping_task(addr, options);
+        self.run(CancellationToken::new(), task).await
+    }
+
+    /// Pings many servers concurrently and returns one result per address, in
+    /// the same order as `addrs`
+    pub async fn ping_many(&self, addrs: Vec<String>, options: PingOptions) -> Vec<PingResult> {
+        let source = self.source;
+        let sockets = self.sockets.clone();
+
+        let pinged = addrs.clone();
+        let results = self.runtime.spawn(async move {
+            let sockets = &sockets;
+            stream::iter(pinged)
+                .map(|addr| async move {
+                    let result = send_ping(sockets, source, addr.clone(), options).await;
+                    PingResult::new(addr, result)
+                })
+                .buffered(MAX_CONCURRENT_PINGS)
+                .collect::<Vec<_>>()
+                .await
+        });
+
+        match results.await {
+            Ok(results) => results,
+            // Every address shares the failure rather than going missing
+            Err(e) => {
+                let error = ClientError::from(e);
+                addrs
+                    .into_iter()
+                    .map(|addr| PingResult::new(addr, Err(error.clone())))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Outcome of pinging one address with `Client::ping_many`
+#[derive(Record)]
+pub struct PingResult {
+    pub addr: String,
+    pub pong: Option<Pong>,
+    /// Why the ping failed; see `ClientError::code` for a stable code
+    pub error: Option<ClientError>,
+}
+
+impl PingResult {
+    fn new(addr: String, result: Result<Pong, ClientError>) -> Self {
+        match result {
+            Ok(pong) => Self {
+                addr,
+                pong: Some(pong),
+                error: None,
+            },
+            Err(e) => Self {
+                addr,
+                pong: None,
+                error: Some(e),
+            },
+        }
+    }
+}
+
+/// Timeout and retry policy for pings
+#[derive(Debug, Clone, Copy, Record)]
+pub struct PingOptions {
+    /// Total time to wait for a pong, across all attempts
+    #[uniffi(default = 5000)]
+    pub timeout_ms: u64,
+    /// How many times to resend the ping if no pong has arrived yet
+    #[uniffi(default = 2)]
+    pub retries: u32,
+    /// Time to wait for a pong before resending
+    #[uniffi(default = 1000)]
+    pub retry_interval_ms: u64,
+}
+
+impl Default for PingOptions {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 5000,
+            retries: 2,
+            retry_interval_ms: 1000,
+        }
     }
 }
 
@@ -81,74 +426,94 @@ fn elapsed_millis_bytes(start: Instant) -> [u8; 8] {
 }
 
 async fn send_ping(
-    client_id: [u8; 8],
-    ping_time: [u8; 8],
+    sockets: &PingSockets,
+    source: PingSource,
     addr: String,
+    options: PingOptions,
 ) -> Result<Pong, ClientError> {
-    // Create and send ping packet
-    let ping = UnconnectedPing::new(client_id, ping_time);
-    let ping_bytes = ping.build();
-
-    let socket = UdpSocket::bind("0.0.0.0:0")
-        .await
-        .map_err(|e| ClientError::IoError(e.to_string()))?;
-    socket
-        .set_broadcast(true)
-        .map_err(|e| ClientError::IoError(e.to_string()))?;
+    let candidates = resolve::resolve(&addr).await?;
+    ping_candidates(sockets, source, &candidates, options).await
+}
 
-    let addr = tokio::net::lookup_host(&addr)
-        .await
-        .map_err(|e| ClientError::InvalidAddress(e.to_string()))?
-        .next()
-        .ok_or_else(|| ClientError::InvalidAddress("No address found".to_string()))?;
+/// Pings each resolved address in turn until one answers. The timeout is split
+/// evenly between them, with the last one getting whatever is left, so a dead
+/// AAAA record doesn't eat the whole budget. `Pong::responder_addr` tells which
+/// address answered.
+async fn ping_candidates(
+    sockets: &PingSockets,
+    source: PingSource,
+    candidates: &[SocketAddr],
+    options: PingOptions,
+) -> Result<Pong, ClientError> {
+    if candidates.is_empty() {
+        return Err(ClientError::invalid_address("", "no addresses to ping"));
+    }
+    let timeout_duration = Duration::from_millis(options.timeout_ms);
+    let deadline = Instant::now() + timeout_duration;
+    let per_candidate = timeout_duration / candidates.len() as u32;
 
-    debug!("Sending ping to {}", addr);
+    let mut last_error = ClientError::Timeout;
+    for (i, addr) in candidates.iter().enumerate() {
+        let budget = if i + 1 == candidates.len() {
+            deadline.saturating_duration_since(Instant::now())
+        } else {
+            per_candidate
+        };
 
-    socket
-        .send_to(&ping_bytes, addr)
-        .await
-        .map_err(|e| ClientError::IoError(e.to_string()))?;
+        match ping_addr(sockets, source, *addr, options, budget).await {
+            Ok(pong) => return Ok(pong),
+            Err(e) => {
+                debug!("No pong from {}: {}", addr, e);
+                last_error = e;
+            }
+        }
+    }
 
-    // Wait for response with timeout
-    let mut buf = vec![0; 1024];
-    let timeout_duration = Duration::from_secs(5);
+    Err(last_error)
+}
 
-    let (len, _) = timeout(timeout_duration, socket.recv_from(&mut buf))
-        .await
-        .map_err(|_| ClientError::Timeout)?
-        .map_err(|e| ClientError::IoError(e.to_string()))?;
+async fn ping_addr(
+    sockets: &PingSockets,
+    source: PingSource,
+    addr: SocketAddr,
+    options: PingOptions,
+    timeout_duration: Duration,
+) -> Result<Pong, ClientError> {
+    // Wait for response with timeout, resending the ping every retry interval.
+    // A late pong to an earlier attempt is just as good, so all attempts share
+    // one exchange on the client's socket.
+    let mut exchange = sockets.for_addr(&addr)?.exchange(addr);
+    let retry_interval = Duration::from_millis(options.retry_interval_ms);
 
-    let response = Bytes::from(buf[..len].to_vec());
+    let (response, responder) = timeout(timeout_duration, async {
+        let mut attempt = 0;
+        loop {
+            // Each attempt carries its own send time, which the pong echoes back,
+            // so latency is measured against whichever attempt was answered
+            debug!("Sending ping to {} (attempt {})", addr, attempt + 1);
+            exchange.send(&source).await?;
 
-    // Verify packet ID
-    if response.is_empty() || response[0] != UNCONNECTED_PONG_ID {
-        return Err(ClientError::InvalidResponse(
-            "Invalid response packet ID".to_string(),
-        ));
-    }
+            if attempt >= options.retries {
+                return exchange.recv().await;
+            }
 
-    // Parse pong response
-    let pong = UnconnectedPong::from_bytes(response)
-        .map_err(|e| ClientError::InvalidResponse(e.to_string()))?;
-
-    Ok(Pong {
-        edition: pong.pong.edition,
-        motd: pong.pong.motd,
-        protocol_version: pong.pong.protocol_version,
-        version: pong.pong.version,
-        players: pong.pong.players,
-        max_players: pong.pong.max_players,
-        server_id: pong.pong.server_id,
-        sub_motd: pong.pong.sub_motd,
-        game_mode: pong.pong.game_mode,
-        game_mode_numeric: pong.pong.game_mode_numeric,
-        port4: pong.pong.port4,
-        port6: pong.pong.port6,
+            if let Ok(pong) = timeout(retry_interval, exchange.recv()).await {
+                return pong;
+            }
+            attempt += 1;
+        }
     })
+    .await
+    .map_err(|_| ClientError::Timeout)??;
+
+    // The socket only routes pongs here, so the packet ID is already checked
+    Ok(Pong::new(response, responder, source.start_time)?)
 }
 
-/// Response data from a server ping
-#[derive(Record)]
+/// Response data from a server ping. The string fields are the raw pong payload
+/// values; the typed fields are parsed from them and are `None` when a server
+/// sends something that isn't a valid number.
+#[derive(Debug, Clone, Record)]
 pub struct Pong {
     pub edition: String,
     pub motd: String,
@@ -162,11 +527,93 @@ pub struct Pong {
     pub game_mode_numeric: String,
     pub port4: String,
     pub port6: String,
+    /// Round-trip time of the ping this pong answered
+    pub latency_ms: u64,
+    pub player_count: Option<u32>,
+    pub max_player_count: Option<u32>,
+    pub protocol: Option<u32>,
+    /// Release that introduced `protocol`, when it's a known number
+    pub game_version: Option<String>,
+    pub ipv4_port: Option<u16>,
+    pub ipv6_port: Option<u16>,
+    /// GUID from the packet header, which should match `server_id`
+    pub server_guid: u64,
+    /// Address the pong came from. With broadcasts or proxies in the way this
+    /// can differ from the address that was pinged.
+    pub responder_addr: String,
+    /// The semicolon-separated payload exactly as the server sent it
+    pub raw: String,
+}
+
+impl Pong {
+    /// True if both pongs advertise the same server state, ignoring latency
+    pub fn same_status(&self, other: &Pong) -> bool {
+        self.edition == other.edition
+            && self.motd == other.motd
+            && self.protocol_version == other.protocol_version
+            && self.version == other.version
+            && self.players == other.players
+            && self.max_players == other.max_players
+            && self.server_id == other.server_id
+            && self.sub_motd == other.sub_motd
+            && self.game_mode == other.game_mode
+            && self.game_mode_numeric == other.game_mode_numeric
+            && self.port4 == other.port4
+            && self.port6 == other.port6
+    }
+
+    /// Parses a pong packet, computing latency from the ping time it echoes
+    fn new(
+        response: Bytes,
+        responder: SocketAddr,
+        client_start_time: Instant,
+    ) -> Result<Self, ProtoError> {
+        let pong_ref = UnconnectedPongRef::from_bytes(response)?;
+        let raw = pong_ref.payload_str()?.to_string();
+        let pong = pong_ref.parse()?;
+
+        let sent_at = u64::from_be_bytes(pong.ping_time);
+        let now = client_start_time.elapsed().as_millis() as u64;
+        let data = &pong.pong;
+
+        Ok(Self {
+            server_guid: pong.server_guid.into(),
+            responder_addr: responder.to_string(),
+            raw,
+            player_count: data.players.trim().parse().ok(),
+            max_player_count: data.max_players.trim().parse().ok(),
+            protocol: data.protocol_version.trim().parse().ok(),
+            game_version: data
+                .protocol_version
+                .trim()
+                .parse()
+                .ok()
+                .and_then(versions::game_version)
+                .map(str::to_string),
+            ipv4_port: data.port4.trim().parse().ok(),
+            ipv6_port: data.port6.trim().parse().ok(),
+            edition: pong.pong.edition,
+            motd: pong.pong.motd,
+            protocol_version: pong.pong.protocol_version,
+            version: pong.pong.version,
+            players: pong.pong.players,
+            max_players: pong.pong.max_players,
+            server_id: pong.pong.server_id,
+            sub_motd: pong.pong.sub_motd,
+            game_mode: pong.pong.game_mode,
+            game_mode_numeric: pong.pong.game_mode_numeric,
+            port4: pong.pong.port4,
+            port6: pong.pong.port6,
+            latency_ms: now.saturating_sub(sent_at),
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::proto::unconnected_pong::UnconnectedPong;
+    use crate::proto::ServerGuid;
 
     #[tokio::test]
     async fn test_ping() {
@@ -177,4 +624,270 @@ mod tests {
         let result = client.ping(addr).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_ping_reports_latency() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            let (len, client_addr) = server.recv_from(&mut buf).await.unwrap();
+            let ping = UnconnectedPing::from_bytes(Bytes::copy_from_slice(&buf[..len])).unwrap();
+
+            tokio::time::sleep(Duration::from_millis(50)).await;
+
+            let mut pong = UnconnectedPong::new();
+            pong.ping_time = ping.ping_time;
+            pong.set_server_guid(ServerGuid(42));
+            server
+                .send_to(&pong.build().unwrap(), client_addr)
+                .await
+                .unwrap();
+        });
+
+        let client = Client::new().await.expect("Failed to create client");
+        let pong = client.ping(addr.clone()).await.expect("Failed to ping");
+
+        assert_eq!(pong.edition, "MCPE");
+        assert_eq!(pong.players, "0");
+        assert_eq!(pong.player_count, Some(0));
+        assert_eq!(pong.max_player_count, Some(1));
+        assert_eq!(pong.protocol, Some(800));
+        assert_eq!(pong.game_version.as_deref(), Some("1.21.80"));
+        assert_eq!(pong.ipv4_port, Some(19132));
+        assert_eq!(pong.server_guid, 42);
+        assert_eq!(pong.responder_addr, addr);
+        assert!(pong.raw.starts_with("MCPE;"));
+        assert!(pong.latency_ms >= 50);
+        assert!(pong.latency_ms < 1000);
+    }
+
+    #[tokio::test]
+    async fn test_pings_share_one_socket() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+
+        // Answers every ping, so concurrent pings each need their own pong back
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            let mut sources = Vec::new();
+            loop {
+                let (len, client_addr) = server.recv_from(&mut buf).await.unwrap();
+                let ping =
+                    UnconnectedPing::from_bytes(Bytes::copy_from_slice(&buf[..len])).unwrap();
+                sources.push(client_addr);
+                assert!(sources.iter().all(|source| *source == sources[0]));
+
+                let mut pong = UnconnectedPong::new();
+                pong.ping_time = ping.ping_time;
+                server
+                    .send_to(&pong.build().unwrap(), client_addr)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let client = Client::new().await.expect("Failed to create client");
+        let results = client
+            .ping_many(vec![addr.clone(); 4], PingOptions::default())
+            .await;
+        assert!(results.iter().all(|result| result.pong.is_some()));
+
+        let pong = client.ping(addr).await.expect("Failed to ping");
+        let local = client
+            .sockets
+            .for_addr(&pong.responder_addr.parse().unwrap())
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        assert_ne!(local.port(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_ping_falls_back_to_next_address() {
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            let (len, client_addr) = server.recv_from(&mut buf).await.unwrap();
+            let ping = UnconnectedPing::from_bytes(Bytes::copy_from_slice(&buf[..len])).unwrap();
+
+            let mut pong = UnconnectedPong::new();
+            pong.ping_time = ping.ping_time;
+            server
+                .send_to(&pong.build().unwrap(), client_addr)
+                .await
+                .unwrap();
+        });
+
+        let client = Client::new().await.expect("Failed to create client");
+        let candidates = [silent.local_addr().unwrap(), server_addr];
+        let options = PingOptions {
+            timeout_ms: 1000,
+            ..PingOptions::default()
+        };
+
+        let started = Instant::now();
+        let pong = ping_candidates(&client.sockets, client.source, &candidates, options)
+            .await
+            .expect("Failed to ping");
+
+        assert_eq!(pong.responder_addr, server_addr.to_string());
+        assert!(started.elapsed() < Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        use std::io::{Error, ErrorKind};
+
+        let error = |kind| ClientError::from(Error::from(kind));
+        assert!(matches!(
+            error(ErrorKind::ConnectionRefused),
+            ClientError::Refused { .. }
+        ));
+        assert!(matches!(
+            error(ErrorKind::HostUnreachable),
+            ClientError::Unreachable { .. }
+        ));
+        assert!(matches!(error(ErrorKind::TimedOut), ClientError::Timeout));
+        assert!(matches!(
+            error(ErrorKind::PermissionDenied),
+            ClientError::IoError { .. }
+        ));
+
+        let truncated = ProtoError::TooShort {
+            packet_id: 0x1c,
+            expected: 35,
+            actual: 3,
+        };
+        assert!(matches!(
+            ClientError::from(truncated),
+            ClientError::Truncated { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_with_config_client_guid() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+
+        let config = ClientConfig {
+            client_guid: Some(0x1122334455667788),
+            ..ClientConfig::default()
+        };
+        let client = Client::with_config(config)
+            .await
+            .expect("Failed to create client");
+        assert_eq!(client.client_guid(), 0x1122334455667788);
+
+        let options = PingOptions {
+            timeout_ms: 100,
+            retries: 0,
+            ..PingOptions::default()
+        };
+        let _ = client.ping_with_options(addr, options).await;
+
+        let mut buf = [0u8; 64];
+        let (len, _) = server.recv_from(&mut buf).await.unwrap();
+        let ping = UnconnectedPing::from_bytes(Bytes::copy_from_slice(&buf[..len])).unwrap();
+        assert_eq!(ping.client_id, 0x1122334455667788u64.to_be_bytes());
+
+        let other = Client::new().await.expect("Failed to create client");
+        assert_ne!(other.client_guid(), client.client_guid());
+    }
+
+    #[tokio::test]
+    async fn test_with_bind_address() {
+        // A port that was free a moment ago, so the ping's source is known
+        let bind_addr = std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = Client::with_bind_address(bind_addr.to_string())
+            .await
+            .expect("Failed to create client");
+
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        let source = tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            let (len, client_addr) = server.recv_from(&mut buf).await.unwrap();
+            let ping = UnconnectedPing::from_bytes(Bytes::copy_from_slice(&buf[..len])).unwrap();
+
+            let mut pong = UnconnectedPong::new();
+            pong.ping_time = ping.ping_time;
+            server
+                .send_to(&pong.build().unwrap(), client_addr)
+                .await
+                .unwrap();
+            client_addr
+        });
+
+        client.ping(addr).await.expect("Failed to ping");
+        assert_eq!(source.await.unwrap(), bind_addr);
+
+        assert!(Client::with_bind_address("127.0.0.1".to_string())
+            .await
+            .is_ok());
+        assert!(matches!(
+            Client::with_bind_address("eth0".to_string()).await,
+            Err(ClientError::InvalidAddress { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ping_many_keeps_order() {
+        let client = Client::new().await.expect("Failed to create client");
+        let addrs = vec!["127.0.0.1:19132".to_string(), "not an address".to_string()];
+
+        let options = PingOptions {
+            timeout_ms: 500,
+            ..PingOptions::default()
+        };
+        let results = client.ping_many(addrs.clone(), options).await;
+
+        assert_eq!(results.len(), 2);
+        for (result, addr) in results.iter().zip(&addrs) {
+            assert_eq!(&result.addr, addr);
+            assert!(result.pong.is_none());
+            assert!(result.error.is_some());
+        }
+        assert!(matches!(
+            results[1].error,
+            Some(ClientError::InvalidAddress { .. })
+        ));
+
+        let result = ping_candidates(&client.sockets, client.source, &[], options).await;
+        assert!(matches!(result, Err(ClientError::InvalidAddress { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_ping_with_options_times_out() {
+        let client = Client::new().await.expect("Failed to create client");
+
+        // Nothing answers on a bound-but-idle socket, so every retry goes unanswered
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = silent.local_addr().unwrap().to_string();
+
+        let options = PingOptions {
+            timeout_ms: 300,
+            retries: 2,
+            retry_interval_ms: 100,
+        };
+        let started = Instant::now();
+        let result = client.ping_with_options(addr, options).await;
+
+        assert!(matches!(result, Err(ClientError::Timeout)));
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let mut buf = [0u8; 64];
+        let mut pings = 0;
+        while let Ok(Ok(_)) = timeout(Duration::from_millis(10), silent.recv_from(&mut buf)).await {
+            pings += 1;
+        }
+        assert_eq!(pings, 3);
+    }
 }
@@ -0,0 +1,118 @@
+use uniffi::Record;
+
+use super::{send_ping, Client, ClientError, PingOptions, Pong};
+
+#[uniffi::export]
+impl Client {
+    /// Pings `server_addr` through the phantom proxy listening on `proxy_addr`,
+    /// and directly from this client at the same time. The direct round trip
+    /// is this client's path to the server, not the proxy's; the two only
+    /// match when the proxy runs on this machine or this LAN.
+    pub async fn ping_through_proxy(
+        &self,
+        proxy_addr: String,
+        server_addr: String,
+        options: PingOptions,
+    ) -> Result<ProxiedPong, ClientError> {
+        let source = self.source;
+        let sockets = self.sockets.clone();
+
+        self.runtime
+            .spawn(async move {
+                let (proxied, direct) = tokio::join!(
+                    send_ping(&sockets, source, proxy_addr, options),
+                    send_ping(&sockets, source, server_addr, options),
+                );
+                Ok(ProxiedPong::new(proxied?, direct.ok()))
+            })
+            .await
+            .map_err(ClientError::from)?
+    }
+}
+
+/// Result of `Client::ping_through_proxy`
+#[derive(Record)]
+pub struct ProxiedPong {
+    /// The pong as relayed by the proxy
+    pub pong: Pong,
+    /// Full round trip through the proxy
+    pub total_latency_ms: u64,
+    /// Round trip from this client straight to the server, or `None` if it
+    /// didn't answer a direct ping (for example when only the proxy can reach
+    /// it). Measured on this client's path, not the proxy's.
+    pub direct_latency_ms: Option<u64>,
+    /// How much longer the proxied round trip took than the direct one. An
+    /// estimate of the proxy hop that only holds when both paths to the server
+    /// are the same.
+    pub local_latency_ms: Option<u64>,
+}
+
+impl ProxiedPong {
+    fn new(pong: Pong, direct: Option<Pong>) -> Self {
+        let total_latency_ms = pong.latency_ms;
+        let direct_latency_ms = direct.map(|direct| direct.latency_ms);
+
+        Self {
+            pong,
+            total_latency_ms,
+            direct_latency_ms,
+            local_latency_ms: direct_latency_ms
+                .map(|direct| total_latency_ms.saturating_sub(direct)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::unconnected_ping::UnconnectedPing;
+    use crate::proto::unconnected_pong::UnconnectedPong;
+    use bytes::Bytes;
+    use tokio::net::UdpSocket;
+    use tokio::time::{sleep, Duration};
+
+    #[tokio::test]
+    async fn test_ping_through_proxy_splits_latency() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            loop {
+                let (len, from) = server.recv_from(&mut buf).await.unwrap();
+                let ping =
+                    UnconnectedPing::from_bytes(Bytes::copy_from_slice(&buf[..len])).unwrap();
+                let mut pong = UnconnectedPong::new();
+                pong.ping_time = ping.ping_time;
+                server.send_to(&pong.build().unwrap(), from).await.unwrap();
+            }
+        });
+
+        // A slow relay standing in for the proxy
+        let proxy = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        tokio::spawn(async move {
+            let upstream = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let mut buf = [0u8; 1500];
+            let (len, client) = proxy.recv_from(&mut buf).await.unwrap();
+            sleep(Duration::from_millis(50)).await;
+            upstream.send_to(&buf[..len], server_addr).await.unwrap();
+            let (len, _) = upstream.recv_from(&mut buf).await.unwrap();
+            proxy.send_to(&buf[..len], client).await.unwrap();
+        });
+
+        let client = Client::new().await.expect("Failed to create client");
+        let result = client
+            .ping_through_proxy(
+                proxy_addr.to_string(),
+                server_addr.to_string(),
+                PingOptions::default(),
+            )
+            .await
+            .expect("Failed to ping through proxy");
+
+        assert_eq!(result.pong.responder_addr, proxy_addr.to_string());
+        assert!(result.total_latency_ms >= 50);
+        assert!(result.direct_latency_ms.unwrap() < 50);
+        assert!(result.local_latency_ms.unwrap() > 0);
+    }
+}
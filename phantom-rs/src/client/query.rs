@@ -0,0 +1,194 @@
+use std::net::SocketAddr;
+
+use bytes::{Bytes, BytesMut};
+use log::debug;
+use tokio::net::UdpSocket;
+use tokio::time::{timeout, Duration};
+use uniffi::Record;
+
+use super::{Client, ClientError, PingSource};
+use crate::proto::query::{
+    BasicStat, FullStat, QueryHandshakeRequest, QueryHandshakeResponse, QueryStatRequest,
+};
+
+/// Full stat replies list every player and plugin, so they often span more
+/// than one MTU. This fits the largest UDP payload.
+const QUERY_BUFFER_SIZE: usize = 64 * 1024;
+
+#[uniffi::export]
+impl Client {
+    /// Queries a server's basic stats over the GS4 query protocol. The server
+    /// must have query enabled, and `addr` must point at its query port.
+    pub async fn query_basic(&self, addr: String) -> Result<QueryBasic, ClientError> {
+        let session_id = rand::random::<i32>();
+        let source = self.source;
+
+        self.runtime
+            .spawn(async move {
+                let stat = query_stat(source, session_id, addr, false).await?;
+                let stat = BasicStat::from_bytes(stat)?;
+                Ok(QueryBasic {
+                    motd: stat.motd,
+                    game_type: stat.game_type,
+                    map: stat.map,
+                    num_players: stat.num_players,
+                    max_players: stat.max_players,
+                    host_port: stat.host_port,
+                    host_ip: stat.host_ip,
+                })
+            })
+            .await
+            .map_err(ClientError::from)?
+    }
+
+    /// Queries a server's full stats over the GS4 query protocol, including the
+    /// player list and plugins
+    pub async fn query_full(&self, addr: String) -> Result<QueryFull, ClientError> {
+        let session_id = rand::random::<i32>();
+        let source = self.source;
+
+        self.runtime
+            .spawn(async move {
+                let stat = query_stat(source, session_id, addr, true).await?;
+                Ok(QueryFull::from(FullStat::from_bytes(stat)?))
+            })
+            .await
+            .map_err(ClientError::from)?
+    }
+}
+
+/// Performs the query handshake and returns the raw stat response
+pub(super) async fn query_stat(
+    source: PingSource,
+    session_id: i32,
+    addr: String,
+    full: bool,
+) -> Result<Bytes, ClientError> {
+    let socket = source.bind().await?;
+
+    let addr = tokio::net::lookup_host(&addr)
+        .await
+        .map_err(|e| ClientError::invalid_address(&addr, e))?
+        .next()
+        .ok_or_else(|| ClientError::invalid_address(&addr, "no address found"))?;
+
+    debug!("Sending query handshake to {}", addr);
+
+    let handshake = QueryHandshakeRequest::new(session_id);
+    let response = request(&socket, addr, handshake.build()).await?;
+    let handshake = QueryHandshakeResponse::from_bytes(response)?;
+
+    let stat = QueryStatRequest {
+        session_id: handshake.session_id,
+        challenge_token: handshake.challenge_token,
+        full,
+    };
+    request(&socket, addr, stat.build()).await
+}
+
+async fn request(socket: &UdpSocket, addr: SocketAddr, data: Bytes) -> Result<Bytes, ClientError> {
+    socket.send_to(&data, addr).await?;
+
+    let mut buf = BytesMut::with_capacity(QUERY_BUFFER_SIZE);
+    let timeout_duration = Duration::from_secs(5);
+
+    timeout(timeout_duration, socket.recv_buf_from(&mut buf))
+        .await
+        .map_err(|_| ClientError::Timeout)??;
+
+    Ok(buf.freeze())
+}
+
+/// Basic stats from a GS4 query
+#[derive(Record)]
+pub struct QueryBasic {
+    pub motd: String,
+    pub game_type: String,
+    pub map: String,
+    pub num_players: String,
+    pub max_players: String,
+    pub host_port: u16,
+    pub host_ip: String,
+}
+
+/// Full stats from a GS4 query
+#[derive(Record)]
+pub struct QueryFull {
+    pub motd: String,
+    pub game_type: String,
+    pub game_id: String,
+    pub version: String,
+    /// Server software, e.g. "PocketMine-MP 5.0"
+    pub server_mod: String,
+    pub plugins: Vec<String>,
+    pub map: String,
+    pub num_players: String,
+    pub max_players: String,
+    pub host_port: u16,
+    pub host_ip: String,
+    pub players: Vec<String>,
+}
+
+impl From<FullStat> for QueryFull {
+    fn from(stat: FullStat) -> Self {
+        let (server_mod, plugins) = stat.plugins();
+        let value = |key: &str| stat.get(key).unwrap_or_default().to_string();
+
+        Self {
+            motd: value("hostname"),
+            game_type: value("gametype"),
+            game_id: value("game_id"),
+            version: value("version"),
+            server_mod,
+            plugins,
+            map: value("map"),
+            num_players: value("numplayers"),
+            max_players: value("maxplayers"),
+            host_port: value("hostport").parse().unwrap_or_default(),
+            host_ip: value("hostip"),
+            players: stat.players,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BufMut;
+
+    #[tokio::test]
+    async fn test_query_full_larger_than_mtu() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            let (_, client_addr) = server.recv_from(&mut buf).await.unwrap();
+            let session: [u8; 4] = buf[3..7].try_into().unwrap();
+            let mut handshake = vec![0x09];
+            handshake.extend_from_slice(&session);
+            handshake.extend_from_slice(b"9513307\x00");
+            server.send_to(&handshake, client_addr).await.unwrap();
+
+            let (_, client_addr) = server.recv_from(&mut buf).await.unwrap();
+            let mut stat = BytesMut::new();
+            stat.put_u8(0x00);
+            stat.put_slice(&session);
+            stat.put_slice(b"splitnum\x00\x80\x00");
+            stat.put_slice(b"hostname\x00A Server\x00\x00");
+            stat.put_slice(b"\x01player_\x00\x00");
+            for i in 0..200 {
+                stat.put_slice(format!("player{:04}\x00", i).as_bytes());
+            }
+            stat.put_u8(0);
+            assert!(stat.len() > 1500);
+            server.send_to(&stat, client_addr).await.unwrap();
+        });
+
+        let client = Client::new().await.unwrap();
+        let full = client.query_full(addr).await.unwrap();
+        assert_eq!(full.motd, "A Server");
+        assert_eq!(full.players.len(), 200);
+        assert_eq!(full.players[199], "player0199");
+    }
+}
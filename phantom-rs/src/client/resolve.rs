@@ -0,0 +1,140 @@
+use std::net::{IpAddr, SocketAddr};
+
+use hickory_resolver::TokioAsyncResolver;
+use log::debug;
+use once_cell::sync::Lazy;
+
+use super::ClientError;
+
+/// Port Bedrock servers listen on when an address doesn't name one
+pub(super) const DEFAULT_BEDROCK_PORT: u16 = 19132;
+
+/// SRV service the vanilla client looks up for port-less Bedrock addresses
+const BEDROCK_SRV_SERVICE: &str = "_minecraft._udp";
+
+/// Resolves a Bedrock server address to every socket address worth trying, in
+/// order. Without a port, a `_minecraft._udp` SRV record takes precedence over
+/// the default port, as it does in the vanilla client.
+pub(super) async fn resolve(addr: &str) -> Result<Vec<SocketAddr>, ClientError> {
+    let (host, port) = split_host_port(addr)?;
+
+    if let Some(port) = port {
+        return lookup(&host, port).await;
+    }
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, DEFAULT_BEDROCK_PORT)]);
+    }
+
+    let mut candidates = Vec::new();
+    for (target, port) in srv_targets(&host).await {
+        match lookup(&target, port).await {
+            Ok(addrs) => candidates.extend(addrs),
+            Err(e) => debug!("Skipping SRV target {}:{}: {}", target, port, e),
+        }
+    }
+    if !candidates.is_empty() {
+        return Ok(candidates);
+    }
+
+    lookup(&host, DEFAULT_BEDROCK_PORT).await
+}
+
+async fn lookup(host: &str, port: u16) -> Result<Vec<SocketAddr>, ClientError> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| ClientError::invalid_address(format!("{}:{}", host, port), e))?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(ClientError::invalid_address(
+            format!("{}:{}", host, port),
+            "no address found",
+        ));
+    }
+    Ok(addrs)
+}
+
+/// SRV targets for `host`, most preferred first. Empty when there is no
+/// record or the lookup fails.
+async fn srv_targets(host: &str) -> Vec<(String, u16)> {
+    static RESOLVER: Lazy<TokioAsyncResolver> = Lazy::new(|| {
+        // Mobile platforms have no resolv.conf to read
+        TokioAsyncResolver::tokio_from_system_conf().unwrap_or_else(|e| {
+            debug!("Using default DNS config: {}", e);
+            TokioAsyncResolver::tokio(Default::default(), Default::default())
+        })
+    });
+
+    let name = format!("{}.{}.", BEDROCK_SRV_SERVICE, host.trim_end_matches('.'));
+    let lookup = match RESOLVER.srv_lookup(name.as_str()).await {
+        Ok(lookup) => lookup,
+        Err(e) => {
+            debug!("No SRV record for {}: {}", name, e);
+            return Vec::new();
+        }
+    };
+
+    let mut records: Vec<_> = lookup.iter().collect();
+    records.sort_by_key(|srv| (srv.priority(), std::cmp::Reverse(srv.weight())));
+    records
+        .into_iter()
+        .map(|srv| (srv.target().to_utf8(), srv.port()))
+        .collect()
+}
+
+/// Splits "host:port", "[v6]:port", or a bare host into its parts
+pub(super) fn split_host_port(addr: &str) -> Result<(String, Option<u16>), ClientError> {
+    if let Ok(addr) = addr.parse::<SocketAddr>() {
+        return Ok((addr.ip().to_string(), Some(addr.port())));
+    }
+
+    let invalid = || ClientError::invalid_address(addr, "expected host, host:port or [ipv6]:port");
+    match addr.rsplit_once(':') {
+        // More than one colon without brackets is a bare IPv6 address
+        Some((host, _)) if host.contains(':') && !host.ends_with(']') => {
+            Ok((addr.to_string(), None))
+        }
+        Some((host, port)) => {
+            let port = port.parse().map_err(|_| invalid())?;
+            Ok((host.trim_matches(['[', ']']).to_string(), Some(port)))
+        }
+        None if addr.is_empty() => Err(invalid()),
+        None => Ok((addr.to_string(), None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_host_port() {
+        let split = |addr| split_host_port(addr).ok();
+        assert_eq!(
+            split("mc.example.com"),
+            Some(("mc.example.com".into(), None))
+        );
+        assert_eq!(
+            split("mc.example.com:25566"),
+            Some(("mc.example.com".into(), Some(25566)))
+        );
+        assert_eq!(
+            split("127.0.0.1:1234"),
+            Some(("127.0.0.1".into(), Some(1234)))
+        );
+        assert_eq!(split("[::1]:1234"), Some(("::1".into(), Some(1234))));
+        assert_eq!(split("::1"), Some(("::1".into(), None)));
+        assert_eq!(split("host:port"), None);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_defaults_port_for_ips() {
+        let addrs = resolve("127.0.0.1").await.unwrap();
+        assert_eq!(addrs, vec!["127.0.0.1:19132".parse().unwrap()]);
+
+        let addrs = resolve("127.0.0.1:1234").await.unwrap();
+        assert_eq!(addrs, vec!["127.0.0.1:1234".parse().unwrap()]);
+
+        assert!(resolve("").await.is_err());
+    }
+}
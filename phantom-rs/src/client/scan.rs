@@ -0,0 +1,278 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use futures::stream::{FuturesUnordered, Stream, StreamExt};
+use log::debug;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use uniffi::Record;
+
+use super::socket::PingSockets;
+use super::{
+    ping_addr, CancelToken, Client, ClientError, DiscoveredServer, DiscoveryListener, PingOptions,
+    PingSource, Pong,
+};
+
+/// Smallest prefix `scan` accepts. A /16 is already 65k pings.
+const MIN_SCAN_PREFIX: u8 = 16;
+
+/// Pacing for `Client::scan`
+#[derive(Debug, Clone, Copy, Record)]
+pub struct ScanOptions {
+    #[uniffi(default = 19132)]
+    pub port: u16,
+    /// Most pings awaiting a pong at once
+    #[uniffi(default = 64)]
+    pub max_in_flight: u32,
+    /// Cap on pings sent per second across the whole scan
+    #[uniffi(default = 200)]
+    pub packets_per_second: u32,
+    /// How long each address gets to answer
+    #[uniffi(default = 1000)]
+    pub timeout_ms: u64,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            port: 19132,
+            max_in_flight: 64,
+            packets_per_second: 200,
+            timeout_ms: 1000,
+        }
+    }
+}
+
+#[uniffi::export]
+impl Client {
+    /// Pings every host in an IPv4 subnet such as "192.168.1.0/24", reporting
+    /// servers to `listener` as they answer. Cancel the returned token to stop.
+    pub fn scan(
+        &self,
+        cidr: String,
+        options: ScanOptions,
+        listener: Box<dyn DiscoveryListener>,
+    ) -> Result<Arc<CancelToken>, ClientError> {
+        let hosts = subnet_hosts(&cidr)?;
+        let cancel = CancelToken::new();
+
+        let source = self.source;
+        let sockets = self.sockets.clone();
+        let token = cancel.token.clone();
+        self.runtime.spawn(async move {
+            let scan = scan_hosts(&sockets, source, hosts, options, |server| {
+                listener.on_server(server)
+            });
+            tokio::select! {
+                _ = token.cancelled() => debug!("Cancelled scan of {}", cidr),
+                _ = scan => listener.on_finished(None),
+            }
+        });
+
+        Ok(cancel)
+    }
+}
+
+impl Client {
+    /// Like `scan`, but yields servers as a stream. Dropping the stream stops
+    /// the scan.
+    pub fn scan_stream(
+        &self,
+        cidr: &str,
+        options: ScanOptions,
+    ) -> Result<impl Stream<Item = DiscoveredServer>, ClientError> {
+        let hosts = subnet_hosts(cidr)?;
+        let source = self.source;
+        let sockets = self.sockets.clone();
+
+        Ok(self.stream(move |tx| async move {
+            scan_hosts(&sockets, source, hosts, options, |server| {
+                let _ = tx.send(server);
+            })
+            .await
+        }))
+    }
+}
+
+async fn scan_hosts(
+    sockets: &PingSockets,
+    source: PingSource,
+    hosts: impl Iterator<Item = Ipv4Addr>,
+    options: ScanOptions,
+    mut on_server: impl FnMut(DiscoveredServer),
+) {
+    let max_in_flight = options.max_in_flight.max(1) as usize;
+    let ping_options = PingOptions {
+        timeout_ms: options.timeout_ms,
+        retries: 0,
+        ..PingOptions::default()
+    };
+    let timeout = Duration::from_millis(options.timeout_ms);
+
+    let mut ticker = interval(Duration::from_secs(1) / options.packets_per_second.max(1));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut in_flight = FuturesUnordered::new();
+    let mut report = |result: Result<Pong, ClientError>| {
+        if let Ok(pong) = result {
+            on_server(DiscoveredServer {
+                addr: pong.responder_addr.clone(),
+                pong,
+            });
+        }
+    };
+
+    for host in hosts {
+        while in_flight.len() >= max_in_flight {
+            if let Some(result) = in_flight.next().await {
+                report(result);
+            }
+        }
+
+        ticker.tick().await;
+        let addr = SocketAddr::from((host, options.port));
+        in_flight.push(ping_addr(sockets, source, addr, ping_options, timeout));
+    }
+
+    while let Some(result) = in_flight.next().await {
+        report(result);
+    }
+}
+
+/// Usable host addresses in an IPv4 CIDR block. Network and broadcast
+/// addresses are skipped except in /31 and /32 blocks, which have none.
+fn subnet_hosts(cidr: &str) -> Result<impl Iterator<Item = Ipv4Addr>, ClientError> {
+    let invalid = |reason: &str| ClientError::invalid_address(cidr, reason);
+
+    let (ip, prefix) = cidr
+        .split_once('/')
+        .ok_or_else(|| invalid("expected an IPv4 subnet like 192.168.1.0/24"))?;
+    let ip: Ipv4Addr = ip.parse().map_err(|_| invalid("invalid IPv4 address"))?;
+    let prefix: u8 = prefix
+        .parse()
+        .map_err(|_| invalid("invalid prefix length"))?;
+    if !(MIN_SCAN_PREFIX..=32).contains(&prefix) {
+        return Err(invalid("prefix length must be between 16 and 32"));
+    }
+
+    let mask = u32::MAX << (32 - prefix);
+    let network = u32::from(ip) & mask;
+    let broadcast = network | !mask;
+
+    let (first, last) = if prefix >= 31 {
+        (network, broadcast)
+    } else {
+        (network + 1, broadcast - 1)
+    };
+    Ok((first..=last).map(Ipv4Addr::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::unconnected_ping::UnconnectedPing;
+    use crate::proto::unconnected_pong::UnconnectedPong;
+    use bytes::Bytes;
+    use std::sync::Mutex;
+    use tokio::net::UdpSocket;
+
+    #[test]
+    fn test_subnet_hosts() {
+        let hosts: Vec<_> = subnet_hosts("192.168.1.77/30").unwrap().collect();
+        assert_eq!(
+            hosts,
+            vec![
+                Ipv4Addr::new(192, 168, 1, 77),
+                Ipv4Addr::new(192, 168, 1, 78)
+            ]
+        );
+        assert_eq!(subnet_hosts("10.0.0.0/24").unwrap().count(), 254);
+        assert_eq!(subnet_hosts("10.0.0.1/32").unwrap().count(), 1);
+
+        assert!(subnet_hosts("10.0.0.0/8").is_err());
+        assert!(subnet_hosts("10.0.0.0").is_err());
+        assert!(subnet_hosts("::1/128").is_err());
+    }
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl DiscoveryListener for Recorder {
+        fn on_server(&self, server: DiscoveredServer) {
+            self.0.lock().unwrap().push(server.addr);
+        }
+
+        fn on_finished(&self, _error: Option<String>) {
+            self.0.lock().unwrap().push("finished".to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan_reports_servers() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            let (len, client_addr) = server.recv_from(&mut buf).await.unwrap();
+            let ping = UnconnectedPing::from_bytes(Bytes::copy_from_slice(&buf[..len])).unwrap();
+
+            let mut pong = UnconnectedPong::new();
+            pong.ping_time = ping.ping_time;
+            server
+                .send_to(&pong.build().unwrap(), client_addr)
+                .await
+                .unwrap();
+        });
+
+        let client = Client::new().await.expect("Failed to create client");
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let options = ScanOptions {
+            port,
+            ..ScanOptions::default()
+        };
+        let _cancel = client
+            .scan(
+                "127.0.0.1/32".to_string(),
+                options,
+                Box::new(Recorder(events.clone())),
+            )
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![format!("127.0.0.1:{}", port), "finished".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scan_stream() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            let (len, client_addr) = server.recv_from(&mut buf).await.unwrap();
+            let ping = UnconnectedPing::from_bytes(Bytes::copy_from_slice(&buf[..len])).unwrap();
+
+            let mut pong = UnconnectedPong::new();
+            pong.ping_time = ping.ping_time;
+            server
+                .send_to(&pong.build().unwrap(), client_addr)
+                .await
+                .unwrap();
+        });
+
+        let client = Client::new().await.expect("Failed to create client");
+        let options = ScanOptions {
+            port,
+            timeout_ms: 200,
+            ..ScanOptions::default()
+        };
+        let servers: Vec<_> = client
+            .scan_stream("127.0.0.1/31", options)
+            .unwrap()
+            .collect()
+            .await;
+
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].addr, format!("127.0.0.1:{}", port));
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::net::{Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use log::debug;
+use parking_lot::Mutex;
+use tokio::net::UdpSocket;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Duration};
+use tokio_util::sync::CancellationToken;
+
+use super::socks::{self, Socks5Relay};
+use super::{ClientError, PingSource};
+use crate::proto::mtu::RECV_BUFFER_SIZE;
+use crate::proto::packet::{classify, OfflineMessageId, PacketKind};
+use crate::proto::unconnected_ping::UnconnectedPing;
+use crate::proto::unconnected_pong::UnconnectedPongRef;
+
+/// Pongs are routed back to the ping that asked for them by the pinged address
+/// and the ping time the server echoes
+type PendingKey = (SocketAddr, [u8; 8]);
+type PongResult = Result<(Bytes, SocketAddr), ClientError>;
+
+/// Reads that fail in a row, other than ICMP errors, before the socket is
+/// given up on
+const MAX_READ_ERRORS: u32 = 8;
+
+/// Pause after a failed read, times the number of failures in a row
+const READ_ERROR_BACKOFF: Duration = Duration::from_millis(10);
+
+/// The pings waiting on a socket's pongs
+#[derive(Default)]
+struct Pending {
+    waiters: HashMap<PendingKey, mpsc::UnboundedSender<PongResult>>,
+    /// Why the read loop stopped, if it has. Later pings fail with it.
+    failed: Option<ClientError>,
+}
+
+/// The long-lived sockets a `Client` pings from, one per address family
+pub(super) struct PingSockets {
+    v4: Option<Arc<PingSocket>>,
+    v6: Option<Arc<PingSocket>>,
+}
+
+impl PingSockets {
+    /// Binds on `bind_addr`. An unspecified IPv4 address also gets an IPv6
+    /// socket when the host supports it; a specific address only covers its
+    /// own family.
+    pub(super) fn bind(bind_addr: SocketAddr, runtime: &Handle) -> Result<Self, ClientError> {
+        let primary = Arc::new(PingSocket::bind(bind_addr, None, runtime)?);
+
+        if bind_addr.is_ipv6() {
+            return Ok(Self {
+                v4: None,
+                v6: Some(primary),
+            });
+        }
+
+        let v6 = if bind_addr.ip().is_unspecified() {
+            let v6_addr = SocketAddr::from((Ipv6Addr::UNSPECIFIED, bind_addr.port()));
+            PingSocket::bind(v6_addr, None, runtime)
+                .inspect_err(|e| debug!("No IPv6 ping socket: {}", e))
+                .ok()
+                .map(Arc::new)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            v4: Some(primary),
+            v6,
+        })
+    }
+
+    /// Binds one socket that sends everything through a SOCKS5 relay, which
+    /// reaches both address families
+    pub(super) fn relayed(
+        bind_addr: SocketAddr,
+        relay: Socks5Relay,
+        runtime: &Handle,
+    ) -> Result<Self, ClientError> {
+        // The socket talks to the relay, so it has to match the relay's family
+        let bind_addr = match (bind_addr, relay.relay_addr) {
+            (SocketAddr::V4(bind), SocketAddr::V6(_)) if bind.ip().is_unspecified() => {
+                SocketAddr::from((Ipv6Addr::UNSPECIFIED, bind.port()))
+            }
+            _ => bind_addr,
+        };
+
+        let socket = Arc::new(PingSocket::bind(bind_addr, Some(relay), runtime)?);
+        Ok(Self {
+            v4: Some(socket.clone()),
+            v6: Some(socket),
+        })
+    }
+
+    /// The socket that can reach `addr`
+    pub(super) fn for_addr(&self, addr: &SocketAddr) -> Result<&PingSocket, ClientError> {
+        let socket = match addr {
+            SocketAddr::V4(_) => self.v4.as_deref(),
+            SocketAddr::V6(_) => self.v6.as_deref(),
+        };
+        socket.ok_or_else(|| ClientError::invalid_address(addr, "no socket bound for its family"))
+    }
+}
+
+/// A UDP socket shared by every ping of one family. A background task reads
+/// pongs off it and hands each one to the exchange waiting for it.
+pub(super) struct PingSocket {
+    socket: Arc<UdpSocket>,
+    pending: Arc<Mutex<Pending>>,
+    /// Last ping time handed out, so concurrent pings never share one
+    last_stamp: AtomicU64,
+    /// SOCKS5 association that pings are tunnelled through, if any
+    relay: Option<Socks5Relay>,
+    token: CancellationToken,
+}
+
+impl PingSocket {
+    fn bind(
+        bind_addr: SocketAddr,
+        relay: Option<Socks5Relay>,
+        runtime: &Handle,
+    ) -> Result<Self, ClientError> {
+        // Bound through std so this works outside the runtime, e.g. from a
+        // foreign executor calling the constructor
+        let socket = std::net::UdpSocket::bind(bind_addr)?;
+        socket.set_nonblocking(true)?;
+        let socket = {
+            let _guard = runtime.enter();
+            Arc::new(UdpSocket::from_std(socket)?)
+        };
+
+        let pending = Arc::new(Mutex::new(Pending::default()));
+        let token = CancellationToken::new();
+        let relay_addr = relay.as_ref().map(|relay| relay.relay_addr);
+        runtime.spawn(recv_loop(
+            socket.clone(),
+            pending.clone(),
+            relay_addr,
+            token.clone(),
+        ));
+
+        Ok(Self {
+            socket,
+            pending,
+            last_stamp: AtomicU64::new(0),
+            relay,
+            token,
+        })
+    }
+
+    #[cfg(test)]
+    pub(super) fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Starts waiting for pongs from `addr`
+    pub(super) fn exchange(&self, addr: SocketAddr) -> PingExchange<'_> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        PingExchange {
+            socket: self,
+            addr,
+            stamps: Vec::new(),
+            tx,
+            rx,
+        }
+    }
+
+    /// Milliseconds since the client started, bumped past the last stamp
+    /// handed out if needed. Keeps pongs to concurrent pings apart at the cost
+    /// of a millisecond or two of latency.
+    fn next_stamp(&self, source: &PingSource) -> [u8; 8] {
+        let now = source.start_time.elapsed().as_millis() as u64;
+        let previous = self
+            .last_stamp
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+                Some(now.max(last + 1))
+            })
+            .unwrap_or_default();
+        now.max(previous + 1).to_be_bytes()
+    }
+}
+
+impl Drop for PingSocket {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
+/// One ping's view of a shared socket. Every attempt sent through it is
+/// registered, so a late pong to an earlier attempt still counts; all of them
+/// are unregistered on drop.
+pub(super) struct PingExchange<'a> {
+    socket: &'a PingSocket,
+    addr: SocketAddr,
+    stamps: Vec<[u8; 8]>,
+    tx: mpsc::UnboundedSender<PongResult>,
+    rx: mpsc::UnboundedReceiver<PongResult>,
+}
+
+impl PingExchange<'_> {
+    /// Sends a freshly stamped ping
+    pub(super) async fn send(&mut self, source: &PingSource) -> Result<(), ClientError> {
+        let stamp = self.socket.next_stamp(source);
+        {
+            let mut pending = self.socket.pending.lock();
+            if let Some(error) = &pending.failed {
+                return Err(error.clone());
+            }
+            pending.waiters.insert((self.addr, stamp), self.tx.clone());
+        }
+        self.stamps.push(stamp);
+
+        let ping = UnconnectedPing::new(source.client_id, stamp).build();
+        match &self.socket.relay {
+            Some(relay) => {
+                let wrapped = socks::wrap(&self.addr, &ping);
+                self.socket
+                    .socket
+                    .send_to(&wrapped, relay.relay_addr)
+                    .await?
+            }
+            None => self.socket.socket.send_to(&ping, self.addr).await?,
+        };
+        Ok(())
+    }
+
+    /// Waits for a pong to any attempt, with the address it came from, or for
+    /// an error the socket could pin on this exchange
+    pub(super) async fn recv(&mut self) -> PongResult {
+        // `self.tx` keeps the channel open, so this only ends with a pong
+        self.rx.recv().await.expect("sender held by exchange")
+    }
+}
+
+impl Drop for PingExchange<'_> {
+    fn drop(&mut self) {
+        let mut pending = self.socket.pending.lock();
+        for stamp in &self.stamps {
+            pending.waiters.remove(&(self.addr, *stamp));
+        }
+    }
+}
+
+async fn recv_loop(
+    socket: Arc<UdpSocket>,
+    pending: Arc<Mutex<Pending>>,
+    relay_addr: Option<SocketAddr>,
+    token: CancellationToken,
+) {
+    let mut buf = BytesMut::with_capacity(RECV_BUFFER_SIZE);
+    let mut errors = 0;
+
+    loop {
+        buf.reserve(RECV_BUFFER_SIZE);
+        let result = tokio::select! {
+            _ = token.cancelled() => break,
+            result = socket.recv_buf_from(&mut buf) => result,
+        };
+        let from = match result {
+            Ok((_, from)) => {
+                errors = 0;
+                from
+            }
+            Err(e) => {
+                debug!("Ping socket read failed: {}", e);
+                buf.clear();
+                let error = ClientError::from(e);
+                if matches!(
+                    error,
+                    ClientError::Refused { .. } | ClientError::Unreachable { .. }
+                ) {
+                    report_icmp_error(&pending, error);
+                    continue;
+                }
+
+                // Anything else may well fail again straight away
+                errors += 1;
+                if errors >= MAX_READ_ERRORS {
+                    debug!("Giving up on ping socket after {} failed reads", errors);
+                    fail_pending(&pending, error);
+                    break;
+                }
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = sleep(READ_ERROR_BACKOFF * errors) => continue,
+                }
+            }
+        };
+        let mut data = buf.split().freeze();
+
+        // Relayed datagrams carry the server's address in a SOCKS5 header
+        let from = match relay_addr {
+            Some(relay_addr) if from != relay_addr => {
+                debug!("Ignoring packet from {} outside the SOCKS5 relay", from);
+                continue;
+            }
+            Some(_) => match socks::unwrap(data) {
+                Ok((src, payload)) => {
+                    data = payload;
+                    src
+                }
+                Err(e) => {
+                    debug!("Ignoring invalid relayed packet: {}", e);
+                    continue;
+                }
+            },
+            None => from,
+        };
+
+        if classify(&data) != PacketKind::Offline(OfflineMessageId::UnconnectedPong) {
+            debug!("Ignoring non-pong packet from {}", from);
+            continue;
+        }
+        let Ok(pong) = UnconnectedPongRef::from_bytes(data.clone()) else {
+            debug!("Ignoring malformed pong from {}", from);
+            continue;
+        };
+
+        let waiter = pending.lock().waiters.get(&(from, pong.ping_time)).cloned();
+        match waiter {
+            Some(waiter) => {
+                let _ = waiter.send(Ok((data, from)));
+            }
+            None => debug!("Ignoring unexpected pong from {}", from),
+        }
+    }
+}
+
+/// Some platforms (Windows in particular) report an ICMP error for an earlier
+/// send as a failed read, without saying which peer it came from. It can only
+/// be attributed when every pending ping went to the same address; otherwise
+/// the affected ping just times out.
+fn report_icmp_error(pending: &Mutex<Pending>, error: ClientError) {
+    let pending = pending.lock();
+    let mut addrs = pending.waiters.keys().map(|(addr, _)| addr);
+    let Some(first) = addrs.next() else {
+        return;
+    };
+    if addrs.all(|addr| addr == first) {
+        for waiter in pending.waiters.values() {
+            let _ = waiter.send(Err(error.clone()));
+        }
+    }
+}
+
+/// Fails every waiting ping with `error`, and every later one too
+fn fail_pending(pending: &Mutex<Pending>, error: ClientError) {
+    let mut pending = pending.lock();
+    for waiter in pending.waiters.values() {
+        let _ = waiter.send(Err(error.clone()));
+    }
+    pending.failed = Some(error);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::IoErrorKind;
+    use crate::client::Client;
+
+    #[tokio::test]
+    async fn test_failed_socket_fails_pings() {
+        let client = Client::new().await.expect("Failed to create client");
+        let socket = PingSocket::bind(
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            None,
+            &Handle::current(),
+        )
+        .unwrap();
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = silent.local_addr().unwrap();
+
+        let mut waiting = socket.exchange(addr);
+        waiting.send(&client.source).await.unwrap();
+
+        let error = ClientError::IoError {
+            kind: IoErrorKind::Other,
+            message: "broken".into(),
+        };
+        fail_pending(&socket.pending, error);
+
+        assert!(matches!(
+            waiting.recv().await,
+            Err(ClientError::IoError { .. })
+        ));
+        assert!(matches!(
+            socket.exchange(addr).send(&client.source).await,
+            Err(ClientError::IoError { .. })
+        ));
+    }
+}
@@ -0,0 +1,244 @@
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use log::debug;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use super::ClientError;
+
+// SOCKS5 constants (RFC 1928)
+const SOCKS_VERSION: u8 = 0x05;
+const NO_AUTH: u8 = 0x00;
+const NO_ACCEPTABLE_METHOD: u8 = 0xff;
+const CMD_UDP_ASSOCIATE: u8 = 0x03;
+const REPLY_SUCCEEDED: u8 = 0x00;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// A UDP association with a SOCKS5 proxy. Datagrams sent to `relay_addr`
+/// with a `wrap` header are forwarded by the proxy; the association lasts as
+/// long as the control connection stays open.
+pub(super) struct Socks5Relay {
+    pub(super) relay_addr: SocketAddr,
+    _control: TcpStream,
+}
+
+/// Opens a UDP association through the SOCKS5 proxy at `proxy`. Only proxies
+/// that allow unauthenticated access are supported.
+pub(super) async fn associate(proxy: &str) -> Result<Socks5Relay, ClientError> {
+    let proxy_addr = tokio::net::lookup_host(proxy)
+        .await
+        .map_err(|e| ClientError::invalid_address(proxy, e))?
+        .next()
+        .ok_or_else(|| ClientError::invalid_address(proxy, "no address found"))?;
+
+    let mut control = TcpStream::connect(proxy_addr).await?;
+
+    control.write_all(&[SOCKS_VERSION, 1, NO_AUTH]).await?;
+    let mut choice = [0u8; 2];
+    control.read_exact(&mut choice).await?;
+    match choice {
+        [SOCKS_VERSION, NO_AUTH] => {}
+        [SOCKS_VERSION, NO_ACCEPTABLE_METHOD] => {
+            return Err(ClientError::refused("SOCKS5 proxy requires authentication"))
+        }
+        _ => return Err(invalid("unexpected method selection")),
+    }
+
+    // The client's UDP address isn't known yet, which all zeroes says
+    let mut request = BytesMut::new();
+    request.put_slice(&[SOCKS_VERSION, CMD_UDP_ASSOCIATE, 0x00]);
+    write_addr(&mut request, &SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)));
+    control.write_all(&request).await?;
+
+    let mut header = [0u8; 4];
+    control.read_exact(&mut header).await?;
+    let [version, reply, _, atyp] = header;
+    if version != SOCKS_VERSION {
+        return Err(invalid("unexpected version in reply"));
+    }
+    if reply != REPLY_SUCCEEDED {
+        return Err(ClientError::refused(format!(
+            "SOCKS5 proxy rejected UDP associate (reply code {})",
+            reply
+        )));
+    }
+
+    let addr_len = match atyp {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        _ => return Err(invalid("unsupported relay address type")),
+    };
+    let mut rest = vec![0u8; addr_len + 2];
+    control.read_exact(&mut rest).await?;
+    let mut reply_addr = BytesMut::new();
+    reply_addr.put_u8(atyp);
+    reply_addr.put_slice(&rest);
+    let mut relay_addr = read_addr(&mut reply_addr.freeze())?;
+
+    // An unspecified relay address means "same host as the proxy"
+    if relay_addr.ip().is_unspecified() {
+        relay_addr.set_ip(proxy_addr.ip());
+    }
+
+    debug!("SOCKS5 proxy {} relays UDP via {}", proxy_addr, relay_addr);
+    Ok(Socks5Relay {
+        relay_addr,
+        _control: control,
+    })
+}
+
+/// Prefixes `data` with the SOCKS5 UDP request header for `dst`
+pub(super) fn wrap(dst: &SocketAddr, data: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(22 + data.len());
+    // Reserved, then fragment number; fragmentation isn't used
+    buf.put_u16(0);
+    buf.put_u8(0);
+    write_addr(&mut buf, dst);
+    buf.put_slice(data);
+    buf.freeze()
+}
+
+/// Strips the SOCKS5 UDP header off a relayed datagram, returning the address
+/// it originally came from and its payload
+pub(super) fn unwrap(mut data: Bytes) -> Result<(SocketAddr, Bytes), ClientError> {
+    if data.remaining() < 3 {
+        return Err(ClientError::truncated("SOCKS5 UDP header"));
+    }
+    data.advance(2);
+    if data.get_u8() != 0 {
+        return Err(invalid("fragmented datagrams are not supported"));
+    }
+
+    let src = read_addr(&mut data)?;
+    Ok((src, data))
+}
+
+fn write_addr(buf: &mut BytesMut, addr: &SocketAddr) {
+    match addr {
+        SocketAddr::V4(addr) => {
+            buf.put_u8(ATYP_IPV4);
+            buf.put_slice(&addr.ip().octets());
+        }
+        SocketAddr::V6(addr) => {
+            buf.put_u8(ATYP_IPV6);
+            buf.put_slice(&addr.ip().octets());
+        }
+    }
+    buf.put_u16(addr.port());
+}
+
+fn read_addr(data: &mut Bytes) -> Result<SocketAddr, ClientError> {
+    let truncated = || ClientError::truncated("SOCKS5 address");
+
+    if !data.has_remaining() {
+        return Err(truncated());
+    }
+    let ip = match data.get_u8() {
+        ATYP_IPV4 if data.remaining() >= 6 => {
+            let mut octets = [0u8; 4];
+            data.copy_to_slice(&mut octets);
+            Ipv4Addr::from(octets).into()
+        }
+        ATYP_IPV6 if data.remaining() >= 18 => {
+            let mut octets = [0u8; 16];
+            data.copy_to_slice(&mut octets);
+            Ipv6Addr::from(octets).into()
+        }
+        ATYP_IPV4 | ATYP_IPV6 => return Err(truncated()),
+        ATYP_DOMAIN => return Err(invalid("domain name addresses are not supported")),
+        _ => return Err(invalid("unknown address type")),
+    };
+
+    Ok(SocketAddr::new(ip, data.get_u16()))
+}
+
+fn invalid(reason: &str) -> ClientError {
+    ClientError::invalid_response(format!("SOCKS5 proxy: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_round_trip() {
+        let dst: SocketAddr = "192.168.1.10:19132".parse().unwrap();
+        let wrapped = wrap(&dst, b"ping");
+        assert_eq!(
+            wrapped.as_ref(),
+            &[0, 0, 0, ATYP_IPV4, 192, 168, 1, 10, 0x4a, 0xbc, b'p', b'i', b'n', b'g']
+        );
+        assert_eq!(
+            unwrap(wrapped).ok(),
+            Some((dst, Bytes::from_static(b"ping")))
+        );
+
+        let dst: SocketAddr = "[2001:db8::1]:19133".parse().unwrap();
+        assert_eq!(unwrap(wrap(&dst, b"")).ok(), Some((dst, Bytes::new())));
+
+        assert!(unwrap(Bytes::from_static(&[0, 0, 1, ATYP_IPV4])).is_err());
+        assert!(unwrap(Bytes::from_static(&[0, 0, 0, ATYP_IPV4, 1])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ping_through_socks5() {
+        use crate::client::{Client, ClientConfig};
+        use crate::proto::unconnected_ping::UnconnectedPing;
+        use crate::proto::unconnected_pong::UnconnectedPong;
+        use tokio::net::{TcpListener, UdpSocket};
+
+        let control = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = control.local_addr().unwrap().to_string();
+        let relay = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let relay_port = relay.local_addr().unwrap().port();
+
+        // A proxy that answers every relayed ping itself, as if the server
+        // behind it had
+        tokio::spawn(async move {
+            let (mut stream, _) = control.accept().await.unwrap();
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            stream.write_all(&[SOCKS_VERSION, NO_AUTH]).await.unwrap();
+
+            let mut request = [0u8; 10];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(request[1], CMD_UDP_ASSOCIATE);
+            let port = relay_port.to_be_bytes();
+            let reply = [SOCKS_VERSION, 0, 0, ATYP_IPV4, 0, 0, 0, 0, port[0], port[1]];
+            stream.write_all(&reply).await.unwrap();
+
+            let mut buf = [0u8; 128];
+            let (len, client_addr) = relay.recv_from(&mut buf).await.unwrap();
+            let (dst, ping) = unwrap(Bytes::copy_from_slice(&buf[..len])).unwrap();
+            let ping = UnconnectedPing::from_bytes(ping).unwrap();
+
+            let mut pong = UnconnectedPong::new();
+            pong.ping_time = ping.ping_time;
+            relay
+                .send_to(&wrap(&dst, &pong.build().unwrap()), client_addr)
+                .await
+                .unwrap();
+
+            // Hold the association open until the client is done
+            let _ = stream.read(&mut buf).await;
+        });
+
+        let config = ClientConfig {
+            socks5_proxy: Some(proxy_addr),
+            ..ClientConfig::default()
+        };
+        let client = Client::with_config(config)
+            .await
+            .expect("Failed to create client");
+
+        // Only reachable through the proxy
+        let pong = client
+            .ping("10.255.255.1:19132".to_string())
+            .await
+            .expect("Failed to ping");
+        assert_eq!(pong.responder_addr, "10.255.255.1:19132");
+    }
+}
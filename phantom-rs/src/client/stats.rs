@@ -0,0 +1,141 @@
+use futures::stream::{self, StreamExt};
+use tokio::time::{sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use uniffi::Record;
+
+use super::{ping_candidates, resolve, Client, ClientError, PingOptions, MAX_CONCURRENT_PINGS};
+
+/// Shortest time a probe waits for its pong before counting as lost
+const PROBE_TIMEOUT_MS: u64 = 1000;
+
+#[uniffi::export]
+impl Client {
+    /// Sends `count` single-attempt pings to `addr`, one every `interval_ms`,
+    /// and summarizes their round-trip times. Probes don't wait on each other,
+    /// so a lost pong doesn't hold up the ones after it.
+    pub async fn ping_stats(
+        &self,
+        addr: String,
+        count: u32,
+        interval_ms: u64,
+    ) -> Result<PingStats, ClientError> {
+        let source = self.source;
+        let sockets = self.sockets.clone();
+
+        // Retrying would hide exactly the loss being measured
+        let options = PingOptions {
+            timeout_ms: interval_ms.max(PROBE_TIMEOUT_MS),
+            retries: 0,
+            ..PingOptions::default()
+        };
+
+        let task = async move {
+            // Resolved once up front so every probe goes to the same place
+            let candidates = resolve::resolve(&addr).await?;
+            let sockets = &sockets;
+            let candidates = &candidates;
+
+            // Scheduled from one start so a probe waiting on a free slot
+            // doesn't push back the ones after it
+            let start = Instant::now();
+            let results: Vec<_> = stream::iter(0..count)
+                .map(|i| async move {
+                    sleep_until(start + Duration::from_millis(interval_ms) * i).await;
+                    ping_candidates(sockets, source, candidates, options).await
+                })
+                .buffer_unordered(MAX_CONCURRENT_PINGS)
+                .collect()
+                .await;
+
+            let samples: Vec<u64> = results
+                .into_iter()
+                .filter_map(|result| result.ok())
+                .map(|pong| pong.latency_ms)
+                .collect();
+            Ok(PingStats::from_samples(count, &samples))
+        };
+
+        self.run(CancellationToken::new(), task).await
+    }
+}
+
+/// Round-trip statistics over a series of pings. The RTT fields are `None`
+/// when no pong came back.
+#[derive(Debug, Clone, PartialEq, Record)]
+pub struct PingStats {
+    pub sent: u32,
+    pub received: u32,
+    /// Share of pings that went unanswered, from 0 to 100
+    pub loss_percent: f64,
+    pub min_ms: Option<u64>,
+    pub avg_ms: Option<f64>,
+    pub max_ms: Option<u64>,
+    /// Population standard deviation, a rough measure of jitter
+    pub stddev_ms: Option<f64>,
+}
+
+impl PingStats {
+    /// Summarizes the latencies of the pings that were answered out of `sent`
+    pub fn from_samples(sent: u32, samples: &[u64]) -> Self {
+        let received = samples.len() as u32;
+        let loss_percent = if sent == 0 {
+            0.0
+        } else {
+            100.0 * sent.saturating_sub(received) as f64 / sent as f64
+        };
+
+        let avg_ms = (!samples.is_empty())
+            .then(|| samples.iter().sum::<u64>() as f64 / samples.len() as f64);
+        let stddev_ms = avg_ms.map(|avg| {
+            let variance = samples
+                .iter()
+                .map(|&sample| (sample as f64 - avg).powi(2))
+                .sum::<f64>()
+                / samples.len() as f64;
+            variance.sqrt()
+        });
+
+        Self {
+            sent,
+            received,
+            loss_percent,
+            min_ms: samples.iter().copied().min(),
+            avg_ms,
+            max_ms: samples.iter().copied().max(),
+            stddev_ms,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_samples() {
+        let stats = PingStats::from_samples(5, &[10, 20, 30, 40]);
+        assert_eq!(stats.received, 4);
+        assert_eq!(stats.loss_percent, 20.0);
+        assert_eq!(stats.min_ms, Some(10));
+        assert_eq!(stats.avg_ms, Some(25.0));
+        assert_eq!(stats.max_ms, Some(40));
+        assert!((stats.stddev_ms.unwrap() - 125f64.sqrt()).abs() < 1e-9);
+
+        let stats = PingStats::from_samples(3, &[]);
+        assert_eq!(stats.loss_percent, 100.0);
+        assert_eq!(stats.avg_ms, None);
+        assert_eq!(stats.stddev_ms, None);
+    }
+
+    #[tokio::test]
+    async fn test_ping_stats_counts_loss() {
+        let client = Client::new().await.expect("Failed to create client");
+        let silent = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = silent.local_addr().unwrap().to_string();
+
+        let stats = client.ping_stats(addr, 3, 10).await.unwrap();
+        assert_eq!(stats.sent, 3);
+        assert_eq!(stats.received, 0);
+        assert_eq!(stats.loss_percent, 100.0);
+    }
+}
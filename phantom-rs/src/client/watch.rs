@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use log::debug;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+
+use super::socket::PingSockets;
+use super::{send_ping, Client, PingOptions, PingSource, Pong};
+
+#[uniffi::export(callback_interface)]
+pub trait WatchListener: Send + Sync {
+    fn on_event(&self, event: WatchEvent);
+}
+
+/// A `WatchListener` whose handler can await. The next ping waits until the
+/// handler returns.
+#[uniffi::export(callback_interface)]
+#[async_trait::async_trait]
+pub trait AsyncWatchListener: Send + Sync {
+    async fn on_event(&self, event: WatchEvent);
+}
+
+/// Either kind of watch listener
+enum Listener {
+    Sync(Box<dyn WatchListener>),
+    Async(Box<dyn AsyncWatchListener>),
+}
+
+impl Listener {
+    async fn notify(&self, event: WatchEvent) {
+        match self {
+            Listener::Sync(listener) => listener.on_event(event),
+            Listener::Async(listener) => listener.on_event(event).await,
+        }
+    }
+}
+
+/// Status change reported by `Client::watch`
+#[derive(uniffi::Enum)]
+pub enum WatchEvent {
+    /// The server answered after being down, or for the first time
+    Up { pong: Pong },
+    /// The server's pong differs from the previous one (MOTD, players, ...)
+    Changed { pong: Pong },
+    /// The server stopped answering
+    Down { reason: String },
+}
+
+/// Stops a watch when cancelled or dropped
+#[derive(uniffi::Object)]
+pub struct WatchHandle {
+    token: CancellationToken,
+}
+
+#[uniffi::export]
+impl WatchHandle {
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
+#[uniffi::export]
+impl Client {
+    /// Pings `addr` every `interval_ms` and notifies `listener` when the server
+    /// comes up, goes down, or its pong changes. Latency alone doesn't count as
+    /// a change. Watching stops when the returned handle is cancelled or dropped.
+    pub fn watch(
+        &self,
+        addr: String,
+        interval_ms: u64,
+        listener: Box<dyn WatchListener>,
+    ) -> Arc<WatchHandle> {
+        self.spawn_watch(addr, interval_ms, Listener::Sync(listener))
+    }
+
+    /// `watch` with a listener that awaits in its handler
+    pub fn watch_async(
+        &self,
+        addr: String,
+        interval_ms: u64,
+        listener: Box<dyn AsyncWatchListener>,
+    ) -> Arc<WatchHandle> {
+        self.spawn_watch(addr, interval_ms, Listener::Async(listener))
+    }
+}
+
+impl Client {
+    fn spawn_watch(&self, addr: String, interval_ms: u64, listener: Listener) -> Arc<WatchHandle> {
+        let token = CancellationToken::new();
+        let source = self.source;
+        let sockets = self.sockets.clone();
+
+        let watch_token = token.clone();
+        self.runtime.spawn(async move {
+            let watch = watch_loop(&sockets, source, addr.clone(), interval_ms, listener);
+            tokio::select! {
+                _ = watch_token.cancelled() => {
+                    debug!("Stopped watching {}", addr);
+                }
+                _ = watch => {}
+            }
+        });
+
+        Arc::new(WatchHandle { token })
+    }
+}
+
+async fn watch_loop(
+    sockets: &PingSockets,
+    source: PingSource,
+    addr: String,
+    interval_ms: u64,
+    listener: Listener,
+) {
+    let mut ticker = interval(Duration::from_millis(interval_ms.max(1)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    // A ping shouldn't outlive the interval, or checks would pile up
+    let options = PingOptions {
+        timeout_ms: PingOptions::default().timeout_ms.min(interval_ms.max(1)),
+        ..PingOptions::default()
+    };
+
+    // None until the first ping completes, so the first result is always reported
+    let mut last: Option<Result<Pong, String>> = None;
+
+    loop {
+        ticker.tick().await;
+
+        let result = send_ping(sockets, source, addr.clone(), options)
+            .await
+            .map_err(|e| e.to_string());
+
+        let event = match (&last, &result) {
+            (Some(Ok(previous)), Ok(pong)) if previous.same_status(pong) => None,
+            (Some(Ok(_)), Ok(pong)) => Some(WatchEvent::Changed { pong: pong.clone() }),
+            (_, Ok(pong)) => Some(WatchEvent::Up { pong: pong.clone() }),
+            (Some(Err(_)), Err(_)) => None,
+            (_, Err(reason)) => Some(WatchEvent::Down {
+                reason: reason.clone(),
+            }),
+        };
+
+        if let Some(event) = event {
+            listener.notify(event).await;
+        }
+        last = Some(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl WatchListener for Recorder {
+        fn on_event(&self, event: WatchEvent) {
+            let name = match event {
+                WatchEvent::Up { .. } => "up",
+                WatchEvent::Changed { .. } => "changed",
+                WatchEvent::Down { .. } => "down",
+            };
+            self.0.lock().unwrap().push(name.to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watch_reports_down_once_and_stops_on_drop() {
+        let client = Client::new().await.expect("Failed to create client");
+        let silent = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = silent.local_addr().unwrap().to_string();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let handle = client.watch(addr, 50, Box::new(Recorder(events.clone())));
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(*events.lock().unwrap(), vec!["down"]);
+
+        let token = handle.token.clone();
+        drop(handle);
+        assert!(token.is_cancelled());
+    }
+
+    struct AsyncRecorder(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait::async_trait]
+    impl AsyncWatchListener for AsyncRecorder {
+        async fn on_event(&self, event: WatchEvent) {
+            tokio::task::yield_now().await;
+            if let WatchEvent::Down { .. } = event {
+                self.0.lock().unwrap().push("down".to_string());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watch_async() {
+        let client = Client::new().await.expect("Failed to create client");
+        let silent = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = silent.local_addr().unwrap().to_string();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let _handle = client.watch_async(addr, 50, Box::new(AsyncRecorder(events.clone())));
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(*events.lock().unwrap(), vec!["down"]);
+    }
+}
@@ -0,0 +1,147 @@
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+use bytes::{Buf, BufMut};
+
+use super::ProtoError;
+
+// Address version bytes
+pub const ADDRESS_VERSION_V4: u8 = 4;
+pub const ADDRESS_VERSION_V6: u8 = 6;
+
+/// sockaddr_in6 family as written by the Windows servers Bedrock is built
+/// around. Readers ignore it.
+const AF_INET6: u16 = 23;
+
+/// Encoded size of an IPv4 address: version, 4 address bytes, port
+pub const ADDRESS_V4_LEN: usize = 7;
+
+/// Encoded size of an IPv6 address: version, then a raw sockaddr_in6 (family,
+/// port, flow info, 16 address bytes, scope ID)
+pub const ADDRESS_V6_LEN: usize = 29;
+
+/// Number of bytes `write_address` produces for `addr`
+pub fn address_len(addr: &SocketAddr) -> usize {
+    match addr {
+        SocketAddr::V4(_) => ADDRESS_V4_LEN,
+        SocketAddr::V6(_) => ADDRESS_V6_LEN,
+    }
+}
+
+/// Writes a RakNet system address. IPv4 octets are bitwise inverted on the wire.
+pub fn write_address(buf: &mut impl BufMut, addr: &SocketAddr) {
+    match addr {
+        SocketAddr::V4(addr) => {
+            buf.put_u8(ADDRESS_VERSION_V4);
+            for octet in addr.ip().octets() {
+                buf.put_u8(!octet);
+            }
+            buf.put_u16(addr.port());
+        }
+        SocketAddr::V6(addr) => {
+            buf.put_u8(ADDRESS_VERSION_V6);
+            buf.put_u16_le(AF_INET6);
+            buf.put_u16(addr.port());
+            buf.put_u32(addr.flowinfo());
+            buf.put_slice(&addr.ip().octets());
+            buf.put_u32(addr.scope_id());
+        }
+    }
+}
+
+/// Reads a RakNet system address written by `write_address`
+pub fn read_address(data: &mut impl Buf, packet_id: u8) -> Result<SocketAddr, ProtoError> {
+    ensure_remaining(data, packet_id, 1)?;
+    match data.get_u8() {
+        ADDRESS_VERSION_V4 => {
+            ensure_remaining(data, packet_id, ADDRESS_V4_LEN - 1)?;
+            let mut octets = [0u8; 4];
+            data.copy_to_slice(&mut octets);
+            let ip = Ipv4Addr::from(octets.map(|octet| !octet));
+            let port = data.get_u16();
+            Ok(SocketAddr::V4(SocketAddrV4::new(ip, port)))
+        }
+        ADDRESS_VERSION_V6 => {
+            ensure_remaining(data, packet_id, ADDRESS_V6_LEN - 1)?;
+            let _family = data.get_u16_le();
+            let port = data.get_u16();
+            let flowinfo = data.get_u32();
+            let mut octets = [0u8; 16];
+            data.copy_to_slice(&mut octets);
+            let scope_id = data.get_u32();
+            Ok(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(octets),
+                port,
+                flowinfo,
+                scope_id,
+            )))
+        }
+        _ => Err(ProtoError::InvalidField {
+            packet_id,
+            field: "address_version",
+        }),
+    }
+}
+
+fn ensure_remaining(data: &impl Buf, packet_id: u8, expected: usize) -> Result<(), ProtoError> {
+    if data.remaining() < expected {
+        return Err(ProtoError::TruncatedField {
+            packet_id,
+            field: "address",
+            expected,
+            actual: data.remaining(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::{Bytes, BytesMut};
+
+    #[test]
+    fn test_ipv4_address() {
+        let addr: SocketAddr = "192.168.1.10:19132".parse().unwrap();
+
+        let mut buf = BytesMut::new();
+        write_address(&mut buf, &addr);
+        assert_eq!(buf.as_ref(), &[0x04, 0x3f, 0x57, 0xfe, 0xf5, 0x4a, 0xbc]);
+        assert_eq!(buf.len(), address_len(&addr));
+
+        let mut data = buf.freeze();
+        assert_eq!(read_address(&mut data, 0x07), Ok(addr));
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn test_ipv6_address_round_trip() {
+        let addr: SocketAddr = "[fe80::1]:19133".parse().unwrap();
+
+        let mut buf = BytesMut::new();
+        write_address(&mut buf, &addr);
+        assert_eq!(buf.len(), ADDRESS_V6_LEN);
+
+        assert_eq!(read_address(&mut buf.freeze(), 0x07), Ok(addr));
+    }
+
+    #[test]
+    fn test_invalid_addresses() {
+        let err = read_address(&mut Bytes::from_static(&[0x05, 0x00]), 0x07).unwrap_err();
+        assert_eq!(
+            err,
+            ProtoError::InvalidField {
+                packet_id: 0x07,
+                field: "address_version"
+            }
+        );
+
+        let err = read_address(&mut Bytes::from_static(&[0x04, 0x00, 0x00]), 0x07).unwrap_err();
+        assert!(matches!(
+            err,
+            ProtoError::TruncatedField {
+                field: "address",
+                ..
+            }
+        ));
+    }
+}
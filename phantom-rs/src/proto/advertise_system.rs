@@ -0,0 +1,104 @@
+use bytes::{Bytes, BytesMut};
+
+use super::guid::ServerGuid;
+use super::unconnected_pong::{PongData, UnconnectedPong, UnconnectedPongRef};
+use super::{MagicMode, ProtoError, MAGIC};
+
+// Packet constants
+pub const ADVERTISE_SYSTEM_ID: u8 = 0x1d;
+
+/// ADVERTISE_SYSTEM (0x1d) shares the UnconnectedPong layout and is sent by some
+/// server software instead of, or alongside, unconnected pongs
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdvertiseSystem {
+    pub ping_time: [u8; 8],
+    pub server_guid: ServerGuid,
+    pub magic: [u8; 16],
+    pub pong: PongData,
+}
+
+impl AdvertiseSystem {
+    /// Serializes the AdvertiseSystem into bytes for the 0x1d packet
+    pub fn build(&self) -> Result<Bytes, ProtoError> {
+        let mut buf = BytesMut::new();
+        self.encode_into(&mut buf)?;
+        Ok(buf.freeze())
+    }
+
+    /// Serializes the AdvertiseSystem into a caller-provided buffer
+    pub fn encode_into(&self, buf: &mut BytesMut) -> Result<(), ProtoError> {
+        UnconnectedPong::from(self.clone()).encode_with_id(ADVERTISE_SYSTEM_ID, buf)
+    }
+
+    /// Returns true if the magic bytes match `MAGIC`
+    pub fn has_valid_magic(&self) -> bool {
+        self.magic == MAGIC
+    }
+
+    /// Deserializes an AdvertiseSystem from bytes, accepting any magic bytes
+    pub fn from_bytes(data: Bytes) -> Result<Self, ProtoError> {
+        Self::from_bytes_with_mode(data, MagicMode::Lenient)
+    }
+
+    /// Deserializes an AdvertiseSystem from bytes, validating the magic bytes per `mode`
+    pub fn from_bytes_with_mode(data: Bytes, mode: MagicMode) -> Result<Self, ProtoError> {
+        let pong = UnconnectedPongRef::decode(data, ADVERTISE_SYSTEM_ID, mode)?.parse()?;
+        Ok(pong.into())
+    }
+}
+
+impl From<UnconnectedPong> for AdvertiseSystem {
+    fn from(pong: UnconnectedPong) -> Self {
+        Self {
+            ping_time: pong.ping_time,
+            server_guid: pong.server_guid,
+            magic: pong.magic,
+            pong: pong.pong,
+        }
+    }
+}
+
+impl From<AdvertiseSystem> for UnconnectedPong {
+    fn from(advertise: AdvertiseSystem) -> Self {
+        Self {
+            ping_time: advertise.ping_time,
+            server_guid: advertise.server_guid,
+            magic: advertise.magic,
+            pong: advertise.pong,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::unconnected_pong::UNCONNECTED_PONG_ID;
+
+    #[test]
+    fn test_advertise_system_round_trip() {
+        let mut pong = UnconnectedPong::new();
+        pong.pong.motd = "Advertised".to_string();
+        let advertise = AdvertiseSystem::from(pong);
+
+        let bytes = advertise.build().unwrap();
+        assert_eq!(bytes[0], ADVERTISE_SYSTEM_ID);
+
+        let parsed = AdvertiseSystem::from_bytes(bytes).expect("Failed to parse");
+        assert_eq!(parsed.pong.motd, "Advertised");
+        assert!(parsed.has_valid_magic());
+    }
+
+    #[test]
+    fn test_advertise_system_rejects_pong_id() {
+        let err = AdvertiseSystem::from_bytes(UnconnectedPong::new().build().unwrap())
+            .expect_err("Pong ID should be rejected");
+        assert_eq!(
+            err,
+            ProtoError::InvalidPacketId {
+                expected: ADVERTISE_SYSTEM_ID,
+                actual: UNCONNECTED_PONG_ID,
+            }
+        );
+    }
+}
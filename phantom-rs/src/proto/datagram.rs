@@ -0,0 +1,437 @@
+use bytes::{Buf, Bytes};
+
+use super::ProtoError;
+
+// Datagram header flags
+pub const DATAGRAM_VALID_FLAG: u8 = 0x80;
+pub const DATAGRAM_ACK_FLAG: u8 = 0x40;
+pub const DATAGRAM_NAK_FLAG: u8 = 0x20;
+
+// Frame header flags
+const FRAME_SPLIT_FLAG: u8 = 0x10;
+
+// Connected message IDs
+pub const DISCONNECTION_NOTIFICATION_ID: u8 = 0x15;
+
+/// RakNet frame reliability, stored in the top 3 bits of the frame flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Reliability {
+    Unreliable,
+    UnreliableSequenced,
+    Reliable,
+    ReliableOrdered,
+    ReliableSequenced,
+    UnreliableWithAckReceipt,
+    ReliableWithAckReceipt,
+    ReliableOrderedWithAckReceipt,
+}
+
+impl Reliability {
+    fn from_flags(flags: u8) -> Self {
+        match flags >> 5 {
+            0 => Reliability::Unreliable,
+            1 => Reliability::UnreliableSequenced,
+            2 => Reliability::Reliable,
+            3 => Reliability::ReliableOrdered,
+            4 => Reliability::ReliableSequenced,
+            5 => Reliability::UnreliableWithAckReceipt,
+            6 => Reliability::ReliableWithAckReceipt,
+            _ => Reliability::ReliableOrderedWithAckReceipt,
+        }
+    }
+
+    /// Whether frames with this reliability carry a reliable message index
+    pub fn is_reliable(&self) -> bool {
+        matches!(
+            self,
+            Reliability::Reliable
+                | Reliability::ReliableOrdered
+                | Reliability::ReliableSequenced
+                | Reliability::ReliableWithAckReceipt
+                | Reliability::ReliableOrderedWithAckReceipt
+        )
+    }
+
+    /// Whether frames with this reliability carry a sequenced index
+    pub fn is_sequenced(&self) -> bool {
+        matches!(
+            self,
+            Reliability::UnreliableSequenced | Reliability::ReliableSequenced
+        )
+    }
+
+    /// Whether frames with this reliability carry ordering info
+    pub fn is_ordered(&self) -> bool {
+        self.is_sequenced()
+            || matches!(
+                self,
+                Reliability::ReliableOrdered | Reliability::ReliableOrderedWithAckReceipt
+            )
+    }
+}
+
+/// Ordering info for ordered and sequenced frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderInfo {
+    pub index: u32,
+    pub channel: u8,
+}
+
+/// Split packet info for fragmented frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FragmentInfo {
+    pub count: u32,
+    pub id: u16,
+    pub index: u32,
+}
+
+/// A single encapsulated frame inside a frame set datagram
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Frame {
+    pub reliability: Reliability,
+    pub reliable_index: Option<u32>,
+    pub sequenced_index: Option<u32>,
+    pub order: Option<OrderInfo>,
+    pub fragment: Option<FragmentInfo>,
+    pub body: Bytes,
+}
+
+impl Frame {
+    /// ID of the message this frame carries. Only the first fragment of a split
+    /// message starts with the ID.
+    pub fn message_id(&self) -> Option<u8> {
+        match self.fragment {
+            Some(fragment) if fragment.index != 0 => None,
+            _ => self.body.first().copied(),
+        }
+    }
+}
+
+/// A frame set datagram (flags 0x80..0x8f) carrying one or more frames
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameSet {
+    pub flags: u8,
+    pub sequence_number: u32,
+    pub frames: Vec<Frame>,
+}
+
+impl FrameSet {
+    /// Returns true if any frame is a DisconnectionNotification
+    pub fn has_disconnect(&self) -> bool {
+        self.frames
+            .iter()
+            .any(|frame| frame.message_id() == Some(DISCONNECTION_NOTIFICATION_ID))
+    }
+}
+
+/// A single ACK/NAK record, either one sequence number or an inclusive range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AckRecord {
+    Single(u32),
+    Range(u32, u32),
+}
+
+impl AckRecord {
+    /// Number of sequence numbers covered by this record
+    pub fn count(&self) -> u32 {
+        match *self {
+            AckRecord::Single(_) => 1,
+            AckRecord::Range(start, end) => end.saturating_sub(start) + 1,
+        }
+    }
+}
+
+/// A connected RakNet datagram
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Datagram {
+    FrameSet(FrameSet),
+    Ack(Vec<AckRecord>),
+    Nak(Vec<AckRecord>),
+}
+
+impl Datagram {
+    /// Returns true if the first byte marks this as a connected datagram rather
+    /// than an offline message
+    pub fn is_datagram(data: &[u8]) -> bool {
+        data.first()
+            .is_some_and(|flags| flags & DATAGRAM_VALID_FLAG != 0)
+    }
+
+    /// Returns true if `data` is a frame set carrying a DisconnectionNotification,
+    /// meaning the sender is closing the session
+    pub fn is_disconnect(data: &Bytes) -> bool {
+        match Datagram::from_bytes(data.clone()) {
+            Ok(Datagram::FrameSet(frame_set)) => frame_set.has_disconnect(),
+            _ => false,
+        }
+    }
+
+    /// Deserializes a frame set, ACK, or NAK datagram from bytes
+    pub fn from_bytes(mut data: Bytes) -> Result<Self, ProtoError> {
+        if data.is_empty() {
+            return Err(ProtoError::TooShort {
+                packet_id: DATAGRAM_VALID_FLAG,
+                expected: 1,
+                actual: 0,
+            });
+        }
+
+        let flags = data.get_u8();
+        if flags & DATAGRAM_VALID_FLAG == 0 {
+            return Err(ProtoError::NotADatagram { flags });
+        }
+
+        if flags & DATAGRAM_ACK_FLAG != 0 {
+            return Ok(Datagram::Ack(read_ack_records(flags, &mut data)?));
+        }
+
+        if flags & DATAGRAM_NAK_FLAG != 0 {
+            return Ok(Datagram::Nak(read_ack_records(flags, &mut data)?));
+        }
+
+        let sequence_number = read_u24_le(flags, "sequence_number", &mut data)?;
+
+        let mut frames = Vec::new();
+        while data.has_remaining() {
+            frames.push(read_frame(flags, &mut data)?);
+        }
+
+        Ok(Datagram::FrameSet(FrameSet {
+            flags,
+            sequence_number,
+            frames,
+        }))
+    }
+}
+
+fn read_frame(packet_id: u8, data: &mut Bytes) -> Result<Frame, ProtoError> {
+    ensure_remaining(packet_id, "frame_header", 3, data)?;
+    let flags = data.get_u8();
+    let reliability = Reliability::from_flags(flags);
+    let length_bits = data.get_u16() as usize;
+
+    let reliable_index = if reliability.is_reliable() {
+        Some(read_u24_le(packet_id, "reliable_index", data)?)
+    } else {
+        None
+    };
+
+    let sequenced_index = if reliability.is_sequenced() {
+        Some(read_u24_le(packet_id, "sequenced_index", data)?)
+    } else {
+        None
+    };
+
+    let order = if reliability.is_ordered() {
+        let index = read_u24_le(packet_id, "order_index", data)?;
+        ensure_remaining(packet_id, "order_channel", 1, data)?;
+        Some(OrderInfo {
+            index,
+            channel: data.get_u8(),
+        })
+    } else {
+        None
+    };
+
+    let fragment = if flags & FRAME_SPLIT_FLAG != 0 {
+        ensure_remaining(packet_id, "fragment", 10, data)?;
+        Some(FragmentInfo {
+            count: data.get_u32(),
+            id: data.get_u16(),
+            index: data.get_u32(),
+        })
+    } else {
+        None
+    };
+
+    let body_len = length_bits.div_ceil(8);
+    ensure_remaining(packet_id, "body", body_len, data)?;
+    let body = data.split_to(body_len);
+
+    Ok(Frame {
+        reliability,
+        reliable_index,
+        sequenced_index,
+        order,
+        fragment,
+        body,
+    })
+}
+
+fn read_ack_records(packet_id: u8, data: &mut Bytes) -> Result<Vec<AckRecord>, ProtoError> {
+    ensure_remaining(packet_id, "record_count", 2, data)?;
+    let count = data.get_u16() as usize;
+
+    let mut records = Vec::with_capacity(count.min(data.remaining() / 4));
+    for _ in 0..count {
+        ensure_remaining(packet_id, "record_type", 1, data)?;
+        let is_single = data.get_u8() != 0;
+        let start = read_u24_le(packet_id, "record_start", data)?;
+
+        if is_single {
+            records.push(AckRecord::Single(start));
+        } else {
+            let end = read_u24_le(packet_id, "record_end", data)?;
+            records.push(AckRecord::Range(start, end));
+        }
+    }
+
+    Ok(records)
+}
+
+fn read_u24_le(packet_id: u8, field: &'static str, data: &mut Bytes) -> Result<u32, ProtoError> {
+    ensure_remaining(packet_id, field, 3, data)?;
+    Ok(data.get_uint_le(3) as u32)
+}
+
+fn ensure_remaining(
+    packet_id: u8,
+    field: &'static str,
+    expected: usize,
+    data: &Bytes,
+) -> Result<(), ProtoError> {
+    if data.remaining() < expected {
+        return Err(ProtoError::TruncatedField {
+            packet_id,
+            field,
+            expected,
+            actual: data.remaining(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_set_reliable_ordered() {
+        let test_bytes = [
+            0x84, // Frame set flags
+            0x05, 0x00, 0x00, // Sequence number 5
+            0x60, // ReliableOrdered
+            0x00, 0x18, // 24 bits = 3 bytes
+            0x02, 0x00, 0x00, // Reliable index 2
+            0x01, 0x00, 0x00, // Order index 1
+            0x00, // Order channel
+            0x15, 0xaa, 0xbb, // Body
+        ];
+
+        let datagram =
+            Datagram::from_bytes(Bytes::from(test_bytes.to_vec())).expect("Failed to parse");
+
+        let Datagram::FrameSet(frame_set) = datagram else {
+            panic!("Expected frame set");
+        };
+        assert_eq!(frame_set.sequence_number, 5);
+        assert_eq!(frame_set.frames.len(), 1);
+
+        let frame = &frame_set.frames[0];
+        assert_eq!(frame.reliability, Reliability::ReliableOrdered);
+        assert_eq!(frame.reliable_index, Some(2));
+        assert_eq!(frame.sequenced_index, None);
+        assert_eq!(
+            frame.order,
+            Some(OrderInfo {
+                index: 1,
+                channel: 0
+            })
+        );
+        assert_eq!(frame.fragment, None);
+        assert_eq!(frame.body.as_ref(), &[0x15, 0xaa, 0xbb]);
+        assert_eq!(frame.message_id(), Some(DISCONNECTION_NOTIFICATION_ID));
+        assert!(frame_set.has_disconnect());
+        assert!(Datagram::is_disconnect(&Bytes::from(test_bytes.to_vec())));
+    }
+
+    #[test]
+    fn test_frame_set_fragmented() {
+        let test_bytes = [
+            0x8c, // Frame set flags
+            0x01, 0x00, 0x00, // Sequence number 1
+            0x50, // Reliable + split
+            0x00, 0x08, // 8 bits = 1 byte
+            0x07, 0x00, 0x00, // Reliable index 7
+            0x00, 0x00, 0x00, 0x03, // Split count
+            0x00, 0x09, // Split ID
+            0x00, 0x00, 0x00, 0x01, // Split index
+            0xfe, // Body
+        ];
+
+        let Datagram::FrameSet(frame_set) =
+            Datagram::from_bytes(Bytes::from(test_bytes.to_vec())).expect("Failed to parse")
+        else {
+            panic!("Expected frame set");
+        };
+
+        assert_eq!(
+            frame_set.frames[0].fragment,
+            Some(FragmentInfo {
+                count: 3,
+                id: 9,
+                index: 1
+            })
+        );
+        assert_eq!(frame_set.frames[0].message_id(), None);
+        assert!(!frame_set.has_disconnect());
+    }
+
+    #[test]
+    fn test_ack_and_nak_records() {
+        let ack_bytes = [
+            0xc0, // ACK
+            0x00, 0x02, // Record count
+            0x01, 0x0a, 0x00, 0x00, // Single 10
+            0x00, 0x0c, 0x00, 0x00, 0x0f, 0x00, 0x00, // Range 12..=15
+        ];
+
+        let datagram =
+            Datagram::from_bytes(Bytes::from(ack_bytes.to_vec())).expect("Failed to parse ACK");
+        assert_eq!(
+            datagram,
+            Datagram::Ack(vec![AckRecord::Single(10), AckRecord::Range(12, 15)])
+        );
+        assert_eq!(AckRecord::Range(12, 15).count(), 4);
+
+        let nak_bytes = [0xa0, 0x00, 0x01, 0x01, 0x03, 0x00, 0x00];
+        let datagram =
+            Datagram::from_bytes(Bytes::from(nak_bytes.to_vec())).expect("Failed to parse NAK");
+        assert_eq!(datagram, Datagram::Nak(vec![AckRecord::Single(3)]));
+    }
+
+    #[test]
+    fn test_truncated_frame_body() {
+        let test_bytes = [0x84, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x01];
+
+        let err = Datagram::from_bytes(Bytes::from(test_bytes.to_vec()))
+            .expect_err("Truncated body should fail");
+        assert_eq!(
+            err,
+            ProtoError::TruncatedField {
+                packet_id: 0x84,
+                field: "body",
+                expected: 8,
+                actual: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_offline_message_is_not_datagram() {
+        assert!(!Datagram::is_datagram(&[0x01]));
+        assert!(!Datagram::is_datagram(&[]));
+        assert!(!Datagram::is_disconnect(&Bytes::from_static(&[0x15])));
+
+        let err = Datagram::from_bytes(Bytes::from_static(&[0x1c]))
+            .expect_err("Offline message should fail");
+        assert_eq!(err, ProtoError::NotADatagram { flags: 0x1c });
+    }
+}
@@ -0,0 +1,65 @@
+use thiserror::Error;
+
+/// Errors produced while decoding protocol packets
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ProtoError {
+    #[error(
+        "Packet 0x{packet_id:02x} is too short: expected at least {expected} bytes, got {actual}"
+    )]
+    TooShort {
+        packet_id: u8,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("Invalid packet ID: expected 0x{expected:02x}, got 0x{actual:02x}")]
+    InvalidPacketId { expected: u8, actual: u8 },
+
+    #[error("Packet 0x{packet_id:02x} field `{field}` is truncated: expected {expected} bytes, got {actual}")]
+    TruncatedField {
+        packet_id: u8,
+        field: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("Packet 0x{packet_id:02x} field `{field}` has an invalid value")]
+    InvalidField { packet_id: u8, field: &'static str },
+
+    #[error("Packet 0x{packet_id:02x} field `{field}` is not valid UTF-8")]
+    InvalidUtf8 { packet_id: u8, field: &'static str },
+
+    #[error("Packet 0x{packet_id:02x} has invalid magic bytes")]
+    InvalidMagic { packet_id: u8 },
+
+    #[error("Not a RakNet datagram: flags 0x{flags:02x}")]
+    NotADatagram { flags: u8 },
+
+    #[error(
+        "MTU {mtu} is outside the supported range {}..={}",
+        super::mtu::MIN_MTU_SIZE,
+        super::mtu::MAX_MTU_SIZE
+    )]
+    InvalidMtu { mtu: u16 },
+
+    #[error("Packet of {size} bytes does not fit in MTU {mtu}")]
+    PacketTooLarge { size: usize, mtu: u16 },
+
+    #[error("Field `{field}` is not a valid VarInt")]
+    InvalidVarInt { field: &'static str },
+
+    #[error("Invalid JSON payload: {0}")]
+    InvalidJson(String),
+
+    #[error("Pong field `{field}` {reason}")]
+    InvalidPongData {
+        field: &'static str,
+        reason: &'static str,
+    },
+
+    #[error("MOTD {reason}")]
+    InvalidMotd { reason: &'static str },
+
+    #[error("Empty pong data string")]
+    EmptyPongData,
+}
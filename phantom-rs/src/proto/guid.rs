@@ -0,0 +1,84 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A RakNet server GUID. On the wire it is 8 big-endian bytes in the packet
+/// header, and the pong payload repeats it as a decimal string (`server_id`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct ServerGuid(pub u64);
+
+impl ServerGuid {
+    pub fn from_bytes(bytes: [u8; 8]) -> Self {
+        Self(u64::from_be_bytes(bytes))
+    }
+
+    pub fn to_bytes(self) -> [u8; 8] {
+        self.0.to_be_bytes()
+    }
+
+    /// Returns a random GUID, for spoofing a server identity
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+}
+
+impl From<u64> for ServerGuid {
+    fn from(guid: u64) -> Self {
+        Self(guid)
+    }
+}
+
+impl From<ServerGuid> for u64 {
+    fn from(guid: ServerGuid) -> Self {
+        guid.0
+    }
+}
+
+impl From<[u8; 8]> for ServerGuid {
+    fn from(bytes: [u8; 8]) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+
+impl From<ServerGuid> for [u8; 8] {
+    fn from(guid: ServerGuid) -> Self {
+        guid.to_bytes()
+    }
+}
+
+/// Formats as the decimal string used by `PongData::server_id`
+impl fmt::Display for ServerGuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Parses the decimal string used by `PongData::server_id`
+impl FromStr for ServerGuid {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().parse().map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_guid_conversions() {
+        let bytes = [0xa2, 0x09, 0x63, 0x85, 0x9f, 0xd0, 0x03, 0xd7];
+        let guid = ServerGuid::from_bytes(bytes);
+
+        assert_eq!(guid.0, 11675972934497731543);
+        assert_eq!(guid.to_bytes(), bytes);
+        assert_eq!(guid.to_string(), "11675972934497731543");
+        assert_eq!("11675972934497731543".parse::<ServerGuid>(), Ok(guid));
+        assert!("not a guid".parse::<ServerGuid>().is_err());
+    }
+}
@@ -0,0 +1,455 @@
+//! Java Edition Server List Ping over TCP.
+//!
+//! Every packet is framed as `VarInt length | VarInt packet id | fields`. A status
+//! query is: Handshake (next state 1) -> StatusRequest -> StatusResponse (JSON),
+//! optionally followed by Ping -> Pong to measure latency.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::json::{self, Value};
+use super::ProtoError;
+
+// Packet constants
+pub const HANDSHAKE_ID: u8 = 0x00;
+pub const STATUS_REQUEST_ID: u8 = 0x00;
+pub const STATUS_RESPONSE_ID: u8 = 0x00;
+pub const PING_ID: u8 = 0x01;
+pub const PONG_ID: u8 = 0x01;
+
+/// Handshake next state requesting the status protocol
+pub const NEXT_STATE_STATUS: i32 = 1;
+
+/// Protocol version sent when the client doesn't care which version the server runs
+pub const ANY_PROTOCOL_VERSION: i32 = -1;
+
+/// Default Java Edition server port
+pub const DEFAULT_JAVA_PORT: u16 = 25565;
+
+/// VarInts are at most 5 bytes long
+const MAX_VARINT_LEN: usize = 5;
+
+/// Largest packet the protocol allows (3-byte VarInt length)
+pub const MAX_PACKET_LEN: usize = 2_097_151;
+
+/// Writes a protocol VarInt into `buf`
+pub fn write_varint(buf: &mut BytesMut, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7f == 0 {
+            buf.put_u8(value as u8);
+            return;
+        }
+        buf.put_u8((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+/// Number of bytes `value` takes when encoded as a VarInt
+pub fn varint_len(value: i32) -> usize {
+    let mut value = value as u32;
+    let mut len = 1;
+    while value & !0x7f != 0 {
+        value >>= 7;
+        len += 1;
+    }
+    len
+}
+
+/// Reads a protocol VarInt from `data`
+pub fn read_varint(data: &mut impl Buf, field: &'static str) -> Result<i32, ProtoError> {
+    let mut value: u32 = 0;
+    for i in 0..MAX_VARINT_LEN {
+        if !data.has_remaining() {
+            return Err(ProtoError::InvalidVarInt { field });
+        }
+        let byte = data.get_u8();
+        value |= ((byte & 0x7f) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(ProtoError::InvalidVarInt { field })
+}
+
+fn write_string(buf: &mut BytesMut, value: &str) {
+    write_varint(buf, value.len() as i32);
+    buf.put_slice(value.as_bytes());
+}
+
+fn read_string(data: &mut Bytes, packet_id: u8, field: &'static str) -> Result<String, ProtoError> {
+    let len = read_varint(data, field)?;
+    let len = usize::try_from(len).map_err(|_| ProtoError::InvalidVarInt { field })?;
+    if data.remaining() < len {
+        return Err(ProtoError::TruncatedField {
+            packet_id,
+            field,
+            expected: len,
+            actual: data.remaining(),
+        });
+    }
+    let bytes = data.split_to(len);
+    std::str::from_utf8(&bytes)
+        .map(str::to_string)
+        .map_err(|_| ProtoError::InvalidUtf8 { packet_id, field })
+}
+
+/// Prefixes a packet body (id + fields) with its VarInt length
+fn frame(body: &[u8], buf: &mut BytesMut) {
+    buf.reserve(varint_len(body.len() as i32) + body.len());
+    write_varint(buf, body.len() as i32);
+    buf.put_slice(body);
+}
+
+/// Splits one complete packet (id + fields, without the length prefix) off the
+/// front of a TCP read buffer. Returns `None` if more data is needed.
+pub fn decode_frame(buf: &mut BytesMut) -> Result<Option<Bytes>, ProtoError> {
+    let mut peek = &buf[..];
+    let len = match read_varint(&mut peek, "length") {
+        Ok(len) => len,
+        // A VarInt cut short by the end of the buffer just needs more data
+        Err(_) if buf.len() < MAX_VARINT_LEN => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let len = usize::try_from(len).map_err(|_| ProtoError::InvalidVarInt { field: "length" })?;
+    if len > MAX_PACKET_LEN {
+        return Err(ProtoError::InvalidVarInt { field: "length" });
+    }
+    let prefix_len = buf.len() - peek.len();
+
+    if peek.len() < len {
+        return Ok(None);
+    }
+
+    buf.advance(prefix_len);
+    Ok(Some(buf.split_to(len).freeze()))
+}
+
+fn expect_packet_id(data: &mut Bytes, expected: u8) -> Result<(), ProtoError> {
+    if !data.has_remaining() {
+        return Err(ProtoError::TooShort {
+            packet_id: expected,
+            expected: 1,
+            actual: 0,
+        });
+    }
+    let actual = data.get_u8();
+    if actual != expected {
+        return Err(ProtoError::InvalidPacketId { expected, actual });
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Handshake {
+    pub protocol_version: i32,
+    pub server_address: String,
+    pub server_port: u16,
+    pub next_state: i32,
+}
+
+impl Handshake {
+    /// Creates a handshake that switches the connection to the status protocol
+    pub fn status(server_address: &str, server_port: u16) -> Self {
+        Self {
+            protocol_version: ANY_PROTOCOL_VERSION,
+            server_address: server_address.to_string(),
+            server_port,
+            next_state: NEXT_STATE_STATUS,
+        }
+    }
+
+    /// Serializes the framed Handshake packet
+    pub fn build(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    /// Serializes the framed Handshake packet into a caller-provided buffer
+    pub fn encode_into(&self, buf: &mut BytesMut) {
+        let mut body = BytesMut::new();
+        body.put_u8(HANDSHAKE_ID);
+        write_varint(&mut body, self.protocol_version);
+        write_string(&mut body, &self.server_address);
+        body.put_u16(self.server_port);
+        write_varint(&mut body, self.next_state);
+        frame(&body, buf);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StatusRequest;
+
+impl StatusRequest {
+    /// Serializes the framed StatusRequest packet
+    pub fn build(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    /// Serializes the framed StatusRequest packet into a caller-provided buffer
+    pub fn encode_into(&self, buf: &mut BytesMut) {
+        frame(&[STATUS_REQUEST_ID], buf);
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatusResponse {
+    pub json: String,
+}
+
+impl StatusResponse {
+    /// Deserializes a StatusResponse from an unframed packet (see `decode_frame`)
+    pub fn from_bytes(mut data: Bytes) -> Result<Self, ProtoError> {
+        expect_packet_id(&mut data, STATUS_RESPONSE_ID)?;
+        let json = read_string(&mut data, STATUS_RESPONSE_ID, "json")?;
+        Ok(Self { json })
+    }
+
+    /// Parses the JSON payload
+    pub fn status(&self) -> Result<JavaStatus, ProtoError> {
+        JavaStatus::from_value(&json::parse(&self.json)?)
+    }
+}
+
+/// Ping and Pong share a layout: an opaque i64 the server echoes back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JavaPing {
+    pub payload: i64,
+}
+
+impl JavaPing {
+    /// Serializes the framed Ping packet
+    pub fn build(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    /// Serializes the framed Ping packet into a caller-provided buffer
+    pub fn encode_into(&self, buf: &mut BytesMut) {
+        let mut body = [0u8; 9];
+        body[0] = PING_ID;
+        body[1..].copy_from_slice(&self.payload.to_be_bytes());
+        frame(&body, buf);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JavaPong {
+    pub payload: i64,
+}
+
+impl JavaPong {
+    /// Deserializes a Pong from an unframed packet (see `decode_frame`)
+    pub fn from_bytes(mut data: Bytes) -> Result<Self, ProtoError> {
+        expect_packet_id(&mut data, PONG_ID)?;
+        if data.remaining() < 8 {
+            return Err(ProtoError::TruncatedField {
+                packet_id: PONG_ID,
+                field: "payload",
+                expected: 8,
+                actual: data.remaining(),
+            });
+        }
+        Ok(Self {
+            payload: data.get_i64(),
+        })
+    }
+}
+
+/// Parsed Server List Ping status JSON
+#[derive(Debug, Clone)]
+pub struct JavaStatus {
+    pub version: JavaVersion,
+    pub players: Option<JavaPlayers>,
+    /// A plain string or a chat component; see `description_text`
+    pub description: Value,
+    pub favicon: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct JavaVersion {
+    pub name: String,
+    pub protocol: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct JavaPlayers {
+    pub max: i64,
+    pub online: i64,
+    pub sample: Vec<JavaPlayerSample>,
+}
+
+#[derive(Debug, Clone)]
+pub struct JavaPlayerSample {
+    pub name: String,
+    pub id: String,
+}
+
+impl JavaStatus {
+    /// Flattens the description, which is either a plain string or a chat component
+    pub fn description_text(&self) -> String {
+        let mut text = String::new();
+        flatten_chat(&self.description, &mut text);
+        text
+    }
+
+    /// Reads the status fields. Only `version` is required; the rest are
+    /// left empty when missing or null.
+    fn from_value(status: &Value) -> Result<Self, ProtoError> {
+        let version = field(status, "version", Some)?;
+        let players = match status.get("players") {
+            None | Some(Value::Null) => None,
+            Some(players) => Some(JavaPlayers {
+                max: field(players, "max", Value::as_i64)?,
+                online: field(players, "online", Value::as_i64)?,
+                sample: match players.get("sample") {
+                    Some(Value::Array(sample)) => sample
+                        .iter()
+                        .map(|player| {
+                            Ok(JavaPlayerSample {
+                                name: field(player, "name", Value::as_str)?.to_string(),
+                                id: field(player, "id", Value::as_str)?.to_string(),
+                            })
+                        })
+                        .collect::<Result<_, ProtoError>>()?,
+                    _ => Vec::new(),
+                },
+            }),
+        };
+
+        Ok(JavaStatus {
+            version: JavaVersion {
+                name: field(version, "name", Value::as_str)?.to_string(),
+                protocol: field(version, "protocol", |protocol| {
+                    protocol.as_i64().and_then(|p| i32::try_from(p).ok())
+                })?,
+            },
+            players,
+            description: status.get("description").cloned().unwrap_or_default(),
+            favicon: status
+                .get("favicon")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+        })
+    }
+}
+
+/// The member `key` of `object`, read by `read`
+fn field<'a, T>(
+    object: &'a Value,
+    key: &'static str,
+    read: impl FnOnce(&'a Value) -> Option<T>,
+) -> Result<T, ProtoError> {
+    object
+        .get(key)
+        .and_then(read)
+        .ok_or_else(|| ProtoError::InvalidJson(format!("missing or invalid `{}`", key)))
+}
+
+fn flatten_chat(value: &Value, out: &mut String) {
+    match value {
+        Value::String(s) => out.push_str(s),
+        Value::Array(parts) => parts.iter().for_each(|part| flatten_chat(part, out)),
+        Value::Object(_) => {
+            if let Some(text) = value.get("text") {
+                flatten_chat(text, out);
+            }
+            if let Some(extra) = value.get("extra") {
+                flatten_chat(extra, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint_round_trip() {
+        for value in [0, 1, 127, 128, 255, 25565, 2097151, i32::MAX, -1, i32::MIN] {
+            let mut buf = BytesMut::new();
+            write_varint(&mut buf, value);
+            assert_eq!(buf.len(), varint_len(value));
+
+            let mut bytes = buf.freeze();
+            assert_eq!(read_varint(&mut bytes, "value"), Ok(value));
+        }
+
+        let mut too_long = Bytes::from_static(&[0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        assert_eq!(
+            read_varint(&mut too_long, "value"),
+            Err(ProtoError::InvalidVarInt { field: "value" })
+        );
+    }
+
+    #[test]
+    fn test_handshake_encoding() {
+        let bytes = Handshake::status("localhost", 25565).build();
+
+        assert_eq!(
+            bytes.as_ref(),
+            &[
+                0x13, // Length
+                0x00, // Packet ID
+                0xff, 0xff, 0xff, 0xff, 0x0f, // Protocol version -1
+                0x09, b'l', b'o', b'c', b'a', b'l', b'h', b'o', b's', b't', // Address
+                0x63, 0xdd, // Port
+                0x01, // Next state
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_frame_waits_for_full_packet() {
+        let mut encoded = BytesMut::new();
+        frame(&[STATUS_RESPONSE_ID, 0x02, b'{', b'}'], &mut encoded);
+
+        let mut partial = BytesMut::from(&encoded[..3]);
+        assert_eq!(decode_frame(&mut partial), Ok(None));
+
+        let mut buf = encoded.clone();
+        buf.extend_from_slice(&[0x01]);
+        let packet = decode_frame(&mut buf)
+            .expect("Failed to decode")
+            .expect("Packet should be complete");
+        assert_eq!(packet.as_ref(), &[0x00, 0x02, b'{', b'}']);
+        assert_eq!(buf.as_ref(), &[0x01]);
+    }
+
+    #[test]
+    fn test_status_response_parse() {
+        let json = r#"{"version":{"name":"1.21.4","protocol":769},"players":{"max":20,"online":1,"sample":[{"name":"jhead","id":"4566e69f-c907-48ee-8d71-d7ba5aa00d20"}]},"description":{"text":"A ","extra":[{"text":"Minecraft"},"Server"]}}"#;
+
+        let mut body = BytesMut::new();
+        body.put_u8(STATUS_RESPONSE_ID);
+        write_string(&mut body, json);
+
+        let response = StatusResponse::from_bytes(body.freeze()).expect("Failed to parse");
+        let status = response.status().expect("Failed to parse JSON");
+
+        assert_eq!(status.version.name, "1.21.4");
+        assert_eq!(status.version.protocol, 769);
+        let players = status.players.as_ref().expect("Players missing");
+        assert_eq!(players.online, 1);
+        assert_eq!(players.max, 20);
+        assert_eq!(players.sample[0].name, "jhead");
+        assert_eq!(status.description_text(), "A MinecraftServer");
+    }
+
+    #[test]
+    fn test_ping_pong_round_trip() {
+        let mut buf = BytesMut::from(&JavaPing { payload: 1234 }.build()[..]);
+        let packet = decode_frame(&mut buf).unwrap().unwrap();
+        let pong = JavaPong::from_bytes(packet).expect("Failed to parse pong");
+        assert_eq!(pong.payload, 1234);
+    }
+}
@@ -0,0 +1,271 @@
+//! A small JSON reader for the few JSON payloads the protocols carry (e.g. the
+//! Java status response), so they parse without the `serde` feature.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use super::ProtoError;
+
+/// Deepest nesting accepted, so hostile payloads can't exhaust the stack
+const MAX_DEPTH: usize = 64;
+
+/// A parsed JSON value. Numbers are kept as `f64`, which holds every integer
+/// these payloads use exactly.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Value {
+    #[default]
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Members in the order they appear
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The member named `key`, if this is an object that has one
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The number, if this is one with no fractional part
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Number(n) if n.fract() == 0.0 => Some(*n as i64),
+            _ => None,
+        }
+    }
+}
+
+/// Parses one JSON document. Anything but whitespace after it is an error.
+pub fn parse(json: &str) -> Result<Value, ProtoError> {
+    let mut parser = Parser {
+        chars: json.chars().peekable(),
+    };
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    match parser.chars.next() {
+        None => Ok(value),
+        Some(c) => Err(invalid(format!("unexpected `{}` after the value", c))),
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> ProtoError {
+    ProtoError::InvalidJson(reason.into())
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn value(&mut self, depth: usize) -> Result<Value, ProtoError> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("nested too deeply"));
+        }
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some('"') => self.string().map(Value::String),
+            Some('t') => self.literal("true", Value::Bool(true)),
+            Some('f') => self.literal("false", Value::Bool(false)),
+            Some('n') => self.literal("null", Value::Null),
+            Some(c) if *c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(invalid(format!("unexpected `{}`", c))),
+            None => Err(invalid("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Value, ProtoError> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            members.push((key, self.value(depth + 1)?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Value::Object(members)),
+                _ => return Err(invalid("expected `,` or `}` in object")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Value, ProtoError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&']').is_some() {
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Value::Array(items)),
+                _ => return Err(invalid("expected `,` or `]` in array")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ProtoError> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(out),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('/') => out.push('/'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('n') => out.push('\n'),
+                    Some('r') => out.push('\r'),
+                    Some('t') => out.push('\t'),
+                    Some('u') => out.push(self.unicode_escape()?),
+                    _ => return Err(invalid("invalid escape in string")),
+                },
+                Some(c) if c < ' ' => return Err(invalid("control character in string")),
+                Some(c) => out.push(c),
+                None => return Err(invalid("unterminated string")),
+            }
+        }
+    }
+
+    /// The character for a `\u` escape, joining surrogate pairs. Lone
+    /// surrogates become U+FFFD rather than failing the whole payload.
+    fn unicode_escape(&mut self) -> Result<char, ProtoError> {
+        let high = self.hex4()?;
+        if !(0xd800..0xdc00).contains(&high) {
+            return Ok(char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER));
+        }
+        if self.chars.next_if_eq(&'\\').is_none() || self.chars.next_if_eq(&'u').is_none() {
+            return Ok(char::REPLACEMENT_CHARACTER);
+        }
+        let low = self.hex4()?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return Ok(char::REPLACEMENT_CHARACTER);
+        }
+        let code = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+        Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    fn hex4(&mut self) -> Result<u32, ProtoError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .chars
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| invalid("invalid `\\u` escape"))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Value, ProtoError> {
+        let mut text = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            text.push(c);
+        }
+        text.parse()
+            .map(Value::Number)
+            .map_err(|_| invalid(format!("invalid number `{}`", text)))
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, ProtoError> {
+        for expected in word.chars() {
+            if self.chars.next() != Some(expected) {
+                return Err(invalid(format!("expected `{}`", word)));
+            }
+        }
+        Ok(value)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ProtoError> {
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(invalid(format!("expected `{}`", expected))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .next_if(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+            .is_some()
+        {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_values() {
+        let value = parse(r#" {"a": [1, -2.5e1, true, null], "b": "x\"é😀", "c": {}} "#).unwrap();
+
+        assert_eq!(
+            value.get("a"),
+            Some(&Value::Array(vec![
+                Value::Number(1.0),
+                Value::Number(-25.0),
+                Value::Bool(true),
+                Value::Null,
+            ]))
+        );
+        assert_eq!(value.get("b").and_then(Value::as_str), Some("x\"é😀"));
+        assert_eq!(value.get("c"), Some(&Value::Object(vec![])));
+        assert_eq!(value.get("missing"), None);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed() {
+        for json in [
+            "",
+            "{",
+            r#"{"a" 1}"#,
+            "[1,]",
+            r#""unterminated"#,
+            "tru",
+            "1 2",
+            r#""\x""#,
+        ] {
+            assert!(
+                matches!(parse(json), Err(ProtoError::InvalidJson(_))),
+                "{json}"
+            );
+        }
+
+        let deep = "[".repeat(MAX_DEPTH + 2);
+        assert!(matches!(parse(&deep), Err(ProtoError::InvalidJson(_))));
+    }
+}
@@ -1,2 +1,43 @@
+pub mod address;
+pub mod advertise_system;
+pub mod datagram;
+pub mod error;
+pub mod guid;
+pub mod java;
+pub mod json;
+pub mod motd;
+pub mod mtu;
+pub mod packet;
+pub mod pong_builder;
+pub mod query;
 pub mod unconnected_ping;
 pub mod unconnected_pong;
+pub mod versions;
+
+pub use error::ProtoError;
+pub use guid::ServerGuid;
+
+// Magic bytes used in the protocol
+pub const MAGIC: [u8; 16] = [
+    0x00, 0xff, 0xff, 0x00, 0xfe, 0xfe, 0xfe, 0xfe, 0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56, 0x78,
+];
+
+/// How decoders treat offline messages whose magic bytes don't match `MAGIC`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MagicMode {
+    /// Reject the packet with `ProtoError::InvalidMagic`
+    Strict,
+    /// Accept the packet; callers can check `has_valid_magic()`
+    #[default]
+    Lenient,
+}
+
+impl MagicMode {
+    /// Checks `magic` against `MAGIC` according to this mode
+    pub fn check(self, packet_id: u8, magic: &[u8; 16]) -> Result<(), ProtoError> {
+        if self == MagicMode::Strict && *magic != MAGIC {
+            return Err(ProtoError::InvalidMagic { packet_id });
+        }
+        Ok(())
+    }
+}
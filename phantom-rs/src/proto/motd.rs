@@ -0,0 +1,153 @@
+use super::ProtoError;
+
+/// Prefix character for color and format codes
+pub const FORMAT_CHAR: char = '§';
+
+/// Longest MOTD line, in visible characters, that clients render in the server list
+pub const MAX_MOTD_LEN: usize = 64;
+
+/// Color codes Bedrock understands: the classic 16 plus minecoin gold and the
+/// material colors added in 1.19.80
+const COLOR_CODES: &str = "0123456789abcdefghijmnpqstuv";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatCode {
+    Color(char),
+    Obfuscated,
+    Bold,
+    Italic,
+    Reset,
+}
+
+impl FormatCode {
+    /// Maps the character following `§` to a code, if the client recognizes it
+    pub fn from_char(c: char) -> Option<Self> {
+        let c = c.to_ascii_lowercase();
+        match c {
+            'k' => Some(FormatCode::Obfuscated),
+            'l' => Some(FormatCode::Bold),
+            'o' => Some(FormatCode::Italic),
+            'r' => Some(FormatCode::Reset),
+            c if COLOR_CODES.contains(c) => Some(FormatCode::Color(c)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MotdSegment {
+    Text(String),
+    Code(FormatCode),
+}
+
+/// Splits a MOTD into text runs and format codes. Unknown codes are kept as text,
+/// matching how the client renders them.
+pub fn parse(motd: &str) -> Vec<MotdSegment> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = motd.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == FORMAT_CHAR {
+            if let Some(code) = chars.peek().copied().and_then(FormatCode::from_char) {
+                chars.next();
+                if !text.is_empty() {
+                    segments.push(MotdSegment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(MotdSegment::Code(code));
+                continue;
+            }
+        }
+        text.push(c);
+    }
+
+    if !text.is_empty() {
+        segments.push(MotdSegment::Text(text));
+    }
+
+    segments
+}
+
+/// Removes all recognized format codes, leaving the text the client displays
+pub fn strip(motd: &str) -> String {
+    parse(motd)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            MotdSegment::Text(text) => Some(text),
+            MotdSegment::Code(_) => None,
+        })
+        .collect()
+}
+
+/// Number of characters the client displays, not counting format codes
+pub fn visible_len(motd: &str) -> usize {
+    parse(motd)
+        .iter()
+        .map(|segment| match segment {
+            MotdSegment::Text(text) => text.chars().count(),
+            MotdSegment::Code(_) => 0,
+        })
+        .sum()
+}
+
+/// Checks that a MOTD encodes cleanly and fits in the server list
+pub fn validate(motd: &str) -> Result<(), ProtoError> {
+    if motd.contains(';') {
+        return Err(ProtoError::InvalidMotd {
+            reason: "must not contain ';'",
+        });
+    }
+
+    if motd.ends_with(FORMAT_CHAR) {
+        return Err(ProtoError::InvalidMotd {
+            reason: "ends with a dangling format code",
+        });
+    }
+
+    if visible_len(motd) > MAX_MOTD_LEN {
+        return Err(ProtoError::InvalidMotd {
+            reason: "is longer than clients display",
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_strip() {
+        let motd = "phantom §cServer §lOffline§r!";
+        assert_eq!(
+            parse(motd),
+            vec![
+                MotdSegment::Text("phantom ".to_string()),
+                MotdSegment::Code(FormatCode::Color('c')),
+                MotdSegment::Text("Server ".to_string()),
+                MotdSegment::Code(FormatCode::Bold),
+                MotdSegment::Text("Offline".to_string()),
+                MotdSegment::Code(FormatCode::Reset),
+                MotdSegment::Text("!".to_string()),
+            ]
+        );
+        assert_eq!(strip(motd), "phantom Server Offline!");
+        assert_eq!(visible_len(motd), 23);
+    }
+
+    #[test]
+    fn test_unknown_codes_are_displayed() {
+        assert_eq!(strip("§zhi"), "§zhi");
+        assert_eq!(visible_len("§zhi"), 4);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("§aHello").is_ok());
+        assert!(validate(&format!("§a{}", "x".repeat(MAX_MOTD_LEN))).is_ok());
+        assert!(validate(&"x".repeat(MAX_MOTD_LEN + 1)).is_err());
+        assert!(validate("Hello§").is_err());
+        assert!(validate("a;b").is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use super::ProtoError;
+
+/// Smallest MTU a RakNet peer is required to support
+pub const MIN_MTU_SIZE: u16 = 576;
+
+/// Largest MTU RakNet will negotiate
+pub const MAX_MTU_SIZE: u16 = 1492;
+
+/// MTU Bedrock clients start discovery with
+pub const DEFAULT_MTU_SIZE: u16 = 1400;
+
+/// IPv4 (20 bytes) + UDP (8 bytes) header overhead
+pub const UDP_HEADER_SIZE_V4: u16 = 28;
+
+/// IPv6 (40 bytes) + UDP (8 bytes) header overhead
+pub const UDP_HEADER_SIZE_V6: u16 = 48;
+
+/// Size of socket receive buffers, large enough for any negotiated datagram
+pub const RECV_BUFFER_SIZE: usize = MAX_MTU_SIZE as usize;
+
+/// Packet ID (1) + magic (16) + protocol version (1)
+const OCR1_HEADER_SIZE: u16 = 18;
+
+/// Returns the MTU if it is within the RakNet bounds
+pub fn validate_mtu(mtu: u16) -> Result<u16, ProtoError> {
+    if !(MIN_MTU_SIZE..=MAX_MTU_SIZE).contains(&mtu) {
+        return Err(ProtoError::InvalidMtu { mtu });
+    }
+    Ok(mtu)
+}
+
+/// Clamps an MTU into the RakNet bounds
+pub fn clamp_mtu(mtu: u16) -> u16 {
+    mtu.clamp(MIN_MTU_SIZE, MAX_MTU_SIZE)
+}
+
+/// Number of zero bytes an OpenConnectionRequest1 is padded with to probe `mtu`
+pub fn ocr1_padding(mtu: u16) -> usize {
+    mtu.saturating_sub(UDP_HEADER_SIZE_V4 + OCR1_HEADER_SIZE) as usize
+}
+
+/// MTU a peer is probing, given the UDP payload length of its OpenConnectionRequest1
+pub fn mtu_from_ocr1_len(payload_len: usize) -> u16 {
+    let mtu = payload_len.saturating_add(UDP_HEADER_SIZE_V4 as usize);
+    mtu.min(u16::MAX as usize) as u16
+}
+
+/// Largest UDP payload that fits in a datagram for the given MTU
+pub fn max_payload_size(mtu: u16) -> usize {
+    mtu.saturating_sub(UDP_HEADER_SIZE_V4) as usize
+}
+
+/// Checks that a UDP payload fits within the given MTU
+pub fn ensure_fits(payload_len: usize, mtu: u16) -> Result<(), ProtoError> {
+    if payload_len > max_payload_size(mtu) {
+        return Err(ProtoError::PacketTooLarge {
+            size: payload_len,
+            mtu,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_mtu() {
+        assert_eq!(validate_mtu(DEFAULT_MTU_SIZE), Ok(DEFAULT_MTU_SIZE));
+        assert_eq!(validate_mtu(MIN_MTU_SIZE), Ok(MIN_MTU_SIZE));
+        assert_eq!(validate_mtu(MAX_MTU_SIZE), Ok(MAX_MTU_SIZE));
+        assert_eq!(validate_mtu(100), Err(ProtoError::InvalidMtu { mtu: 100 }));
+        assert_eq!(clamp_mtu(9000), MAX_MTU_SIZE);
+    }
+
+    #[test]
+    fn test_ocr1_padding_round_trip() {
+        let padding = ocr1_padding(DEFAULT_MTU_SIZE);
+        assert_eq!(padding, 1400 - 28 - 18);
+
+        let payload_len = OCR1_HEADER_SIZE as usize + padding;
+        assert_eq!(mtu_from_ocr1_len(payload_len), DEFAULT_MTU_SIZE);
+    }
+
+    #[test]
+    fn test_ensure_fits() {
+        assert!(ensure_fits(1372, DEFAULT_MTU_SIZE).is_ok());
+        assert_eq!(
+            ensure_fits(1373, DEFAULT_MTU_SIZE),
+            Err(ProtoError::PacketTooLarge {
+                size: 1373,
+                mtu: DEFAULT_MTU_SIZE,
+            })
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use bytes::Bytes;
+
+use super::advertise_system::ADVERTISE_SYSTEM_ID;
+use super::datagram::{DATAGRAM_ACK_FLAG, DATAGRAM_NAK_FLAG, DATAGRAM_VALID_FLAG};
+use super::unconnected_ping::UNCONNECTED_PING_ID;
+use super::unconnected_pong::UNCONNECTED_PONG_ID;
+
+// Packet constants
+pub const UNCONNECTED_PING_OPEN_CONNECTIONS_ID: u8 = 0x02;
+pub const OPEN_CONNECTION_REQUEST_1_ID: u8 = 0x05;
+pub const OPEN_CONNECTION_REPLY_1_ID: u8 = 0x06;
+pub const OPEN_CONNECTION_REQUEST_2_ID: u8 = 0x07;
+pub const OPEN_CONNECTION_REPLY_2_ID: u8 = 0x08;
+pub const INCOMPATIBLE_PROTOCOL_VERSION_ID: u8 = 0x19;
+
+/// RakNet messages sent outside of a connection, identified by their first byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum OfflineMessageId {
+    UnconnectedPing = UNCONNECTED_PING_ID,
+    UnconnectedPingOpenConnections = UNCONNECTED_PING_OPEN_CONNECTIONS_ID,
+    OpenConnectionRequest1 = OPEN_CONNECTION_REQUEST_1_ID,
+    OpenConnectionReply1 = OPEN_CONNECTION_REPLY_1_ID,
+    OpenConnectionRequest2 = OPEN_CONNECTION_REQUEST_2_ID,
+    OpenConnectionReply2 = OPEN_CONNECTION_REPLY_2_ID,
+    IncompatibleProtocolVersion = INCOMPATIBLE_PROTOCOL_VERSION_ID,
+    UnconnectedPong = UNCONNECTED_PONG_ID,
+    AdvertiseSystem = ADVERTISE_SYSTEM_ID,
+}
+
+impl OfflineMessageId {
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            UNCONNECTED_PING_ID => Some(Self::UnconnectedPing),
+            UNCONNECTED_PING_OPEN_CONNECTIONS_ID => Some(Self::UnconnectedPingOpenConnections),
+            OPEN_CONNECTION_REQUEST_1_ID => Some(Self::OpenConnectionRequest1),
+            OPEN_CONNECTION_REPLY_1_ID => Some(Self::OpenConnectionReply1),
+            OPEN_CONNECTION_REQUEST_2_ID => Some(Self::OpenConnectionRequest2),
+            OPEN_CONNECTION_REPLY_2_ID => Some(Self::OpenConnectionReply2),
+            INCOMPATIBLE_PROTOCOL_VERSION_ID => Some(Self::IncompatibleProtocolVersion),
+            UNCONNECTED_PONG_ID => Some(Self::UnconnectedPong),
+            ADVERTISE_SYSTEM_ID => Some(Self::AdvertiseSystem),
+            _ => None,
+        }
+    }
+
+    /// True for both unconnected ping variants
+    pub fn is_ping(self) -> bool {
+        matches!(
+            self,
+            Self::UnconnectedPing | Self::UnconnectedPingOpenConnections
+        )
+    }
+
+    /// True for packets that carry a pong payload the proxy rewrites
+    pub fn is_pong(self) -> bool {
+        matches!(self, Self::UnconnectedPong | Self::AdvertiseSystem)
+    }
+}
+
+/// What a raw UDP payload is, judged from its first byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketKind {
+    /// A zero-length datagram
+    Empty,
+    Offline(OfflineMessageId),
+    FrameSet,
+    Ack,
+    Nak,
+    /// An offline message ID this crate doesn't know about
+    Unknown(u8),
+}
+
+impl PacketKind {
+    /// The packet's first byte, if there is one
+    pub fn id(self) -> Option<u8> {
+        match self {
+            PacketKind::Empty => None,
+            PacketKind::Offline(id) => Some(id.id()),
+            PacketKind::FrameSet => Some(DATAGRAM_VALID_FLAG),
+            PacketKind::Ack => Some(DATAGRAM_VALID_FLAG | DATAGRAM_ACK_FLAG),
+            PacketKind::Nak => Some(DATAGRAM_VALID_FLAG | DATAGRAM_NAK_FLAG),
+            PacketKind::Unknown(id) => Some(id),
+        }
+    }
+}
+
+/// Classifies a packet without parsing it
+pub fn classify(data: &Bytes) -> PacketKind {
+    let Some(&first) = data.first() else {
+        return PacketKind::Empty;
+    };
+
+    if first & DATAGRAM_VALID_FLAG != 0 {
+        if first & DATAGRAM_ACK_FLAG != 0 {
+            PacketKind::Ack
+        } else if first & DATAGRAM_NAK_FLAG != 0 {
+            PacketKind::Nak
+        } else {
+            PacketKind::FrameSet
+        }
+    } else {
+        OfflineMessageId::from_id(first)
+            .map(PacketKind::Offline)
+            .unwrap_or(PacketKind::Unknown(first))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::unconnected_ping::UnconnectedPing;
+    use crate::proto::unconnected_pong::UnconnectedPong;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(&Bytes::new()), PacketKind::Empty);
+        assert_eq!(
+            classify(&UnconnectedPing::new([0; 8], [0; 8]).build()),
+            PacketKind::Offline(OfflineMessageId::UnconnectedPing)
+        );
+        assert_eq!(
+            classify(&UnconnectedPong::new().build().unwrap()),
+            PacketKind::Offline(OfflineMessageId::UnconnectedPong)
+        );
+        assert_eq!(
+            classify(&Bytes::from_static(&[0x84, 0x00])),
+            PacketKind::FrameSet
+        );
+        assert_eq!(classify(&Bytes::from_static(&[0xc0])), PacketKind::Ack);
+        assert_eq!(classify(&Bytes::from_static(&[0xa0])), PacketKind::Nak);
+        assert_eq!(
+            classify(&Bytes::from_static(&[0x42])),
+            PacketKind::Unknown(0x42)
+        );
+    }
+
+    #[test]
+    fn test_offline_message_id_round_trip() {
+        for id in 0..=u8::MAX {
+            if let Some(message) = OfflineMessageId::from_id(id) {
+                assert_eq!(message.id(), id);
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use super::guid::ServerGuid;
+use super::motd;
+use super::unconnected_pong::PongData;
+use super::ProtoError;
+
+pub use super::motd::MAX_MOTD_LEN;
+
+/// Fluent, validating constructor for `PongData`
+///
+/// ```ignore
+/// let pong = PongData::builder()
+///     .motd("My Server")
+///     .players(3)
+///     .max_players(10)
+///     .port4(19132)
+///     .build()?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PongDataBuilder {
+    pong: PongData,
+}
+
+impl PongData {
+    /// Starts a builder from the default (offline) pong
+    pub fn builder() -> PongDataBuilder {
+        PongDataBuilder::default()
+    }
+}
+
+impl From<PongData> for PongDataBuilder {
+    fn from(pong: PongData) -> Self {
+        Self { pong }
+    }
+}
+
+impl PongDataBuilder {
+    pub fn edition(mut self, edition: impl Into<String>) -> Self {
+        self.pong.edition = edition.into();
+        self
+    }
+
+    pub fn motd(mut self, motd: impl Into<String>) -> Self {
+        self.pong.motd = motd.into();
+        self
+    }
+
+    pub fn sub_motd(mut self, sub_motd: impl Into<String>) -> Self {
+        self.pong.sub_motd = sub_motd.into();
+        self
+    }
+
+    pub fn protocol_version(mut self, protocol_version: u32) -> Self {
+        self.pong.protocol_version = protocol_version.to_string();
+        self
+    }
+
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.pong.version = version.into();
+        self
+    }
+
+    pub fn players(mut self, players: u32) -> Self {
+        self.pong.players = players.to_string();
+        self
+    }
+
+    pub fn max_players(mut self, max_players: u32) -> Self {
+        self.pong.max_players = max_players.to_string();
+        self
+    }
+
+    pub fn server_id(mut self, server_id: impl Into<ServerGuid>) -> Self {
+        self.pong.set_server_guid(server_id.into());
+        self
+    }
+
+    pub fn game_mode(mut self, game_mode: impl Into<String>, game_mode_numeric: u8) -> Self {
+        self.pong.game_mode = game_mode.into();
+        self.pong.game_mode_numeric = game_mode_numeric.to_string();
+        self
+    }
+
+    pub fn port4(mut self, port: u16) -> Self {
+        self.pong.port4 = port.to_string();
+        self
+    }
+
+    pub fn port6(mut self, port: u16) -> Self {
+        self.pong.port6 = port.to_string();
+        self
+    }
+
+    /// Validates every field and returns the pong
+    pub fn build(self) -> Result<PongData, ProtoError> {
+        let pong = self.pong;
+
+        for (field, value) in [("motd", &pong.motd), ("sub_motd", &pong.sub_motd)] {
+            motd::validate(value).map_err(|e| match e {
+                ProtoError::InvalidMotd { reason } => invalid(field, reason),
+                other => other,
+            })?;
+        }
+
+        for (field, value) in [
+            ("edition", &pong.edition),
+            ("version", &pong.version),
+            ("game_mode", &pong.game_mode),
+        ] {
+            // A semicolon would shift every following field when encoded
+            if value.contains(';') {
+                return Err(invalid(field, "must not contain ';'"));
+            }
+        }
+
+        if pong.edition.is_empty() {
+            return Err(invalid("edition", "must not be empty"));
+        }
+
+        parse_numeric::<u32>("protocol_version", &pong.protocol_version)?;
+        parse_numeric::<u32>("players", &pong.players)?;
+        parse_numeric::<u32>("max_players", &pong.max_players)?;
+        parse_numeric::<ServerGuid>("server_id", &pong.server_id)?;
+        parse_numeric::<u8>("game_mode_numeric", &pong.game_mode_numeric)?;
+
+        for (field, value) in [("port4", &pong.port4), ("port6", &pong.port6)] {
+            if parse_numeric::<u16>(field, value)? == 0 {
+                return Err(invalid(field, "must be between 1 and 65535"));
+            }
+        }
+
+        if pong.encoded_len() > u16::MAX as usize {
+            return Err(invalid("pong", "is too long to encode"));
+        }
+
+        Ok(pong)
+    }
+}
+
+fn parse_numeric<T: std::str::FromStr>(field: &'static str, value: &str) -> Result<T, ProtoError> {
+    value
+        .parse::<T>()
+        .map_err(|_| invalid(field, "must be a number in range"))
+}
+
+fn invalid(field: &'static str, reason: &'static str) -> ProtoError {
+    ProtoError::InvalidPongData { field, reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_produces_pong() {
+        let pong = PongData::builder()
+            .motd("My Server")
+            .sub_motd("world")
+            .protocol_version(800)
+            .version("1.21.83")
+            .players(3)
+            .max_players(10)
+            .server_id(11675972934497731543)
+            .game_mode("Survival", 0)
+            .port4(19132)
+            .port6(19133)
+            .build()
+            .expect("Failed to build pong");
+
+        let pong_string: String = pong.into();
+        assert_eq!(
+            pong_string,
+            "MCPE;My Server;800;1.21.83;3;10;11675972934497731543;world;Survival;0;19132;19133;"
+        );
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_fields() {
+        let err = PongData::builder().motd("a;b").build().unwrap_err();
+        assert_eq!(
+            err,
+            ProtoError::InvalidPongData {
+                field: "motd",
+                reason: "must not contain ';'"
+            }
+        );
+
+        let err = PongData::builder().port4(0).build().unwrap_err();
+        assert!(matches!(
+            err,
+            ProtoError::InvalidPongData { field: "port4", .. }
+        ));
+
+        let err = PongData::builder()
+            .motd("x".repeat(MAX_MOTD_LEN + 1))
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ProtoError::InvalidPongData { field: "motd", .. }
+        ));
+
+        // Format codes don't count towards the displayed length
+        let colored = format!("§a{}", "x".repeat(MAX_MOTD_LEN));
+        assert!(PongData::builder().motd(colored).build().is_ok());
+
+        let from_server = PongData {
+            players: "lots".to_string(),
+            ..PongData::default()
+        };
+        let err = PongDataBuilder::from(from_server).build().unwrap_err();
+        assert!(matches!(
+            err,
+            ProtoError::InvalidPongData {
+                field: "players",
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,361 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use super::ProtoError;
+
+// Packet constants
+pub const QUERY_MAGIC: [u8; 2] = [0xfe, 0xfd];
+pub const QUERY_HANDSHAKE_TYPE: u8 = 0x09;
+pub const QUERY_STAT_TYPE: u8 = 0x00;
+
+/// Servers only echo the low nibble of each session ID byte
+pub const SESSION_ID_MASK: i32 = 0x0f0f0f0f;
+
+// Padding that precedes the key/value section of a full stat response
+const FULL_STAT_KV_PADDING: [u8; 11] = *b"splitnum\x00\x80\x00";
+
+// Padding that precedes the player list of a full stat response
+const FULL_STAT_PLAYERS_PADDING: [u8; 10] = *b"\x01player_\x00\x00";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueryHandshakeRequest {
+    pub session_id: i32,
+}
+
+impl QueryHandshakeRequest {
+    pub fn new(session_id: i32) -> Self {
+        Self {
+            session_id: session_id & SESSION_ID_MASK,
+        }
+    }
+
+    /// Serializes the handshake request
+    pub fn build(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    /// Serializes the handshake request into a caller-provided buffer
+    pub fn encode_into(&self, buf: &mut BytesMut) {
+        buf.reserve(7);
+        buf.put_slice(&QUERY_MAGIC);
+        buf.put_u8(QUERY_HANDSHAKE_TYPE);
+        buf.put_i32(self.session_id);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueryHandshakeResponse {
+    pub session_id: i32,
+    pub challenge_token: i32,
+}
+
+impl QueryHandshakeResponse {
+    /// Deserializes a handshake response, whose token is a null-terminated decimal string
+    pub fn from_bytes(mut data: Bytes) -> Result<Self, ProtoError> {
+        let session_id = read_header(&mut data, QUERY_HANDSHAKE_TYPE)?;
+        let token = read_cstring(&mut data, QUERY_HANDSHAKE_TYPE, "challenge_token")?;
+        let challenge_token = token
+            .trim()
+            .parse::<i64>()
+            .map_err(|_| ProtoError::InvalidField {
+                packet_id: QUERY_HANDSHAKE_TYPE,
+                field: "challenge_token",
+            })? as i32;
+
+        Ok(Self {
+            session_id,
+            challenge_token,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueryStatRequest {
+    pub session_id: i32,
+    pub challenge_token: i32,
+    pub full: bool,
+}
+
+impl QueryStatRequest {
+    /// Serializes the stat request. Full stat is requested by appending 4 padding bytes.
+    pub fn build(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    /// Serializes the stat request into a caller-provided buffer
+    pub fn encode_into(&self, buf: &mut BytesMut) {
+        buf.reserve(15);
+        buf.put_slice(&QUERY_MAGIC);
+        buf.put_u8(QUERY_STAT_TYPE);
+        buf.put_i32(self.session_id);
+        buf.put_i32(self.challenge_token);
+        if self.full {
+            buf.put_slice(&[0; 4]);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BasicStat {
+    pub session_id: i32,
+    pub motd: String,
+    pub game_type: String,
+    pub map: String,
+    pub num_players: String,
+    pub max_players: String,
+    pub host_port: u16,
+    pub host_ip: String,
+}
+
+impl BasicStat {
+    /// Deserializes a basic stat response
+    pub fn from_bytes(mut data: Bytes) -> Result<Self, ProtoError> {
+        let session_id = read_header(&mut data, QUERY_STAT_TYPE)?;
+        let motd = read_cstring(&mut data, QUERY_STAT_TYPE, "motd")?;
+        let game_type = read_cstring(&mut data, QUERY_STAT_TYPE, "game_type")?;
+        let map = read_cstring(&mut data, QUERY_STAT_TYPE, "map")?;
+        let num_players = read_cstring(&mut data, QUERY_STAT_TYPE, "num_players")?;
+        let max_players = read_cstring(&mut data, QUERY_STAT_TYPE, "max_players")?;
+
+        // The port is the one little-endian field in the protocol
+        ensure_remaining(&data, QUERY_STAT_TYPE, "host_port", 2)?;
+        let host_port = data.get_u16_le();
+        let host_ip = read_cstring(&mut data, QUERY_STAT_TYPE, "host_ip")?;
+
+        Ok(Self {
+            session_id,
+            motd,
+            game_type,
+            map,
+            num_players,
+            max_players,
+            host_port,
+            host_ip,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FullStat {
+    pub session_id: i32,
+    /// Key/value section in server order (hostname, gametype, version, plugins, map, ...)
+    pub values: Vec<(String, String)>,
+    pub players: Vec<String>,
+}
+
+impl FullStat {
+    /// Deserializes a full stat response
+    pub fn from_bytes(mut data: Bytes) -> Result<Self, ProtoError> {
+        let session_id = read_header(&mut data, QUERY_STAT_TYPE)?;
+        skip_padding(&mut data, "kv_padding", FULL_STAT_KV_PADDING.len())?;
+
+        let mut values = Vec::new();
+        loop {
+            let key = read_cstring(&mut data, QUERY_STAT_TYPE, "key")?;
+            if key.is_empty() {
+                break;
+            }
+            let value = read_cstring(&mut data, QUERY_STAT_TYPE, "value")?;
+            values.push((key, value));
+        }
+
+        skip_padding(
+            &mut data,
+            "players_padding",
+            FULL_STAT_PLAYERS_PADDING.len(),
+        )?;
+
+        let mut players = Vec::new();
+        while data.has_remaining() {
+            let name = read_cstring(&mut data, QUERY_STAT_TYPE, "player")?;
+            if name.is_empty() {
+                break;
+            }
+            players.push(name);
+        }
+
+        Ok(Self {
+            session_id,
+            values,
+            players,
+        })
+    }
+
+    /// Looks up a value from the key/value section
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Splits the `plugins` value ("ServerMod: Plugin v1; Other v2") into the
+    /// server software name and its plugin list
+    pub fn plugins(&self) -> (String, Vec<String>) {
+        let raw = self.get("plugins").unwrap_or_default();
+        let (server_mod, list) = match raw.split_once(':') {
+            Some((server_mod, list)) => (server_mod.trim(), list),
+            None => (raw.trim(), ""),
+        };
+
+        let plugins = list
+            .split(';')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        (server_mod.to_string(), plugins)
+    }
+}
+
+fn read_header(data: &mut Bytes, packet_type: u8) -> Result<i32, ProtoError> {
+    if data.len() < 5 {
+        return Err(ProtoError::TooShort {
+            packet_id: packet_type,
+            expected: 5,
+            actual: data.len(),
+        });
+    }
+
+    let actual = data.get_u8();
+    if actual != packet_type {
+        return Err(ProtoError::InvalidPacketId {
+            expected: packet_type,
+            actual,
+        });
+    }
+
+    Ok(data.get_i32())
+}
+
+fn read_cstring(
+    data: &mut Bytes,
+    packet_type: u8,
+    field: &'static str,
+) -> Result<String, ProtoError> {
+    let end = data
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or(ProtoError::TruncatedField {
+            packet_id: packet_type,
+            field,
+            expected: data.len() + 1,
+            actual: data.len(),
+        })?;
+
+    let value = data.split_to(end);
+    data.advance(1);
+
+    // Servers send whatever encoding their config file used
+    Ok(String::from_utf8_lossy(&value).into_owned())
+}
+
+fn skip_padding(data: &mut Bytes, field: &'static str, len: usize) -> Result<(), ProtoError> {
+    ensure_remaining(data, QUERY_STAT_TYPE, field, len)?;
+    data.advance(len);
+    Ok(())
+}
+
+fn ensure_remaining(
+    data: &Bytes,
+    packet_type: u8,
+    field: &'static str,
+    expected: usize,
+) -> Result<(), ProtoError> {
+    if data.remaining() < expected {
+        return Err(ProtoError::TruncatedField {
+            packet_id: packet_type,
+            field,
+            expected,
+            actual: data.remaining(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handshake_round_trip() {
+        let request = QueryHandshakeRequest::new(0x7fff_ffff);
+        assert_eq!(
+            request.build().as_ref(),
+            &[0xfe, 0xfd, 0x09, 0x0f, 0x0f, 0x0f, 0x0f]
+        );
+
+        let response = QueryHandshakeResponse::from_bytes(Bytes::from_static(
+            b"\x09\x0f\x0f\x0f\x0f9513307\x00",
+        ))
+        .expect("Failed to parse handshake");
+        assert_eq!(response.session_id, 0x0f0f0f0f);
+        assert_eq!(response.challenge_token, 9513307);
+
+        let stat = QueryStatRequest {
+            session_id: response.session_id,
+            challenge_token: response.challenge_token,
+            full: true,
+        };
+        assert_eq!(stat.build().len(), 15);
+    }
+
+    #[test]
+    fn test_basic_stat() {
+        let data = Bytes::from_static(
+            b"\x00\x00\x00\x00\x01A Server\x00SMP\x00world\x002\x0020\x00\xdd\x63127.0.0.1\x00",
+        );
+
+        let stat = BasicStat::from_bytes(data).expect("Failed to parse basic stat");
+        assert_eq!(stat.session_id, 1);
+        assert_eq!(stat.motd, "A Server");
+        assert_eq!(stat.game_type, "SMP");
+        assert_eq!(stat.map, "world");
+        assert_eq!(stat.num_players, "2");
+        assert_eq!(stat.max_players, "20");
+        assert_eq!(stat.host_port, 25565);
+        assert_eq!(stat.host_ip, "127.0.0.1");
+    }
+
+    #[test]
+    fn test_full_stat() {
+        let mut data = BytesMut::new();
+        data.put_u8(QUERY_STAT_TYPE);
+        data.put_i32(1);
+        data.put_slice(&FULL_STAT_KV_PADDING);
+        data.put_slice(b"hostname\x00A Server\x00");
+        data.put_slice(b"plugins\x00PocketMine-MP 5.0: WorldEdit 1.0; Essentials 2.1\x00");
+        data.put_slice(b"map\x00world\x00");
+        data.put_slice(b"\x00");
+        data.put_slice(&FULL_STAT_PLAYERS_PADDING);
+        data.put_slice(b"alice\x00bob\x00\x00");
+
+        let stat = FullStat::from_bytes(data.freeze()).expect("Failed to parse full stat");
+        assert_eq!(stat.get("hostname"), Some("A Server"));
+        assert_eq!(stat.get("map"), Some("world"));
+        assert_eq!(stat.players, vec!["alice", "bob"]);
+
+        let (server_mod, plugins) = stat.plugins();
+        assert_eq!(server_mod, "PocketMine-MP 5.0");
+        assert_eq!(plugins, vec!["WorldEdit 1.0", "Essentials 2.1"]);
+    }
+
+    #[test]
+    fn test_truncated_basic_stat() {
+        let err = BasicStat::from_bytes(Bytes::from_static(b"\x00\x00\x00\x00\x01motd"))
+            .expect_err("Truncated stat should fail");
+        assert!(matches!(
+            err,
+            ProtoError::TruncatedField { field: "motd", .. }
+        ));
+    }
+}
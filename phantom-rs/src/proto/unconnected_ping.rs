@@ -1,14 +1,18 @@
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 
+use super::{MagicMode, ProtoError};
+
 // Packet constants
 pub const UNCONNECTED_PING_ID: u8 = 0x01;
 
+/// Packet ID (1) + ping time (8) + magic (16) + client ID (8)
+pub const UNCONNECTED_PING_LEN: usize = 33;
+
 // Magic bytes used in the protocol
-pub const MAGIC: [u8; 16] = [
-    0x00, 0xff, 0xff, 0x00, 0xfe, 0xfe, 0xfe, 0xfe, 0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56, 0x78,
-];
+pub use super::MAGIC;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UnconnectedPing {
     pub ping_time: [u8; 8],
     pub magic: [u8; 16],
@@ -28,6 +32,13 @@ impl UnconnectedPing {
     /// Serializes the UnconnectedPing into bytes for the 0x01 packet
     pub fn build(&self) -> Bytes {
         let mut buf = BytesMut::new();
+        self.encode_into(&mut buf);
+        buf.freeze()
+    }
+
+    /// Serializes the UnconnectedPing into a caller-provided buffer
+    pub fn encode_into(&self, buf: &mut BytesMut) {
+        buf.reserve(UNCONNECTED_PING_LEN);
 
         // Packet ID
         buf.put_u8(UNCONNECTED_PING_ID);
@@ -40,21 +51,36 @@ impl UnconnectedPing {
 
         // Client ID (8 bytes)
         buf.put_slice(&self.client_id);
+    }
 
-        buf.freeze()
+    /// Returns true if the magic bytes match `MAGIC`
+    pub fn has_valid_magic(&self) -> bool {
+        self.magic == MAGIC
+    }
+
+    /// Deserializes an UnconnectedPing from bytes, accepting any magic bytes
+    pub fn from_bytes(data: Bytes) -> Result<Self, ProtoError> {
+        Self::from_bytes_with_mode(data, MagicMode::Lenient)
     }
 
-    /// Deserializes an UnconnectedPing from bytes
-    pub fn from_bytes(mut data: Bytes) -> Result<Self, &'static str> {
-        if data.len() < 25 {
-            // Minimum: 1 + 8 + 16 = 25 bytes
-            return Err("Data too short for UnconnectedPing packet");
+    /// Deserializes an UnconnectedPing from bytes, validating the magic bytes per `mode`
+    pub fn from_bytes_with_mode(mut data: Bytes, mode: MagicMode) -> Result<Self, ProtoError> {
+        if data.len() < UNCONNECTED_PING_LEN {
+            // Minimum: 1 + 8 + 16 + 8 = 33 bytes
+            return Err(ProtoError::TooShort {
+                packet_id: UNCONNECTED_PING_ID,
+                expected: UNCONNECTED_PING_LEN,
+                actual: data.len(),
+            });
         }
 
         // Check packet ID
         let packet_id = data.get_u8();
         if packet_id != UNCONNECTED_PING_ID {
-            return Err("Invalid packet ID for UnconnectedPing");
+            return Err(ProtoError::InvalidPacketId {
+                expected: UNCONNECTED_PING_ID,
+                actual: packet_id,
+            });
         }
 
         // Read ping time (8 bytes)
@@ -64,6 +90,7 @@ impl UnconnectedPing {
         // Read magic (16 bytes)
         let mut magic = [0u8; 16];
         data.copy_to_slice(&mut magic);
+        mode.check(UNCONNECTED_PING_ID, &magic)?;
 
         // Read client ID (8 bytes)
         let mut client_id = [0u8; 8];
@@ -92,7 +119,8 @@ mod tests {
         // Test data from a real packet capture
         let test_bytes = [
             0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x99, 0xa6, 0x00, 0xff, 0xff, 0x00, 0xfe,
-            0xfe, 0xfe, 0xfe, 0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56, 0x78,
+            0xfe, 0xfe, 0xfe, 0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56, 0x78, 0x5e, 0x2b, 0x1a,
+            0x6c, 0x83, 0x0d, 0x47, 0x91,
         ];
 
         let bytes = Bytes::from(test_bytes.to_vec());
@@ -104,6 +132,30 @@ mod tests {
             [0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x99, 0xa6]
         );
         assert_eq!(ping.magic, MAGIC);
+        assert_eq!(
+            ping.client_id,
+            [0x5e, 0x2b, 0x1a, 0x6c, 0x83, 0x0d, 0x47, 0x91]
+        );
+    }
+
+    #[test]
+    fn test_unconnected_ping_truncated_never_panics() {
+        let bytes = UnconnectedPing::default().build();
+
+        // 25-32 byte packets used to pass the length check and then panic
+        // reading the client ID
+        for len in 0..UNCONNECTED_PING_LEN {
+            let err = UnconnectedPing::from_bytes(bytes.slice(..len))
+                .expect_err("Truncated ping should fail");
+            assert_eq!(
+                err,
+                ProtoError::TooShort {
+                    packet_id: UNCONNECTED_PING_ID,
+                    expected: UNCONNECTED_PING_LEN,
+                    actual: len,
+                }
+            );
+        }
     }
 
     #[test]
@@ -123,4 +175,49 @@ mod tests {
         assert_eq!(ping.ping_time, parsed_ping.ping_time);
         assert_eq!(ping.magic, parsed_ping.magic);
     }
+
+    #[test]
+    fn test_unconnected_ping_errors() {
+        let err = UnconnectedPing::from_bytes(Bytes::from_static(&[UNCONNECTED_PING_ID, 0x00]))
+            .expect_err("Short packet should fail");
+        assert_eq!(
+            err,
+            ProtoError::TooShort {
+                packet_id: UNCONNECTED_PING_ID,
+                expected: UNCONNECTED_PING_LEN,
+                actual: 2,
+            }
+        );
+
+        let mut bytes = UnconnectedPing::default().build().to_vec();
+        bytes[0] = 0x1c;
+        let err =
+            UnconnectedPing::from_bytes(Bytes::from(bytes)).expect_err("Wrong ID should fail");
+        assert_eq!(
+            err,
+            ProtoError::InvalidPacketId {
+                expected: UNCONNECTED_PING_ID,
+                actual: 0x1c,
+            }
+        );
+    }
+
+    #[test]
+    fn test_unconnected_ping_magic_modes() {
+        let mut ping = UnconnectedPing::default();
+        ping.magic[0] = 0xaa;
+        let bytes = ping.build();
+
+        let lenient = UnconnectedPing::from_bytes(bytes.clone()).expect("Lenient should accept");
+        assert!(!lenient.has_valid_magic());
+
+        let err = UnconnectedPing::from_bytes_with_mode(bytes, MagicMode::Strict)
+            .expect_err("Strict should reject");
+        assert_eq!(
+            err,
+            ProtoError::InvalidMagic {
+                packet_id: UNCONNECTED_PING_ID
+            }
+        );
+    }
 }
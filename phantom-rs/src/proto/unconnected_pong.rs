@@ -1,6 +1,10 @@
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 
+use super::guid::ServerGuid;
+use super::{MagicMode, ProtoError};
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PongData {
     pub edition: String,
     pub motd: String,
@@ -37,12 +41,12 @@ impl Default for PongData {
 
 impl PongData {
     /// Creates a PongData from a semicolon-separated string
-    pub fn from_string(data: &str) -> Result<Self, &'static str> {
+    pub fn from_string(data: &str) -> Result<Self, ProtoError> {
         let parts: Vec<&str> = data.split(';').collect();
 
         // We need at least 10 fields, but can handle more or fewer gracefully
         if parts.is_empty() {
-            return Err("Empty pong data string");
+            return Err(ProtoError::EmptyPongData);
         }
 
         let mut pong = Self::default();
@@ -89,9 +93,18 @@ impl PongData {
     }
 }
 
-impl Into<String> for PongData {
-    fn into(self) -> String {
-        let fields = vec![
+impl PongData {
+    /// Parses `server_id` as a GUID, if the server sent a numeric one
+    pub fn server_guid(&self) -> Option<ServerGuid> {
+        self.server_id.parse().ok()
+    }
+
+    pub fn set_server_guid(&mut self, guid: ServerGuid) {
+        self.server_id = guid.to_string();
+    }
+
+    fn fields(&self) -> [&str; 12] {
+        [
             self.edition.as_str(),
             self.motd.as_str(),
             self.protocol_version.as_str(),
@@ -104,9 +117,27 @@ impl Into<String> for PongData {
             self.game_mode_numeric.as_str(),
             self.port4.as_str(),
             self.port6.as_str(),
-        ];
+        ]
+    }
+
+    /// Length in bytes of the semicolon-separated encoding
+    pub fn encoded_len(&self) -> usize {
+        self.fields().iter().map(|field| field.len() + 1).sum()
+    }
+
+    /// Writes the semicolon-separated encoding into `buf`
+    pub fn encode_into(&self, buf: &mut BytesMut) {
+        buf.reserve(self.encoded_len());
+        for field in self.fields() {
+            buf.put_slice(field.as_bytes());
+            buf.put_u8(b';');
+        }
+    }
+}
 
-        let joined = fields.join(";");
+impl Into<String> for PongData {
+    fn into(self) -> String {
+        let joined = self.fields().join(";");
         format!("{};", joined)
     }
 }
@@ -114,15 +145,17 @@ impl Into<String> for PongData {
 // Packet constants
 pub const UNCONNECTED_PONG_ID: u8 = 0x1c;
 
+/// Packet ID (1) + ping time (8) + GUID (8) + magic (16) + payload length (2)
+pub const UNCONNECTED_PONG_HEADER_SIZE: usize = 35;
+
 // Magic bytes used in the protocol
-pub const MAGIC: [u8; 16] = [
-    0x00, 0xff, 0xff, 0x00, 0xfe, 0xfe, 0xfe, 0xfe, 0xfd, 0xfd, 0xfd, 0xfd, 0x12, 0x34, 0x56, 0x78,
-];
+pub use super::MAGIC;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UnconnectedPong {
     pub ping_time: [u8; 8],
-    pub server_guid: [u8; 8],
+    pub server_guid: ServerGuid,
     pub magic: [u8; 16],
     pub pong: PongData,
 }
@@ -132,52 +165,123 @@ impl UnconnectedPong {
     pub fn new() -> Self {
         Self {
             ping_time: [0; 8],
-            server_guid: [0; 8],
+            server_guid: ServerGuid::default(),
             magic: MAGIC,
             pong: PongData::default(),
         }
     }
 
-    /// Serializes the UnconnectedPong into bytes for the 0x1c packet
-    pub fn build(&self) -> Bytes {
+    /// Sets the header GUID and the payload's `server_id` together so they stay in sync
+    pub fn set_server_guid(&mut self, guid: ServerGuid) {
+        self.server_guid = guid;
+        self.pong.set_server_guid(guid);
+    }
+
+    /// Serializes the UnconnectedPong into bytes for the 0x1c packet. Fails if
+    /// the payload is too long for its length field.
+    pub fn build(&self) -> Result<Bytes, ProtoError> {
         let mut buf = BytesMut::new();
+        self.encode_into(&mut buf)?;
+        Ok(buf.freeze())
+    }
+
+    /// Serializes the UnconnectedPong into a caller-provided buffer
+    pub fn encode_into(&self, buf: &mut BytesMut) -> Result<(), ProtoError> {
+        self.encode_with_id(UNCONNECTED_PONG_ID, buf)
+    }
+
+    /// Serializes the pong layout under a different packet ID (e.g. ADVERTISE_SYSTEM)
+    pub(crate) fn encode_with_id(
+        &self,
+        packet_id: u8,
+        buf: &mut BytesMut,
+    ) -> Result<(), ProtoError> {
+        let pong_len = self.pong.encoded_len();
+        let encoded_len = u16::try_from(pong_len).map_err(|_| ProtoError::InvalidPongData {
+            field: "pong",
+            reason: "is too long to encode",
+        })?;
+        buf.reserve(UNCONNECTED_PONG_HEADER_SIZE + pong_len);
 
         // Packet ID
-        buf.put_u8(UNCONNECTED_PONG_ID);
+        buf.put_u8(packet_id);
 
         // Ping time (8 bytes)
         buf.put_slice(&self.ping_time);
 
         // ID (8 bytes)
-        buf.put_slice(&self.server_guid);
+        buf.put_slice(&self.server_guid.to_bytes());
 
         // Magic (16 bytes)
         buf.put_slice(&self.magic);
 
-        // Pong data
-        let pong_string: String = self.pong.clone().into();
-        let pong_bytes = pong_string.as_bytes();
-
         // Pong data length (2 bytes, big endian)
-        buf.put_u16(pong_bytes.len() as u16);
+        buf.put_u16(encoded_len);
 
         // Pong data
-        buf.put_slice(pong_bytes);
+        self.pong.encode_into(buf);
+        Ok(())
+    }
+
+    /// Returns true if the magic bytes match `MAGIC`
+    pub fn has_valid_magic(&self) -> bool {
+        self.magic == MAGIC
+    }
+
+    /// Deserializes an UnconnectedPong from bytes, accepting any magic bytes
+    pub fn from_bytes(data: Bytes) -> Result<Self, ProtoError> {
+        UnconnectedPongRef::from_bytes(data)?.parse()
+    }
+
+    /// Deserializes an UnconnectedPong from bytes, validating the magic bytes per `mode`
+    pub fn from_bytes_with_mode(data: Bytes, mode: MagicMode) -> Result<Self, ProtoError> {
+        UnconnectedPongRef::from_bytes_with_mode(data, mode)?.parse()
+    }
+}
 
-        buf.freeze()
+/// A decoded UnconnectedPong header whose pong payload still borrows from the
+/// original buffer, for callers that only need to inspect the packet
+#[derive(Debug, Clone)]
+pub struct UnconnectedPongRef {
+    pub packet_id: u8,
+    pub ping_time: [u8; 8],
+    pub server_guid: ServerGuid,
+    pub magic: [u8; 16],
+    payload: Bytes,
+}
+
+impl UnconnectedPongRef {
+    /// Decodes the fixed header and slices the pong payload without copying it
+    pub fn from_bytes(data: Bytes) -> Result<Self, ProtoError> {
+        Self::from_bytes_with_mode(data, MagicMode::Lenient)
+    }
+
+    /// Like `from_bytes`, validating the magic bytes per `mode`
+    pub fn from_bytes_with_mode(data: Bytes, mode: MagicMode) -> Result<Self, ProtoError> {
+        Self::decode(data, UNCONNECTED_PONG_ID, mode)
     }
 
-    /// Deserializes an UnconnectedPong from bytes
-    pub fn from_bytes(mut data: Bytes) -> Result<Self, &'static str> {
-        if data.len() < 35 {
-            // Minimum: 1 + 8 + 8 + 16 + 2 = 35 bytes
-            return Err("Data too short for UnconnectedPong packet");
+    /// Decodes any packet sharing the pong layout (e.g. ADVERTISE_SYSTEM)
+    pub(crate) fn decode(
+        mut data: Bytes,
+        expected_id: u8,
+        mode: MagicMode,
+    ) -> Result<Self, ProtoError> {
+        if data.len() < UNCONNECTED_PONG_HEADER_SIZE {
+            return Err(ProtoError::TooShort {
+                packet_id: expected_id,
+                expected: UNCONNECTED_PONG_HEADER_SIZE,
+                actual: data.len(),
+            });
         }
 
         // Check packet ID
         let packet_id = data.get_u8();
-        if packet_id != UNCONNECTED_PONG_ID {
-            return Err("Invalid packet ID for UnconnectedPong");
+        if packet_id != expected_id {
+            return Err(ProtoError::InvalidPacketId {
+                expected: expected_id,
+                actual: packet_id,
+            });
         }
 
         // Read ping time (8 bytes)
@@ -185,33 +289,70 @@ impl UnconnectedPong {
         data.copy_to_slice(&mut ping_time);
 
         // Read ID (8 bytes)
-        let mut server_guid = [0u8; 8];
-        data.copy_to_slice(&mut server_guid);
+        let server_guid = ServerGuid::from(data.get_u64());
 
         // Read magic (16 bytes)
         let mut magic = [0u8; 16];
         data.copy_to_slice(&mut magic);
+        mode.check(packet_id, &magic)?;
 
         // Read pong data length
         if data.remaining() < 2 {
-            return Err("Not enough data for pong length");
+            return Err(ProtoError::TruncatedField {
+                packet_id,
+                field: "pong_len",
+                expected: 2,
+                actual: data.remaining(),
+            });
         }
         let pong_len = data.get_u16() as usize;
 
-        // Read pong data
+        // Slice pong data, sharing the underlying buffer
         if data.remaining() < pong_len {
-            return Err("Not enough data for pong content");
+            return Err(ProtoError::TruncatedField {
+                packet_id,
+                field: "pong",
+                expected: pong_len,
+                actual: data.remaining(),
+            });
         }
-        let pong_bytes = data.split_to(pong_len);
-        let pong_string =
-            String::from_utf8(pong_bytes.to_vec()).map_err(|_| "Invalid UTF-8 in pong data")?;
-
-        let pong = PongData::from_string(&pong_string)?;
+        let payload = data.split_to(pong_len);
 
         Ok(Self {
+            packet_id,
             ping_time,
             server_guid,
             magic,
+            payload,
+        })
+    }
+
+    /// Returns true if the magic bytes match `MAGIC`
+    pub fn has_valid_magic(&self) -> bool {
+        self.magic == MAGIC
+    }
+
+    /// The raw semicolon-separated pong payload
+    pub fn payload(&self) -> &Bytes {
+        &self.payload
+    }
+
+    /// The pong payload as a string, borrowed from the packet buffer
+    pub fn payload_str(&self) -> Result<&str, ProtoError> {
+        std::str::from_utf8(&self.payload).map_err(|_| ProtoError::InvalidUtf8 {
+            packet_id: self.packet_id,
+            field: "pong",
+        })
+    }
+
+    /// Parses the payload into an owned UnconnectedPong
+    pub fn parse(&self) -> Result<UnconnectedPong, ProtoError> {
+        let pong = PongData::from_string(self.payload_str()?)?;
+
+        Ok(UnconnectedPong {
+            ping_time: self.ping_time,
+            server_guid: self.server_guid,
+            magic: self.magic,
             pong,
         })
     }
@@ -252,9 +393,10 @@ mod tests {
             [0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x99, 0xa6]
         );
         assert_eq!(
-            ping.server_guid,
+            ping.server_guid.to_bytes(),
             [0xa2, 0x09, 0x63, 0x85, 0x9f, 0xd0, 0x03, 0xd7]
         );
+        assert_eq!(ping.pong.server_guid(), Some(ping.server_guid));
         assert_eq!(ping.magic, MAGIC);
 
         // Verify pong data
@@ -277,7 +419,7 @@ mod tests {
         // Create a ping packet
         let mut ping = UnconnectedPong::new();
         ping.ping_time = [0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x99, 0xa6];
-        ping.server_guid = [0xa2, 0x09, 0x63, 0x85, 0x9f, 0xd0, 0x03, 0xd7];
+        ping.server_guid = ServerGuid::from([0xa2, 0x09, 0x63, 0x85, 0x9f, 0xd0, 0x03, 0xd7]);
 
         ping.pong.edition = "MCPE".to_string();
         ping.pong.motd = "Test Server".to_string();
@@ -287,7 +429,7 @@ mod tests {
         ping.pong.max_players = "20".to_string();
 
         // Serialize
-        let bytes = ping.build();
+        let bytes = ping.build().unwrap();
 
         // Deserialize
         let parsed_ping =
@@ -308,6 +450,111 @@ mod tests {
         assert_eq!(ping.pong.max_players, parsed_ping.pong.max_players);
     }
 
+    #[test]
+    fn test_unconnected_pong_ref_borrows_payload() {
+        let bytes = UnconnectedPong::new().build().unwrap();
+        let pong_ref = UnconnectedPongRef::from_bytes(bytes.clone()).expect("Failed to parse");
+
+        let expected: String = PongData::default().into();
+        assert_eq!(pong_ref.payload_str(), Ok(expected.as_str()));
+        assert_eq!(pong_ref.payload().as_ptr(), bytes[35..].as_ptr());
+    }
+
+    #[test]
+    fn test_unconnected_pong_truncated_never_panics() {
+        let bytes = UnconnectedPong::new().build().unwrap();
+        for len in 0..bytes.len() {
+            assert!(UnconnectedPong::from_bytes(bytes.slice(..len)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_unconnected_pong_truncated_content() {
+        let mut bytes = UnconnectedPong::new().build().unwrap().to_vec();
+        bytes.truncate(40);
+
+        let err = UnconnectedPong::from_bytes(Bytes::from(bytes))
+            .expect_err("Truncated pong should fail");
+        match err {
+            ProtoError::TruncatedField {
+                packet_id, field, ..
+            } => {
+                assert_eq!(packet_id, UNCONNECTED_PONG_ID);
+                assert_eq!(field, "pong");
+            }
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_unconnected_pong_serde_round_trip() {
+        let mut pong = UnconnectedPong::new();
+        pong.server_guid = ServerGuid(11675972934497731543);
+        pong.pong.motd = "Test Server".to_string();
+
+        let json = serde_json::to_string(&pong).expect("Failed to serialize pong");
+        let parsed: UnconnectedPong =
+            serde_json::from_str(&json).expect("Failed to deserialize pong");
+
+        assert_eq!(parsed.server_guid, pong.server_guid);
+        assert_eq!(parsed.magic, MAGIC);
+        assert_eq!(parsed.pong.motd, "Test Server");
+        assert_eq!(parsed.build().unwrap(), pong.build().unwrap());
+    }
+
+    #[test]
+    fn test_set_server_guid_keeps_payload_in_sync() {
+        let mut pong = UnconnectedPong::new();
+        pong.set_server_guid(ServerGuid(42));
+
+        let parsed = UnconnectedPong::from_bytes(pong.build().unwrap()).expect("Failed to parse");
+        assert_eq!(parsed.server_guid, ServerGuid(42));
+        assert_eq!(parsed.pong.server_id, "42");
+        assert_eq!(parsed.pong.server_guid(), Some(ServerGuid(42)));
+    }
+
+    #[test]
+    fn test_unconnected_pong_encode_into_appends() {
+        let pong = UnconnectedPong::new();
+
+        let mut buf = BytesMut::from(&b"prefix"[..]);
+        pong.encode_into(&mut buf).unwrap();
+
+        assert_eq!(&buf[..6], b"prefix");
+        assert_eq!(&buf[6..], &pong.build().unwrap()[..]);
+    }
+
+    #[test]
+    fn test_unconnected_pong_too_long_to_encode() {
+        let mut pong = UnconnectedPong::new();
+        pong.pong.motd = "a".repeat(u16::MAX as usize);
+
+        assert!(matches!(
+            pong.build(),
+            Err(ProtoError::InvalidPongData { field: "pong", .. })
+        ));
+    }
+
+    #[test]
+    fn test_unconnected_pong_magic_modes() {
+        let mut pong = UnconnectedPong::new();
+        pong.magic = [0; 16];
+        let bytes = pong.build().unwrap();
+
+        let lenient = UnconnectedPong::from_bytes(bytes.clone()).expect("Lenient should accept");
+        assert!(!lenient.has_valid_magic());
+
+        let err = UnconnectedPong::from_bytes_with_mode(bytes, MagicMode::Strict)
+            .expect_err("Strict should reject");
+        assert_eq!(
+            err,
+            ProtoError::InvalidMagic {
+                packet_id: UNCONNECTED_PONG_ID
+            }
+        );
+    }
+
     #[test]
     fn test_pong_data_from_string() {
         let pong_string = "MCPE;Dedicated Server;800;1.21.83;0;10;11675972934497731543;Bedrock level;Survival;1;19132;19133;0;";
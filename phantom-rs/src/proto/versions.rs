@@ -0,0 +1,154 @@
+/// Bedrock network protocol numbers and the first game release that used each,
+/// oldest first. Hotfix releases that kept the protocol number aren't listed.
+pub const PROTOCOL_VERSIONS: &[(u32, &str)] = &[
+    (407, "1.16.0"),
+    (408, "1.16.20"),
+    (419, "1.16.100"),
+    (422, "1.16.200"),
+    (428, "1.16.210"),
+    (431, "1.16.220"),
+    (440, "1.17.0"),
+    (448, "1.17.10"),
+    (465, "1.17.30"),
+    (471, "1.17.40"),
+    (475, "1.18.0"),
+    (486, "1.18.10"),
+    (503, "1.18.30"),
+    (527, "1.19.0"),
+    (534, "1.19.10"),
+    (544, "1.19.20"),
+    (545, "1.19.21"),
+    (554, "1.19.30"),
+    (557, "1.19.40"),
+    (560, "1.19.50"),
+    (567, "1.19.60"),
+    (568, "1.19.63"),
+    (575, "1.19.70"),
+    (582, "1.19.80"),
+    (589, "1.20.0"),
+    (594, "1.20.10"),
+    (618, "1.20.30"),
+    (622, "1.20.40"),
+    (630, "1.20.50"),
+    (649, "1.20.60"),
+    (662, "1.20.70"),
+    (671, "1.20.80"),
+    (685, "1.21.0"),
+    (686, "1.21.2"),
+    (712, "1.21.20"),
+    (729, "1.21.30"),
+    (748, "1.21.40"),
+    (766, "1.21.50"),
+    (776, "1.21.60"),
+    (786, "1.21.70"),
+    (800, "1.21.80"),
+    (818, "1.21.90"),
+    (819, "1.21.93"),
+    (827, "1.21.100"),
+    (844, "1.21.111"),
+];
+
+/// Newest protocol number in `PROTOCOL_VERSIONS`
+pub const LATEST_PROTOCOL: u32 = PROTOCOL_VERSIONS[PROTOCOL_VERSIONS.len() - 1].0;
+
+/// Whether a client can join a server, based on their protocol numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    Compatible,
+    /// The server runs a newer protocol; the client needs to update
+    ClientOutdated,
+    /// The server runs an older protocol than the client
+    ServerOutdated,
+}
+
+/// Returns the game version that introduced `protocol`, if it's a known number
+pub fn game_version(protocol: u32) -> Option<&'static str> {
+    PROTOCOL_VERSIONS
+        .iter()
+        .find(|(p, _)| *p == protocol)
+        .map(|(_, version)| *version)
+}
+
+/// Returns the protocol number for a game version. Patch releases that aren't in
+/// the table resolve to the newest protocol at or below them (e.g. 1.21.84 -> 800).
+pub fn protocol_version(game_version: &str) -> Option<u32> {
+    let target = parse_version(game_version)?;
+    PROTOCOL_VERSIONS
+        .iter()
+        .filter_map(|(protocol, version)| Some((*protocol, parse_version(version)?)))
+        .take_while(|(_, version)| *version <= target)
+        .last()
+        .map(|(protocol, _)| protocol)
+}
+
+/// Human-readable game version for a protocol number, falling back to the
+/// nearest known release for numbers missing from the table
+pub fn describe(protocol: u32) -> String {
+    if let Some(version) = game_version(protocol) {
+        return version.to_string();
+    }
+
+    match PROTOCOL_VERSIONS.iter().rev().find(|(p, _)| *p < protocol) {
+        Some((_, version)) if protocol > LATEST_PROTOCOL => format!("newer than {}", version),
+        Some((_, version)) => format!("{}+", version),
+        None => format!("protocol {}", protocol),
+    }
+}
+
+/// Bedrock only lets clients join servers with the exact same protocol number
+pub fn check_compatibility(client_protocol: u32, server_protocol: u32) -> Compatibility {
+    match client_protocol.cmp(&server_protocol) {
+        std::cmp::Ordering::Equal => Compatibility::Compatible,
+        std::cmp::Ordering::Less => Compatibility::ClientOutdated,
+        std::cmp::Ordering::Greater => Compatibility::ServerOutdated,
+    }
+}
+
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = version.trim().split('.').map(|part| part.parse::<u32>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next()?.ok()?;
+    let patch = match parts.next() {
+        Some(patch) => patch.ok()?,
+        None => 0,
+    };
+    Some((major, minor, patch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_is_sorted() {
+        assert!(PROTOCOL_VERSIONS.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(PROTOCOL_VERSIONS
+            .windows(2)
+            .all(|w| parse_version(w[0].1) < parse_version(w[1].1)));
+    }
+
+    #[test]
+    fn test_lookups() {
+        assert_eq!(game_version(800), Some("1.21.80"));
+        assert_eq!(game_version(801), None);
+        assert_eq!(protocol_version("1.21.80"), Some(800));
+        assert_eq!(protocol_version("1.21.84"), Some(800));
+        assert_eq!(protocol_version("1.15"), None);
+        assert_eq!(protocol_version("garbage"), None);
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe(800), "1.21.80");
+        assert_eq!(describe(801), "1.21.80+");
+        assert_eq!(describe(LATEST_PROTOCOL + 1), "newer than 1.21.111");
+        assert_eq!(describe(100), "protocol 100");
+    }
+
+    #[test]
+    fn test_compatibility() {
+        assert_eq!(check_compatibility(800, 800), Compatibility::Compatible);
+        assert_eq!(check_compatibility(786, 800), Compatibility::ClientOutdated);
+        assert_eq!(check_compatibility(818, 800), Compatibility::ServerOutdated);
+    }
+}
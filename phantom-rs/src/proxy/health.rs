@@ -0,0 +1,98 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::time::Duration;
+use tracing::{debug_span, Instrument};
+
+use crate::actor::RestartPolicy;
+use crate::api::{log_to, EventSink, LogSink, PhantomEvent};
+use crate::client::Client;
+use crate::task::SupervisedTask;
+
+use super::metrics::ProxyMetrics;
+use super::power::PowerMode;
+
+/// How often the server is pinged to report `UpstreamUp`/`UpstreamDown`,
+/// unless in low-power mode with no clients
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Pings the server in the background and reports when it comes up or goes
+/// down. The first result is always reported. Restarted, without giving up,
+/// if it can't create its ping client.
+pub(super) fn upstream_health_check(
+    server: SocketAddr,
+    events: EventSink,
+    log: LogSink,
+    metrics: Arc<ProxyMetrics>,
+    power: Arc<PowerMode>,
+) -> SupervisedTask {
+    let span = debug_span!("task", name = "upstream-health");
+    let policy = RestartPolicy {
+        max_restarts: None,
+        ..RestartPolicy::default()
+    };
+    SupervisedTask::spawn(
+        move |_| {
+            check_loop(
+                server,
+                events.clone(),
+                log.clone(),
+                metrics.clone(),
+                power.clone(),
+            )
+            .instrument(span.clone())
+        },
+        policy,
+    )
+}
+
+async fn check_loop(
+    server: SocketAddr,
+    events: EventSink,
+    log: LogSink,
+    metrics: Arc<ProxyMetrics>,
+    power: Arc<PowerMode>,
+) {
+    let client = match Client::new().await {
+        Ok(client) => client,
+        Err(e) => {
+            log_to!(
+                log,
+                Warn,
+                "[upstream-health] Unable to create ping client, retrying: {}",
+                e
+            );
+            return;
+        }
+    };
+
+    // None until the first ping completes
+    let mut was_up: Option<bool> = None;
+
+    loop {
+        let event = match client.ping(server.to_string()).await {
+            Ok(pong) if was_up != Some(true) => {
+                was_up = Some(true);
+                Some(PhantomEvent::UpstreamUp {
+                    server: server.to_string(),
+                    latency_ms: pong.latency_ms,
+                })
+            }
+            Err(e) if was_up != Some(false) => {
+                was_up = Some(false);
+                Some(PhantomEvent::UpstreamDown {
+                    server: server.to_string(),
+                    reason: e.to_string(),
+                })
+            }
+            _ => None,
+        };
+
+        if let Some(event) = event {
+            log_to!(log, Debug, "[upstream-health] {:?}", event);
+            events.emit(event);
+        }
+
+        let idle = metrics.active_clients() == 0;
+        power.wait(HEALTH_CHECK_INTERVAL, idle).await;
+    }
+}
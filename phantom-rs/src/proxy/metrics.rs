@@ -0,0 +1,132 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::api::{ClientSession, PhantomMetrics};
+
+/// Running totals for one instance, shared by the router and read loops.
+/// They carry on across restarts; only `active_clients` goes back to zero.
+#[derive(Debug, Default)]
+pub(crate) struct ProxyMetrics {
+    packets_from_clients: AtomicU64,
+    bytes_from_clients: AtomicU64,
+    packets_to_clients: AtomicU64,
+    bytes_to_clients: AtomicU64,
+    active_clients: AtomicU64,
+    lifetime_clients: AtomicU64,
+    pong_rewrites: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl ProxyMetrics {
+    /// A packet from a client was forwarded to the server
+    pub(crate) fn forwarded_to_server(&self, bytes: usize) {
+        self.packets_from_clients.fetch_add(1, Ordering::Relaxed);
+        self.bytes_from_clients
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// A packet from the server was forwarded to a client
+    pub(crate) fn forwarded_to_client(&self, bytes: usize) {
+        self.packets_to_clients.fetch_add(1, Ordering::Relaxed);
+        self.bytes_to_clients
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn client_connected(&self) {
+        self.active_clients.fetch_add(1, Ordering::Relaxed);
+        self.lifetime_clients.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn client_disconnected(&self) {
+        // Saturating, in case `clients_dropped` already zeroed it
+        let _ = self
+            .active_clients
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    }
+
+    pub(crate) fn active_clients(&self) -> u64 {
+        self.active_clients.load(Ordering::Relaxed)
+    }
+
+    /// The router stopped, taking every session with it
+    pub(crate) fn clients_dropped(&self) {
+        self.active_clients.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn pong_rewritten(&self) {
+        self.pong_rewrites.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A packet couldn't be forwarded
+    pub(crate) fn error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> PhantomMetrics {
+        PhantomMetrics {
+            packets_from_clients: self.packets_from_clients.load(Ordering::Relaxed),
+            bytes_from_clients: self.bytes_from_clients.load(Ordering::Relaxed),
+            packets_to_clients: self.packets_to_clients.load(Ordering::Relaxed),
+            bytes_to_clients: self.bytes_to_clients.load(Ordering::Relaxed),
+            active_clients: self.active_clients.load(Ordering::Relaxed),
+            lifetime_clients: self.lifetime_clients.load(Ordering::Relaxed),
+            pong_rewrites: self.pong_rewrites.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counters for one client session. The router counts what the client sends
+/// and the session's read loop what it receives.
+#[derive(Debug)]
+pub(crate) struct SessionStats {
+    connected_at: SystemTime,
+    started: Instant,
+    bytes_from_client: AtomicU64,
+    bytes_to_client: AtomicU64,
+    /// Milliseconds after `started`
+    last_activity_ms: AtomicU64,
+}
+
+impl SessionStats {
+    pub(crate) fn new() -> Self {
+        SessionStats {
+            connected_at: SystemTime::now(),
+            started: Instant::now(),
+            bytes_from_client: AtomicU64::new(0),
+            bytes_to_client: AtomicU64::new(0),
+            last_activity_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn client_sent(&self, bytes: usize) {
+        self.bytes_from_client
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub(crate) fn client_received(&self, bytes: usize) {
+        self.bytes_to_client
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    fn touch(&self) {
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        self.last_activity_ms.fetch_max(elapsed, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self, client_addr: SocketAddr, upstream_port: u16) -> ClientSession {
+        let last_activity_ms = self.last_activity_ms.load(Ordering::Relaxed);
+        ClientSession {
+            client_address: client_addr.ip().to_string(),
+            client_port: client_addr.port(),
+            upstream_port,
+            connected_at: self.connected_at,
+            last_activity: self.connected_at + Duration::from_millis(last_activity_ms),
+            bytes_from_client: self.bytes_from_client.load(Ordering::Relaxed),
+            bytes_to_client: self.bytes_to_client.load(Ordering::Relaxed),
+        }
+    }
+}
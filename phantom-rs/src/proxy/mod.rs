@@ -1,130 +1,659 @@
+mod health;
+mod metrics;
+mod ports;
+mod power;
 mod router;
 mod socket;
 
-use log::{debug, error, info};
-use socket::{read_cancellable, CancellablePacketReader};
-use std::net::{SocketAddr, ToSocketAddrs};
-use std::sync::atomic::{AtomicBool, Ordering};
+use parking_lot::{Mutex, RwLock};
+use socket::read_supervised;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
-use tokio::sync::Notify;
+use tokio::sync::{Mutex as AsyncMutex, Notify};
+use tracing::{debug_span, Instrument};
 
-use crate::actor::ActorRef;
-use crate::api::{PhantomError, PhantomOpts};
-use crate::task::TaskManager;
-use router::{create_router, Router, RouterMessage};
+use crate::actor::{ActorRef, ActorRegistry, RestartPolicy};
+use crate::api::{
+    log_to, AsyncPhantomEventListener, BoundAddresses, ClientSession, EventSink, InvalidOption,
+    IoErrorKind, LogLevel, LogSink, OptsUpdate, PhantomError, PhantomEvent, PhantomEventListener,
+    PhantomLogger, PhantomMetrics, PhantomOpts, PhantomRecordLogger, PhantomState, PhantomStatus,
+    BROADCAST_PORT,
+};
+use crate::proto::{motd, ProtoError};
+use crate::task::{ShutdownGroup, SupervisedTask, TaskConfig, TaskExit, TaskInfo, TaskManager};
+use health::upstream_health_check;
+use metrics::ProxyMetrics;
+use ports::PortClaim;
+use power::PowerMode;
+use router::{create_router, max_pong_payload, DrainingRouter, PongRewrite, Router, RouterMessage};
+
+/// How long shutdown waits for the router to forward packets it has queued
+const ROUTER_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+static NEXT_INSTANCE_ID: AtomicU64 = AtomicU64::new(1);
 
 #[derive(uniffi::Object)]
 pub struct ProxyInstance {
+    /// Tells instances in one process apart, e.g. in actor paths
+    id: u64,
     running: AtomicBool,
-    opts: PhantomOpts,
+    /// Held by `listen`, `shutdown` and `restart` so they don't interleave
+    lifecycle: AsyncMutex<()>,
+    /// Replaced by `update_opts`; read when the listeners start
+    opts: Mutex<PhantomOpts>,
     manager: TaskManager,
+    registry: ActorRegistry,
     notify_shutdown: Notify,
+    /// Set while the listeners are up
+    listening: Mutex<Option<Listening>>,
+    /// Held while listening on an explicit `bind_port`
+    port_claim: Mutex<Option<PortClaim>>,
+    /// Set by `suspend` until the proxy starts again or is shut down
+    suspended: Mutex<Option<Suspended>>,
+    state: Mutex<PhantomState>,
+    events: EventSink,
+    log: LogSink,
+    /// Kept across restarts
+    metrics: Arc<ProxyMetrics>,
+    power: Arc<PowerMode>,
+    /// Advertised in place of the server's MOTD, see `set_motd`
+    motd: Arc<RwLock<Option<String>>>,
+}
+
+/// `PhantomOpts` fields that only take effect when the listeners start
+const RESTART_FIELDS: [&str; 4] = ["server", "bind", "bind_port", "ipv6"];
+
+/// What `suspend` remembers for when the proxy starts again
+#[derive(Debug, Clone, Copy)]
+struct Suspended {
+    /// The port the proxy was listening on, to keep it the same for clients
+    /// even if the OS picked it. Cleared when the options change it.
+    proxy_port: Option<u16>,
+}
+
+/// Where the listeners are bound, and since when
+#[derive(Debug, Clone, Copy)]
+struct Listening {
+    broadcast_addr: SocketAddr,
+    proxy_addr: SocketAddr,
+    since: Instant,
 }
 
 impl ProxyInstance {
     pub fn new(opts: PhantomOpts) -> Result<Self, PhantomError> {
+        opts.validate()?;
+        let log = LogSink::default();
+        log.set_debug(opts.debug);
         Ok(ProxyInstance {
+            id: NEXT_INSTANCE_ID.fetch_add(1, Ordering::Relaxed),
             running: AtomicBool::new(false),
-            opts,
+            lifecycle: AsyncMutex::new(()),
+            opts: Mutex::new(opts),
             manager: TaskManager::new(),
+            registry: ActorRegistry::new(),
             notify_shutdown: Notify::new(),
+            listening: Mutex::new(None),
+            port_claim: Mutex::new(None),
+            suspended: Mutex::new(None),
+            state: Mutex::new(PhantomState::Idle),
+            events: EventSink::default(),
+            log,
+            metrics: Arc::default(),
+            power: Arc::default(),
+            motd: Arc::default(),
         })
     }
 
+    /// The options the proxy runs with
+    pub fn opts(&self) -> PhantomOpts {
+        self.opts.lock().clone()
+    }
+
     pub fn is_running(&self) -> bool {
         self.running.load(Ordering::SeqCst)
     }
 
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.lock().is_some()
+    }
+
+    pub fn state(&self) -> PhantomState {
+        self.state.lock().clone()
+    }
+
+    /// Moves to `state`, telling the event listener if it's a change
+    fn set_state(&self, state: PhantomState) {
+        {
+            let mut current = self.state.lock();
+            if *current == state {
+                return;
+            }
+            *current = state.clone();
+        }
+        log_to!(self.log, Debug, "State changed to {:?}", state);
+        self.events.emit(PhantomEvent::StateChanged { state });
+    }
+
+    /// This instance's actors, by name
+    pub fn registry(&self) -> &ActorRegistry {
+        &self.registry
+    }
+
     pub async fn listen(&self) -> Result<(), PhantomError> {
+        let _lifecycle = self.lifecycle.lock().await;
+        self.listen_locked().await
+    }
+
+    /// Starts the listeners, on the port they had before if suspended
+    async fn listen_locked(&self) -> Result<(), PhantomError> {
         self.running
             .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
             .map_err(|_| PhantomError::AlreadyRunning)?;
+        self.set_state(PhantomState::Starting);
+        let suspended = self.suspended.lock().take();
+        let reuse_port = suspended.and_then(|s| s.proxy_port);
 
-        let remote_server = resolve_remote_address(&self.opts.server).await?;
-        self.start_listeners(remote_server).await?;
+        let mut started = self.start(reuse_port).await;
+        if let (Some(port), Err(e)) = (reuse_port, &started) {
+            if is_port_taken(e) {
+                log_to!(
+                    self.log,
+                    Warn,
+                    "Port {} was taken while suspended, using the configured port",
+                    port
+                );
+                *self.port_claim.lock() = None;
+                started = self.start(None).await;
+            }
+        }
 
-        Ok(())
+        match &started {
+            Ok(()) => self.set_state(PhantomState::Running),
+            Err(e) => {
+                *self.port_claim.lock() = None;
+                self.running.store(false, Ordering::SeqCst);
+                self.set_state(PhantomState::Failed {
+                    message: e.to_string(),
+                });
+                if suspended.is_some() {
+                    // Not coming back, so don't leave `join` waiting
+                    self.notify_shutdown.notify_waiters();
+                }
+            }
+        }
+        started
     }
 
-    async fn start_listeners(&self, remote_addr: SocketAddr) -> Result<(), PhantomError> {
-        let broadcast_socket = bind_socket_reuse(&self.opts.bind, 19132).await?;
+    /// Listens on `proxy_port` if given, otherwise the configured `bind_port`
+    async fn start(&self, proxy_port: Option<u16>) -> Result<(), PhantomError> {
+        let opts = self.opts();
+        // Checked by `PhantomOpts::validate`
+        let bind_ip: IpAddr = opts
+            .bind
+            .parse()
+            .map_err(|_| PhantomError::InvalidOptions {
+                invalid: vec![InvalidOption {
+                    field: "bind".to_string(),
+                    reason: format!("{:?} is not an IP address", opts.bind),
+                }],
+            })?;
+        let proxy_addr = SocketAddr::new(bind_ip, proxy_port.unwrap_or(opts.bind_port));
+        *self.port_claim.lock() = PortClaim::claim(proxy_addr)?;
+
+        let remote_server = resolve_remote_address(&opts.server).await?;
+        self.start_listeners(
+            SocketAddr::new(bind_ip, BROADCAST_PORT),
+            proxy_addr,
+            remote_server,
+        )
+        .await
+    }
+
+    async fn start_listeners(
+        &self,
+        broadcast_addr: SocketAddr,
+        proxy_addr: SocketAddr,
+        remote_addr: SocketAddr,
+    ) -> Result<(), PhantomError> {
+        let broadcast_socket = bind_socket_reuse(broadcast_addr).await?;
         let broadcast_local_addr = broadcast_socket
             .local_addr()
-            .map_err(|e| PhantomError::FailedToBind(e.to_string()))?;
+            .map_err(|e| PhantomError::bind(broadcast_addr, e))?;
 
-        info!("Broadcast server listening on {}", broadcast_local_addr);
+        log_to!(
+            self.log,
+            Info,
+            "Broadcast server listening on {}",
+            broadcast_local_addr
+        );
 
-        let proxy_socket = bind_socket(&self.opts.bind, self.opts.bind_port).await?;
+        let proxy_socket = bind_socket(proxy_addr).await?;
         let proxy_local_addr = proxy_socket
             .local_addr()
-            .map_err(|e| PhantomError::FailedToBind(e.to_string()))?;
+            .map_err(|e| PhantomError::bind(proxy_addr, e))?;
 
-        info!("Proxy server listening on {}", proxy_local_addr);
+        log_to!(
+            self.log,
+            Info,
+            "Proxy server listening on {}",
+            proxy_local_addr
+        );
 
         let proxy_port = proxy_local_addr.port();
 
-        let router = create_router(remote_addr, proxy_port);
-        self.spawn_socket_reader(broadcast_socket, &router).await;
-        self.spawn_socket_reader(proxy_socket, &router).await;
-        self.manager.add_task(router);
+        let pong_rewrite = PongRewrite {
+            proxy_port,
+            motd: self.motd.clone(),
+        };
+        let router = create_router(
+            remote_addr,
+            pong_rewrite,
+            format!("proxy-{}", self.id),
+            self.events.clone(),
+            self.log.clone(),
+            self.metrics.clone(),
+            self.power.clone(),
+        );
+        if let Err(e) = self.registry.register(&router) {
+            log_to!(self.log, Error, "Failed to register router: {}", e);
+        }
+        self.spawn_socket_reader("broadcast-reader", broadcast_socket, &router)
+            .await;
+        self.spawn_socket_reader("proxy-reader", proxy_socket, &router)
+            .await;
+        // Stopped after the readers, so it can drain what they handed it
+        let router = DrainingRouter {
+            router,
+            drain_timeout: ROUTER_DRAIN_TIMEOUT,
+        };
+        let config = TaskConfig {
+            name: Some("router".to_string()),
+            group: ShutdownGroup::Processing,
+        };
+        self.manager.add_task_with_config(router, config);
+
+        let health = upstream_health_check(
+            remote_addr,
+            self.events.clone(),
+            self.log.clone(),
+            self.metrics.clone(),
+            self.power.clone(),
+        );
+        let config = TaskConfig {
+            name: Some("upstream-health".to_string()),
+            group: ShutdownGroup::Auxiliary,
+        };
+        self.manager.add_task_with_config(health, config);
+
+        *self.listening.lock() = Some(Listening {
+            broadcast_addr: broadcast_local_addr,
+            proxy_addr: proxy_local_addr,
+            since: Instant::now(),
+        });
+
+        Ok(())
+    }
+
+    async fn spawn_socket_reader(&self, name: &str, socket: UdpSocket, router: &Router) {
+        let task = socket_pipe_to_router(socket, router, self.log.clone());
+        let config = TaskConfig {
+            name: Some(name.to_string()),
+            group: ShutdownGroup::Ingress,
+        };
+        self.manager.add_task_with_config(task, config);
+    }
+
+    /// Sends proxy events to `listener` from now on, replacing any previous one
+    pub fn set_event_listener(&self, listener: Box<dyn PhantomEventListener>) {
+        self.events.set_listener(Some(listener));
+    }
+
+    /// Same, for a listener that's awaited in a task on `rt`
+    pub fn set_async_event_listener(
+        &self,
+        listener: Box<dyn AsyncPhantomEventListener>,
+        rt: &tokio::runtime::Handle,
+    ) {
+        self.events.set_async_listener(listener, rt);
+    }
+
+    /// Sends this instance's log output to `logger` from now on, replacing any
+    /// previous one. Other instances and the process-wide `log` logger are
+    /// unaffected.
+    pub fn set_logger(&self, logger: Box<dyn PhantomLogger>) {
+        self.log.set_logger(Some(logger));
+    }
+
+    /// Sends this instance's log records to `logger` from now on, replacing
+    /// any previous one
+    pub fn set_record_logger(&self, logger: Box<dyn PhantomRecordLogger>) {
+        self.log.set_record_logger(Some(logger));
+    }
+
+    /// The most verbose level sent to this instance's logger
+    pub fn set_log_level(&self, level: LogLevel) {
+        self.log.set_level(level);
+    }
+
+    /// Changes `opts.debug`, which takes effect immediately
+    pub fn set_debug(&self, enabled: bool) {
+        self.opts.lock().debug = enabled;
+        self.log.set_debug(enabled);
+    }
 
+    /// Advertises `motd` to clients instead of the server's, or the server's
+    /// own again with `None`. Applies to the next pong, running or not.
+    pub fn set_motd(&self, motd: Option<String>) -> Result<(), PhantomError> {
+        if let Some(motd) = &motd {
+            let reason = match motd::validate(motd) {
+                Err(ProtoError::InvalidMotd { reason }) => Some(reason),
+                Err(_) => Some("is invalid"),
+                // Leaves room for at least the field's separator
+                Ok(()) if motd.len() + 1 > max_pong_payload() => Some("doesn't fit in a pong"),
+                Ok(()) => None,
+            };
+            if let Some(reason) = reason {
+                return Err(PhantomError::InvalidOptions {
+                    invalid: vec![InvalidOption {
+                        field: "motd".to_string(),
+                        reason: reason.to_string(),
+                    }],
+                });
+            }
+        }
+        *self.motd.write() = motd;
         Ok(())
     }
 
-    async fn spawn_socket_reader(&self, socket: UdpSocket, router: &Router) {
-        let task = socket_pipe_to_router(socket, router);
-        self.manager.add_task(task);
+    /// Slows periodic work while no clients are connected
+    pub fn set_low_power_mode(&self, enabled: bool) {
+        log_to!(
+            self.log,
+            Debug,
+            "Low-power mode {}",
+            if enabled { "on" } else { "off" }
+        );
+        self.power.set_low_power(enabled);
     }
 
+    /// Logs through this instance's logger
+    pub(crate) fn log(&self) -> &LogSink {
+        &self.log
+    }
+
+    /// What the proxy is running right now, for debugging
+    pub fn tasks(&self) -> Vec<TaskInfo> {
+        self.manager.list()
+    }
+
+    /// Clients with an open session through the proxy. Empty when stopped.
+    pub async fn connected_clients(&self) -> Vec<SocketAddr> {
+        let Some(router) = self.registry.lookup::<RouterMessage>("router") else {
+            return Vec::new();
+        };
+        router::connected_clients(&router).await.unwrap_or_default()
+    }
+
+    /// Details of each open client session. Empty when stopped.
+    pub async fn list_clients(&self) -> Vec<ClientSession> {
+        let Some(router) = self.registry.lookup::<RouterMessage>("router") else {
+            return Vec::new();
+        };
+        router::client_sessions(&router).await.unwrap_or_default()
+    }
+
+    /// Ends the session of the client at `client_addr`, closing the proxy's
+    /// socket to the server for it. Returns whether it had one.
+    pub async fn disconnect_client(&self, client_addr: SocketAddr) -> bool {
+        let Some(router) = self.registry.lookup::<RouterMessage>("router") else {
+            return false;
+        };
+        router::disconnect_client(&router, client_addr)
+            .await
+            .unwrap_or(false)
+    }
+
+    /// Where the listeners are bound, with the port the OS picked if
+    /// `bind_port` was 0. `None` until they're bound and after shutdown.
+    pub fn bound_addresses(&self) -> Option<BoundAddresses> {
+        let listening = (*self.listening.lock())?;
+        Some(BoundAddresses {
+            broadcast_address: listening.broadcast_addr.ip().to_string(),
+            broadcast_port: listening.broadcast_addr.port(),
+            proxy_address: listening.proxy_addr.ip().to_string(),
+            proxy_port: listening.proxy_addr.port(),
+        })
+    }
+
+    /// A snapshot of whether the proxy is running, where it's listening and
+    /// how many clients it has
+    pub async fn status(&self) -> PhantomStatus {
+        let since = self.listening.lock().map(|l| l.since);
+        let bound = self.bound_addresses();
+        let active_clients = self.connected_clients().await.len();
+        PhantomStatus {
+            running: self.is_running(),
+            broadcast_address: bound.as_ref().map(|b| b.broadcast_address.clone()),
+            broadcast_port: bound.as_ref().map(|b| b.broadcast_port),
+            proxy_address: bound.as_ref().map(|b| b.proxy_address.clone()),
+            proxy_port: bound.as_ref().map(|b| b.proxy_port),
+            active_clients: active_clients as u32,
+            uptime: since.map(|since| since.elapsed()),
+            state: self.state(),
+            low_power: self.power.is_low_power(),
+            suspended: self.is_suspended(),
+        }
+    }
+
+    /// Traffic and client totals since the instance was created
+    pub fn metrics(&self) -> PhantomMetrics {
+        self.metrics.snapshot()
+    }
+
+    /// Waits for `shutdown`, or a restart that fails. Returns straight away
+    /// if the proxy isn't running.
     pub async fn join(&self) {
-        self.notify_shutdown.notified().await;
-        debug!("All tasks completed");
+        let notified = self.notify_shutdown.notified();
+        tokio::pin!(notified);
+        // Registered before checking, so a shutdown in between isn't missed
+        notified.as_mut().enable();
+        if !self.is_running() && !self.is_suspended() {
+            return;
+        }
+        notified.await;
+        log_to!(self.log, Debug, "All tasks completed");
     }
 
+    /// Stops the proxy, waking anyone in `join`. Does nothing if it isn't
+    /// running or suspended.
     pub async fn shutdown(&self) -> Result<(), PhantomError> {
-        debug!("Shutdown signal sent to all tasks");
-        self.manager.shutdown().await;
-        self.running.store(false, Ordering::SeqCst);
-        self.notify_shutdown.notify_waiters();
+        let _lifecycle = self.lifecycle.lock().await;
+        let was_suspended = self.suspended.lock().take().is_some();
+        let was_running = self.is_running();
+        if was_running {
+            self.stop_locked().await;
+        }
+        if was_running || was_suspended {
+            self.set_state(PhantomState::Idle);
+            self.notify_shutdown.notify_waiters();
+        }
+        Ok(())
+    }
+
+    /// Stops the listeners and drops client sessions, remembering the port
+    /// for `resume`. Unlike `shutdown`, those in `join` keep waiting. Does
+    /// nothing if the proxy isn't running.
+    pub async fn suspend(&self) -> Result<(), PhantomError> {
+        let _lifecycle = self.lifecycle.lock().await;
+        if !self.is_running() {
+            return Ok(());
+        }
+
+        let proxy_port = self.listening.lock().map(|l| l.proxy_addr.port());
+        log_to!(self.log, Info, "Suspending proxy");
+        self.stop_locked().await;
+        *self.suspended.lock() = Some(Suspended { proxy_port });
+        self.set_state(PhantomState::Suspended);
         Ok(())
     }
+
+    /// Starts the listeners again after `suspend`, returning once they're
+    /// bound. Returns false, doing nothing, if the proxy isn't suspended.
+    pub async fn resume(&self) -> Result<bool, PhantomError> {
+        let _lifecycle = self.lifecycle.lock().await;
+        if !self.is_suspended() {
+            return Ok(false);
+        }
+
+        log_to!(self.log, Info, "Resuming proxy");
+        self.listen_locked().await.map(|_| true)
+    }
+
+    /// Stops the proxy if it's running and starts it again with the same
+    /// options, returning once it's listening. Nothing can start or stop it
+    /// in between, and those in `join` keep waiting.
+    pub async fn restart(&self) -> Result<(), PhantomError> {
+        let _lifecycle = self.lifecycle.lock().await;
+        if self.is_running() {
+            self.stop_locked().await;
+        }
+        let restarted = self.listen_locked().await;
+        if restarted.is_err() {
+            // Not coming back, so don't leave `join` waiting
+            self.notify_shutdown.notify_waiters();
+        }
+        restarted
+    }
+
+    /// Switches to `opts`. Changes to the server or listening addresses
+    /// restart the listeners if the proxy is running, dropping client
+    /// sessions; the rest apply straight away. If the restart fails the
+    /// proxy is left stopped, with the new options.
+    pub async fn update_opts(&self, opts: PhantomOpts) -> Result<OptsUpdate, PhantomError> {
+        opts.validate()?;
+        let _lifecycle = self.lifecycle.lock().await;
+
+        let changed = self.opts.lock().changed_fields(&opts);
+        let needs_restart = changed.iter().any(|field| RESTART_FIELDS.contains(field));
+        self.log.set_debug(opts.debug);
+        *self.opts.lock() = opts;
+        if needs_restart {
+            if let Some(suspended) = self.suspended.lock().as_mut() {
+                suspended.proxy_port = None;
+            }
+        }
+
+        let restarted = needs_restart && self.is_running();
+        if restarted {
+            log_to!(
+                self.log,
+                Info,
+                "Restarting listeners to apply new options: {}",
+                changed.join(", ")
+            );
+            self.stop_locked().await;
+            if let Err(e) = self.listen_locked().await {
+                self.notify_shutdown.notify_waiters();
+                return Err(e);
+            }
+        }
+
+        Ok(OptsUpdate {
+            changed: changed.into_iter().map(str::to_string).collect(),
+            restarted,
+        })
+    }
+
+    /// Stops everything, leaving the caller to set the state it ends in
+    async fn stop_locked(&self) {
+        log_to!(self.log, Debug, "Shutdown signal sent to all tasks");
+        // Readers stop first, then the router forwards what they handed it
+        self.set_state(PhantomState::Draining);
+        self.manager
+            .shutdown_observed(|group| {
+                if group == ShutdownGroup::Auxiliary {
+                    self.set_state(PhantomState::Stopping);
+                }
+            })
+            .await;
+        // Sessions end with the router, without a disconnect each
+        self.metrics.clients_dropped();
+        *self.listening.lock() = None;
+        *self.port_claim.lock() = None;
+        self.running.store(false, Ordering::SeqCst);
+    }
 }
 
+/// Forwards packets from a listening socket to the router. The read loop is
+/// restarted if it fails, so a transient socket error doesn't take the
+/// listener down for good.
 fn socket_pipe_to_router(
     socket: UdpSocket,
     router: &ActorRef<RouterMessage>,
-) -> CancellablePacketReader {
+    log: LogSink,
+) -> SupervisedTask {
     let socket: Arc<UdpSocket> = Arc::new(socket);
     let router = router.clone();
 
-    read_cancellable(socket.clone(), move |packet| {
-        let router = router.clone();
-        let socket = socket.clone();
-        async move {
-            router
-                .send(RouterMessage::PacketFromClient {
-                    data: packet.data,
-                    client_addr: packet.client_addr,
-                    to_client: socket,
-                })
-                .unwrap_or_else(|e| error!("Error sending message to router: {}", e));
-        }
-    })
+    let local_addr = socket
+        .local_addr()
+        .map(|a| a.to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+    let exit_log = log.clone();
+    read_supervised(
+        socket.clone(),
+        move |packet| {
+            let router = router.clone();
+            let socket = socket.clone();
+            let log = log.clone();
+            // The router's handling of the packet nests under this span
+            let span = debug_span!("client_packet", client = %packet.client_addr);
+            async move {
+                router
+                    .send_async(RouterMessage::PacketFromClient {
+                        data: packet.data,
+                        client_addr: packet.client_addr,
+                        to_client: socket,
+                    })
+                    .await
+                    .unwrap_or_else(|e| {
+                        log_to!(log, Error, "Error sending message to router: {}", e)
+                    });
+            }
+            .instrument(span)
+        },
+        RestartPolicy::default(),
+        move |exit| {
+            if !matches!(exit, TaskExit::Cancelled) {
+                log_to!(
+                    exit_log,
+                    Error,
+                    "Listener on {} {}, no longer accepting packets",
+                    local_addr,
+                    exit
+                );
+            }
+        },
+    )
 }
 
 async fn resolve_remote_address(server: &str) -> Result<SocketAddr, PhantomError> {
+    let invalid = |message: String| PhantomError::InvalidAddress {
+        address: server.to_string(),
+        message,
+    };
     server
         .to_socket_addrs()
-        .map_err(|e| PhantomError::InvalidAddress(e.to_string()))?
+        .map_err(|e| invalid(e.to_string()))?
         .next()
-        .ok_or(PhantomError::InvalidAddress(
-            "Remote server address not found".to_string(),
-        ))
+        .ok_or_else(|| invalid("no address found".to_string()))
 }
 
-async fn bind_socket_reuse(bind: &str, port: u16) -> Result<UdpSocket, PhantomError> {
-    let addr: SocketAddr = format!("{}:{}", bind, port).parse().unwrap();
+async fn bind_socket_reuse(addr: SocketAddr) -> Result<UdpSocket, PhantomError> {
+    let bind_error = |e| PhantomError::bind(addr, e);
 
     // TODO: Support ipv6
     let socket = socket2::Socket::new(
@@ -132,32 +661,522 @@ async fn bind_socket_reuse(bind: &str, port: u16) -> Result<UdpSocket, PhantomEr
         socket2::Type::DGRAM,
         Some(socket2::Protocol::UDP),
     )
-    .map_err(|e| PhantomError::FailedToBind(e.to_string()))?;
+    .map_err(bind_error)?;
 
-    socket
-        .set_reuse_port(true)
-        .map_err(|e| PhantomError::FailedToBind(e.to_string()))?;
+    socket.set_reuse_port(true).map_err(bind_error)?;
 
-    socket
-        .set_reuse_address(true)
-        .map_err(|e| PhantomError::FailedToBind(e.to_string()))?;
+    socket.set_reuse_address(true).map_err(bind_error)?;
 
-    socket
-        .set_nonblocking(true)
-        .map_err(|e| PhantomError::FailedToBind(e.to_string()))?;
+    socket.set_nonblocking(true).map_err(bind_error)?;
 
-    socket
-        .bind(&addr.into())
-        .map_err(|e| PhantomError::FailedToBind(e.to_string()))?;
+    socket.bind(&addr.into()).map_err(bind_error)?;
 
     let socket_std = std::net::UdpSocket::from(socket);
 
-    UdpSocket::from_std(socket_std).map_err(|e| PhantomError::FailedToBind(e.to_string()))
+    UdpSocket::from_std(socket_std).map_err(bind_error)
 }
 
-async fn bind_socket(bind: &str, port: u16) -> Result<UdpSocket, PhantomError> {
-    let addr = format!("{}:{}", bind, port);
-    UdpSocket::bind(&addr)
+/// Someone else has the port, in this process or another
+fn is_port_taken(error: &PhantomError) -> bool {
+    matches!(
+        error,
+        PhantomError::PortInUse { .. }
+            | PhantomError::FailedToBind {
+                kind: IoErrorKind::AddressInUse,
+                ..
+            }
+    )
+}
+
+async fn bind_socket(addr: SocketAddr) -> Result<UdpSocket, PhantomError> {
+    UdpSocket::bind(addr)
+        .await
+        .map_err(|e| PhantomError::bind(addr, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts() -> PhantomOpts {
+        PhantomOpts {
+            server: "127.0.0.1:19133".to_string(),
+            bind: "127.0.0.1".to_string(),
+            bind_port: 0,
+            timeout: 60,
+            debug: false,
+            ipv6: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let instance = ProxyInstance::new(opts()).unwrap();
+        let status = instance.status().await;
+        assert!(!status.running);
+        assert_eq!(status.proxy_port, None);
+
+        instance.listen().await.unwrap();
+        let status = instance.status().await;
+        assert!(status.running);
+        assert_eq!(status.broadcast_address.as_deref(), Some("127.0.0.1"));
+        assert_eq!(status.broadcast_port, Some(19132));
+        assert_ne!(status.proxy_port, Some(0));
+        assert_eq!(status.active_clients, 0);
+        assert!(status.uptime.is_some());
+        let bound = instance.bound_addresses().unwrap();
+        assert_eq!(Some(bound.proxy_port), status.proxy_port);
+        assert_eq!(bound.proxy_address, "127.0.0.1");
+
+        instance.shutdown().await.unwrap();
+        let status = instance.status().await;
+        assert!(!status.running);
+        assert_eq!(status.uptime, None);
+        assert_eq!(instance.bound_addresses(), None);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_instances() {
+        let first = ProxyInstance::new(opts()).unwrap();
+        let second = ProxyInstance::new(opts()).unwrap();
+        first.listen().await.unwrap();
+        second.listen().await.unwrap();
+        assert_ne!(
+            first.bound_addresses().unwrap().proxy_port,
+            second.bound_addresses().unwrap().proxy_port
+        );
+
+        let fixed = PhantomOpts {
+            bind_port: 40411,
+            ..opts()
+        };
+        let third = ProxyInstance::new(fixed.clone()).unwrap();
+        let fourth = ProxyInstance::new(fixed).unwrap();
+        third.listen().await.unwrap();
+        assert!(matches!(
+            fourth.listen().await,
+            Err(PhantomError::PortInUse { port: 40411 })
+        ));
+        assert!(!fourth.is_running());
+
+        // The port is free again once its owner stops
+        third.shutdown().await.unwrap();
+        fourth.listen().await.unwrap();
+
+        for instance in [first, second, fourth] {
+            instance.shutdown().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind_error_kind() {
+        let taken = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let instance = ProxyInstance::new(PhantomOpts {
+            bind_port: port,
+            ..opts()
+        })
+        .unwrap();
+
+        match instance.listen().await {
+            Err(PhantomError::FailedToBind { address, kind, .. }) => {
+                assert_eq!(address, format!("127.0.0.1:{}", port));
+                assert_eq!(kind, IoErrorKind::AddressInUse);
+            }
+            other => panic!("expected FailedToBind, got {:?}", other),
+        }
+        assert!(!instance.is_running());
+    }
+
+    #[tokio::test]
+    async fn test_restart() {
+        let instance = Arc::new(
+            ProxyInstance::new(PhantomOpts {
+                bind_port: 40412,
+                ..opts()
+            })
+            .unwrap(),
+        );
+        // Starts it when stopped
+        instance.restart().await.unwrap();
+        assert!(instance.is_running());
+
+        let joining = instance.clone();
+        let join = tokio::spawn(async move { joining.join().await });
+        tokio::task::yield_now().await;
+
+        // Rebinds the same port, and doesn't count as stopping
+        instance.restart().await.unwrap();
+        assert_eq!(instance.bound_addresses().unwrap().proxy_port, 40412);
+        tokio::task::yield_now().await;
+        assert!(!join.is_finished());
+
+        instance.shutdown().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), join)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_suspend_resume() {
+        let instance = Arc::new(ProxyInstance::new(opts()).unwrap());
+        instance.listen().await.unwrap();
+        let port = instance.bound_addresses().unwrap().proxy_port;
+
+        let joining = instance.clone();
+        let join = tokio::spawn(async move { joining.join().await });
+        tokio::task::yield_now().await;
+
+        instance.suspend().await.unwrap();
+        assert!(!instance.is_running() && instance.is_suspended());
+        assert!(instance.bound_addresses().is_none());
+        // Sockets are closed, so the port is free meanwhile
+        drop(UdpSocket::bind(("127.0.0.1", port)).await.unwrap());
+        tokio::task::yield_now().await;
+        assert!(!join.is_finished());
+
+        // Comes back on the port the OS picked the first time
+        assert!(instance.resume().await.unwrap());
+        assert_eq!(instance.bound_addresses().unwrap().proxy_port, port);
+        assert!(!instance.resume().await.unwrap());
+
+        // Shutting down while suspended still wakes `join`
+        instance.suspend().await.unwrap();
+        instance.shutdown().await.unwrap();
+        assert!(!instance.is_suspended());
+        tokio::time::timeout(Duration::from_secs(1), join)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_opts() {
+        let instance = ProxyInstance::new(opts()).unwrap();
+        instance.listen().await.unwrap();
+
+        let update = instance
+            .update_opts(PhantomOpts {
+                timeout: 30,
+                ..opts()
+            })
+            .await
+            .unwrap();
+        assert_eq!(update.changed, ["timeout"]);
+        assert!(!update.restarted);
+
+        let update = instance
+            .update_opts(PhantomOpts {
+                timeout: 30,
+                debug: true,
+                ..opts()
+            })
+            .await
+            .unwrap();
+        assert_eq!(update.changed, ["debug"]);
+        assert!(!update.restarted);
+        assert!(instance.log.is_debug());
+        instance.set_debug(false);
+        assert!(!instance.opts().debug);
+        assert!(!instance.log.is_debug());
+
+        let update = instance
+            .update_opts(PhantomOpts {
+                timeout: 30,
+                bind_port: 40413,
+                ..opts()
+            })
+            .await
+            .unwrap();
+        assert_eq!(update.changed, ["bind_port"]);
+        assert!(update.restarted);
+        assert_eq!(instance.bound_addresses().unwrap().proxy_port, 40413);
+
+        assert!(matches!(
+            instance
+                .update_opts(PhantomOpts {
+                    bind: "nowhere".to_string(),
+                    ..opts()
+                })
+                .await,
+            Err(PhantomError::InvalidOptions { .. })
+        ));
+        assert_eq!(instance.opts().bind_port, 40413);
+
+        instance.shutdown().await.unwrap();
+    }
+
+    struct LogRecorder(Arc<Mutex<Vec<String>>>);
+
+    impl PhantomLogger for LogRecorder {
+        fn log_string(&self, line: String) {
+            self.0.lock().push(line);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_per_instance_logger() {
+        let first = ProxyInstance::new(opts()).unwrap();
+        let second = ProxyInstance::new(opts()).unwrap();
+        let first_lines = Arc::new(Mutex::new(Vec::new()));
+        let second_lines = Arc::new(Mutex::new(Vec::new()));
+        first.set_logger(Box::new(LogRecorder(first_lines.clone())));
+        second.set_logger(Box::new(LogRecorder(second_lines.clone())));
+
+        first.listen().await.unwrap();
+        let proxy_port = first.bound_addresses().unwrap().proxy_port;
+        let expected = format!("[INFO] Proxy server listening on 127.0.0.1:{}", proxy_port);
+        assert!(first_lines.lock().contains(&expected));
+        assert!(second_lines.lock().is_empty());
+
+        first.shutdown().await.unwrap();
+    }
+
+    struct Recorder(std::sync::mpsc::Sender<PhantomEvent>);
+
+    impl PhantomEventListener for Recorder {
+        fn on_event(&self, event: PhantomEvent) {
+            let _ = self.0.send(event);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_connected_event() {
+        let instance = ProxyInstance::new(opts()).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        instance.set_event_listener(Box::new(Recorder(tx)));
+        instance.listen().await.unwrap();
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let proxy_port = instance.bound_addresses().unwrap().proxy_port;
+        client
+            .send_to(&[0x84, 0, 0, 0], ("127.0.0.1", proxy_port))
+            .await
+            .unwrap();
+
+        let event = tokio::task::spawn_blocking(move || loop {
+            match rx.recv_timeout(Duration::from_secs(3)) {
+                Ok(PhantomEvent::StateChanged { .. }) => continue,
+                received => break received,
+            }
+        })
         .await
-        .map_err(|e| PhantomError::FailedToBind(e.to_string()))
+        .unwrap()
+        .expect("no event received");
+        assert_eq!(
+            event,
+            PhantomEvent::ClientConnected {
+                client_address: "127.0.0.1".to_string(),
+                client_port: client.local_addr().unwrap().port(),
+            }
+        );
+
+        instance.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_state_changes() {
+        let instance = ProxyInstance::new(opts()).unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        instance.set_event_listener(Box::new(Recorder(tx)));
+        assert_eq!(instance.state(), PhantomState::Idle);
+
+        instance.listen().await.unwrap();
+        assert_eq!(instance.state(), PhantomState::Running);
+        instance.suspend().await.unwrap();
+        instance.shutdown().await.unwrap();
+
+        let states: Vec<_> = rx
+            .try_iter()
+            .filter_map(|event| match event {
+                PhantomEvent::StateChanged { state } => Some(state),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                PhantomState::Starting,
+                PhantomState::Running,
+                PhantomState::Draining,
+                PhantomState::Stopping,
+                PhantomState::Suspended,
+                PhantomState::Idle,
+            ]
+        );
+
+        // Stays failed until started again
+        let instance = ProxyInstance::new(PhantomOpts {
+            server: "unresolvable.invalid:19132".to_string(),
+            ..opts()
+        })
+        .unwrap();
+        assert!(instance.listen().await.is_err());
+        assert!(matches!(instance.state(), PhantomState::Failed { .. }));
+    }
+
+    /// Waits for a client packet to reach `server`, skipping the health
+    /// check's pings. Returns where the proxy sent it from.
+    async fn recv_forwarded(server: &UdpSocket) -> SocketAddr {
+        let mut buf = [0; 1500];
+        loop {
+            let (len, from) =
+                tokio::time::timeout(Duration::from_secs(3), server.recv_from(&mut buf))
+                    .await
+                    .unwrap()
+                    .unwrap();
+            if len > 0 && buf[0] != 0x01 {
+                return from;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_clients() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let instance = ProxyInstance::new(PhantomOpts {
+            server: server.local_addr().unwrap().to_string(),
+            ..opts()
+        })
+        .unwrap();
+        instance.listen().await.unwrap();
+        assert!(instance.list_clients().await.is_empty());
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let proxy_port = instance.bound_addresses().unwrap().proxy_port;
+        client
+            .send_to(&[0x84, 0, 0, 0, 0, 0], ("127.0.0.1", proxy_port))
+            .await
+            .unwrap();
+
+        let from = recv_forwarded(&server).await;
+
+        let sessions = instance.list_clients().await;
+        assert_eq!(sessions.len(), 1);
+        let session = &sessions[0];
+        assert_eq!(session.client_address, "127.0.0.1");
+        assert_eq!(session.client_port, client.local_addr().unwrap().port());
+        assert_eq!(session.upstream_port, from.port());
+        assert_eq!(session.bytes_from_client, 6);
+        assert_eq!(session.bytes_to_client, 0);
+        assert!(session.last_activity >= session.connected_at);
+
+        let client_addr = client.local_addr().unwrap();
+        assert!(instance.disconnect_client(client_addr).await);
+        assert!(instance.list_clients().await.is_empty());
+        assert!(!instance.disconnect_client(client_addr).await);
+
+        instance.shutdown().await.unwrap();
+        assert!(instance.list_clients().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_motd() {
+        use crate::proto::unconnected_pong::UnconnectedPong;
+        use bytes::Bytes;
+
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let instance = ProxyInstance::new(PhantomOpts {
+            server: server.local_addr().unwrap().to_string(),
+            ..opts()
+        })
+        .unwrap();
+        instance.listen().await.unwrap();
+        instance
+            .set_motd(Some("Restarting in 5 min".to_string()))
+            .unwrap();
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let proxy_port = instance.bound_addresses().unwrap().proxy_port;
+        client
+            .send_to(&[0x84, 0, 0, 0], ("127.0.0.1", proxy_port))
+            .await
+            .unwrap();
+        let from = recv_forwarded(&server).await;
+
+        let mut pong = UnconnectedPong::new();
+        pong.pong.motd = "Dedicated Server".to_string();
+        let relayed_motd = || async {
+            server.send_to(&pong.build().unwrap(), from).await.unwrap();
+            let mut buf = [0; 1500];
+            let (len, _) = tokio::time::timeout(Duration::from_secs(3), client.recv_from(&mut buf))
+                .await
+                .unwrap()
+                .unwrap();
+            UnconnectedPong::from_bytes(Bytes::copy_from_slice(&buf[..len]))
+                .unwrap()
+                .pong
+                .motd
+        };
+
+        assert_eq!(relayed_motd().await, "Restarting in 5 min");
+        instance.set_motd(None).unwrap();
+        assert_eq!(relayed_motd().await, "Dedicated Server");
+
+        assert!(matches!(
+            instance.set_motd(Some("a;b".to_string())),
+            Err(PhantomError::InvalidOptions { .. })
+        ));
+        // Format codes don't count towards the visible length, only the size
+        assert!(matches!(
+            instance.set_motd(Some(format!("{}x", "§a".repeat(600)))),
+            Err(PhantomError::InvalidOptions { .. })
+        ));
+
+        instance.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        use crate::proto::unconnected_pong::UnconnectedPong;
+
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let instance = ProxyInstance::new(PhantomOpts {
+            server: server.local_addr().unwrap().to_string(),
+            ..opts()
+        })
+        .unwrap();
+        instance.listen().await.unwrap();
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let proxy_port = instance.bound_addresses().unwrap().proxy_port;
+        client
+            .send_to(&[0x84, 0, 0, 0], ("127.0.0.1", proxy_port))
+            .await
+            .unwrap();
+
+        // Answer with a pong, which the proxy rewrites on the way back
+        let from = recv_forwarded(&server).await;
+        let pong = UnconnectedPong::new().build().unwrap();
+        server.send_to(&pong, from).await.unwrap();
+        let mut buf = [0; 1500];
+        let (len, _) = tokio::time::timeout(Duration::from_secs(3), client.recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Counted once the send returns, which can be after the client has it
+        let mut metrics = instance.metrics();
+        for _ in 0..50 {
+            if metrics.packets_to_clients > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            metrics = instance.metrics();
+        }
+        assert_eq!(metrics.packets_from_clients, 1);
+        assert_eq!(metrics.bytes_from_clients, 4);
+        assert_eq!(metrics.packets_to_clients, 1);
+        assert_eq!(metrics.bytes_to_clients, len as u64);
+        assert_eq!(metrics.active_clients, 1);
+        assert_eq!(metrics.lifetime_clients, 1);
+        assert_eq!(metrics.pong_rewrites, 1);
+        assert_eq!(metrics.errors, 0);
+
+        instance.shutdown().await.unwrap();
+        let metrics = instance.metrics();
+        assert_eq!(metrics.active_clients, 0);
+        assert_eq!(metrics.lifetime_clients, 1);
+    }
 }
@@ -0,0 +1,76 @@
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::net::SocketAddr;
+
+use crate::api::PhantomError;
+
+/// Proxy addresses with an explicit port that instances in this process are
+/// listening on
+static CLAIMED: Lazy<Mutex<Vec<SocketAddr>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Reserves a proxy address for one instance until dropped, so a second
+/// instance asking for the same port fails up front with `PortInUse` rather
+/// than with whatever the OS reports when binding
+#[derive(Debug)]
+pub(super) struct PortClaim {
+    addr: SocketAddr,
+}
+
+impl PortClaim {
+    /// Port 0 lets the OS pick a free port, so it never conflicts and `None`
+    /// is returned
+    pub(super) fn claim(addr: SocketAddr) -> Result<Option<PortClaim>, PhantomError> {
+        if addr.port() == 0 {
+            return Ok(None);
+        }
+
+        let mut claimed = CLAIMED.lock();
+        if claimed.iter().any(|other| overlaps(*other, addr)) {
+            return Err(PhantomError::PortInUse { port: addr.port() });
+        }
+        claimed.push(addr);
+        Ok(Some(PortClaim { addr }))
+    }
+}
+
+impl Drop for PortClaim {
+    fn drop(&mut self) {
+        let mut claimed = CLAIMED.lock();
+        if let Some(index) = claimed.iter().position(|addr| *addr == self.addr) {
+            claimed.swap_remove(index);
+        }
+    }
+}
+
+/// Same port on the same IP, or on any IP if either is unspecified
+fn overlaps(a: SocketAddr, b: SocketAddr) -> bool {
+    a.port() == b.port() && (a.ip() == b.ip() || a.ip().is_unspecified() || b.ip().is_unspecified())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claims_conflict_until_dropped() {
+        let any: SocketAddr = "0.0.0.0:40321".parse().unwrap();
+        let loopback: SocketAddr = "127.0.0.1:40321".parse().unwrap();
+        let other_ip: SocketAddr = "127.0.0.2:40321".parse().unwrap();
+
+        let claim = PortClaim::claim(any).unwrap();
+        assert!(claim.is_some());
+        assert!(matches!(
+            PortClaim::claim(loopback),
+            Err(PhantomError::PortInUse { port: 40321 })
+        ));
+
+        drop(claim);
+        let loopback_claim = PortClaim::claim(loopback).unwrap();
+        let other_claim = PortClaim::claim(other_ip).unwrap();
+        assert!(loopback_claim.is_some() && other_claim.is_some());
+
+        assert!(PortClaim::claim("0.0.0.0:0".parse().unwrap())
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,99 @@
+use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
+use tokio::time::{sleep, sleep_until, Duration, Instant};
+
+/// How often periodic work runs in low-power mode while no clients are
+/// connected
+pub(super) const LOW_POWER_INTERVAL: Duration = Duration::from_secs(120);
+
+/// Low-power wake-ups land on multiples of `LOW_POWER_INTERVAL` from here, so
+/// every instance in the process wakes at once instead of each on its own
+static EPOCH: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// Whether the instance is in low-power mode, and a way to cut an idle
+/// wait short when a client shows up
+#[derive(Debug, Default)]
+pub(crate) struct PowerMode {
+    low_power: AtomicBool,
+    activity: Notify,
+}
+
+impl PowerMode {
+    pub(crate) fn set_low_power(&self, enabled: bool) {
+        self.low_power.store(enabled, Ordering::Relaxed);
+        // Anything waiting out a low-power interval goes back to normal
+        self.activity.notify_waiters();
+    }
+
+    pub(crate) fn is_low_power(&self) -> bool {
+        self.low_power.load(Ordering::Relaxed)
+    }
+
+    /// A new client session started
+    pub(crate) fn client_arrived(&self) {
+        self.activity.notify_waiters();
+    }
+
+    /// Waits `period` before the next round of periodic work. In low-power
+    /// mode with no clients (`idle`), waits for the next shared low-power
+    /// wake-up instead, or until a client arrives.
+    pub(super) async fn wait(&self, period: Duration, idle: bool) {
+        // Created before checking the mode, so a change in between still wakes it
+        let woken = self.activity.notified();
+        if !(idle && self.is_low_power()) {
+            sleep(period).await;
+            return;
+        }
+
+        tokio::select! {
+            _ = sleep_until(next_low_power_wake()) => {}
+            _ = woken => {}
+        }
+    }
+}
+
+/// The first shared wake-up at least one `LOW_POWER_INTERVAL` from now
+fn next_low_power_wake() -> Instant {
+    let epoch = *EPOCH;
+    let earliest = Instant::now() + LOW_POWER_INTERVAL - epoch;
+    let interval = LOW_POWER_INTERVAL.as_millis();
+    let rounded = earliest.as_millis().div_ceil(interval) * interval;
+    epoch + Duration::from_millis(rounded as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_low_power_wait() {
+        let power = Arc::new(PowerMode::default());
+
+        // Idle alone changes nothing
+        let start = Instant::now();
+        power.wait(Duration::from_secs(10), true).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+
+        power.set_low_power(true);
+        let start = Instant::now();
+        power.wait(Duration::from_secs(10), true).await;
+        let waited = start.elapsed();
+        assert!(waited >= LOW_POWER_INTERVAL && waited < LOW_POWER_INTERVAL * 2);
+        assert_eq!(
+            (Instant::now() - *EPOCH).as_secs() % LOW_POWER_INTERVAL.as_secs(),
+            0
+        );
+
+        // A client arriving ends the wait early
+        let waiting = power.clone();
+        let wait = tokio::spawn(async move { waiting.wait(Duration::from_secs(10), true).await });
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        power.client_arrived();
+        tokio::time::timeout(Duration::from_millis(1), wait)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
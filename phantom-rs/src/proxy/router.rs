@@ -1,73 +1,266 @@
-use log::{debug, info};
+use std::any::Any;
 use std::collections::HashMap;
+use std::future::Future;
 use std::net::SocketAddr;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::actor::{behavior, Actor, ActorRef, RunningActor};
-use crate::proto::unconnected_pong::UnconnectedPong;
+use crate::actor::{
+    batch_behavior, Actor, ActorConfig, ActorError, ActorRef, ChildId, MailboxConfig,
+    OverflowPolicy, Reply, RunningActor, WeakActorRef,
+};
+use crate::api::{log_packet, log_to, ClientSession, EventSink, LogSink, PhantomEvent};
+use crate::proto::advertise_system::AdvertiseSystem;
+use crate::proto::datagram::Datagram;
+use crate::proto::mtu::{max_payload_size, MAX_MTU_SIZE};
+use crate::proto::packet::{classify, OfflineMessageId, PacketKind};
+use crate::proto::unconnected_ping::UnconnectedPing;
+use crate::proto::unconnected_pong::{PongData, UnconnectedPong, UNCONNECTED_PONG_HEADER_SIZE};
+use crate::proto::MagicMode;
 use crate::proxy::socket::read_cancellable;
+use crate::task::{CancellableTask, TaskExit};
 use tokio::net::UdpSocket;
+use tokio::task::AbortHandle;
 
 use bytes::Bytes;
+use parking_lot::RwLock;
 
+use super::metrics::{ProxyMetrics, SessionStats};
+use super::power::PowerMode;
 use super::socket::CancellablePacketReader;
 
 #[derive(Debug, Clone)]
 struct RouterState {
     remote_addr: SocketAddr,
-    proxy_port: u16,
+    pong_rewrite: PongRewrite,
     client_map: HashMap<SocketAddr, ClientConnectionPair>,
+    events: EventSink,
+    log: LogSink,
+    metrics: Arc<ProxyMetrics>,
+    power: Arc<PowerMode>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub enum RouterMessage {
     PacketFromClient {
         data: Bytes,
         client_addr: SocketAddr,
         to_client: Arc<UdpSocket>,
     },
+    /// The client or server sent a DisconnectionNotification
+    SessionClosed { client_addr: SocketAddr },
+    /// Drops the client's session, answering whether it had one
+    Disconnect {
+        client_addr: SocketAddr,
+        reply: Reply<bool>,
+    },
 }
 
 #[derive(Debug, Clone)]
 struct ClientConnectionPair {
     to_server: Arc<UdpSocket>,
+    read_loop: ChildId,
+    /// Local port of `to_server`, which the server sees the client as
+    upstream_port: u16,
+    stats: Arc<SessionStats>,
 }
 
+/// Packets the router may have queued before socket readers have to wait.
+/// Waiting readers leave packets in the kernel buffer, which drops the excess
+/// under a flood instead of the router's memory growing without bound.
+const ROUTER_MAILBOX_CAPACITY: usize = 1024;
+
+/// Packets the router handles per wakeup when readers outpace it
+const ROUTER_MAX_BATCH: usize = 64;
+
+/// A batch taking longer than this usually means a socket send is stuck,
+/// which stalls every client
+const ROUTER_HANDLER_DEADLINE: Duration = Duration::from_millis(500);
+
 pub type Router = RunningActor<RouterMessage>;
+
+/// Runs the router as a managed task. Cancelling it lets the packets it has
+/// already queued reach the server, for up to `drain_timeout`.
+pub struct DrainingRouter {
+    pub router: Router,
+    pub drain_timeout: Duration,
+}
+
+impl CancellableTask for DrainingRouter {
+    fn cancel(&self) {
+        self.router.stop_graceful(Some(self.drain_timeout));
+    }
+
+    fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::new(self.router).join()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        self.router.as_any()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.router.is_finished()
+    }
+
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        self.router.abort_handle()
+    }
+}
 type RouterRef = ActorRef<RouterMessage>;
 
-pub fn create_router(remote_addr: SocketAddr, proxy_port: u16) -> Router {
+pub fn create_router(
+    remote_addr: SocketAddr,
+    pong_rewrite: PongRewrite,
+    parent_path: String,
+    events: EventSink,
+    log: LogSink,
+    metrics: Arc<ProxyMetrics>,
+    power: Arc<PowerMode>,
+) -> Router {
     let initial_state = RouterState {
         remote_addr,
-        proxy_port,
+        pong_rewrite,
         client_map: HashMap::new(),
+        events,
+        log,
+        metrics,
+        power,
     };
 
-    Actor::run(initial_state, behavior(router_handler_message))
+    let config = ActorConfig {
+        name: Some("router".to_string()),
+        parent_path: Some(parent_path),
+        mailbox: MailboxConfig::Bounded {
+            capacity: ROUTER_MAILBOX_CAPACITY,
+            overflow: OverflowPolicy::Block,
+        },
+        max_batch: ROUTER_MAX_BATCH,
+        handler_deadline: Some(ROUTER_HANDLER_DEADLINE),
+        ..ActorConfig::default()
+    };
+    let handler = batch_behavior(|self_ref, messages, state| {
+        Box::pin(router_handler_messages(self_ref, messages, state))
+    });
+    Actor::run_with_config(initial_state, handler, config)
 }
 
-async fn router_handler_message(
+/// Addresses of the clients the router currently has a session for
+pub async fn connected_clients(router: &RouterRef) -> Result<Vec<SocketAddr>, ActorError> {
+    router
+        .inspect(|state: &RouterState| state.client_map.keys().copied().collect())
+        .await
+}
+
+/// Ends a client's session. Returns whether it had one.
+pub async fn disconnect_client(
+    router: &RouterRef,
+    client_addr: SocketAddr,
+) -> Result<bool, ActorError> {
+    router
+        .ask(|reply| RouterMessage::Disconnect { client_addr, reply })
+        .await
+}
+
+/// A snapshot of every session the router has open
+pub async fn client_sessions(router: &RouterRef) -> Result<Vec<ClientSession>, ActorError> {
+    router
+        .inspect(|state: &RouterState| {
+            state
+                .client_map
+                .iter()
+                .map(|(addr, pair)| pair.stats.snapshot(*addr, pair.upstream_port))
+                .collect()
+        })
+        .await
+}
+
+async fn router_handler_messages(
     self_ref: RouterRef,
+    messages: Vec<RouterMessage>,
+    state: &mut RouterState,
+) {
+    for message in messages {
+        router_handler_message(&self_ref, message, state).await;
+    }
+}
+
+async fn router_handler_message(
+    self_ref: &RouterRef,
     message: RouterMessage,
-    mut state: RouterState,
-) -> RouterState {
-    let RouterMessage::PacketFromClient {
-        data,
-        client_addr,
-        to_client,
-    } = message;
+    state: &mut RouterState,
+) {
+    match message {
+        RouterMessage::PacketFromClient {
+            data,
+            client_addr,
+            to_client,
+        } => handle_client_packet(self_ref, state, data, client_addr, to_client).await,
+        RouterMessage::SessionClosed { client_addr } => {
+            remove_connection(self_ref, state, client_addr);
+        }
+        RouterMessage::Disconnect { client_addr, reply } => {
+            reply.send(remove_connection(self_ref, state, client_addr));
+        }
+    }
+}
+
+async fn handle_client_packet(
+    self_ref: &RouterRef,
+    state: &mut RouterState,
+    data: Bytes,
+    client_addr: SocketAddr,
+    to_client: Arc<UdpSocket>,
+) {
+    match classify(&data) {
+        PacketKind::Empty => {
+            log_packet!(
+                state.log,
+                "[router] Dropping empty packet from {}",
+                client_addr
+            );
+            return;
+        }
+        PacketKind::Offline(OfflineMessageId::UnconnectedPing) => {
+            if let Err(e) = UnconnectedPing::from_bytes_with_mode(data.clone(), MagicMode::Strict) {
+                log_packet!(
+                    state.log,
+                    "[router] Dropping invalid ping from {}: {}",
+                    client_addr,
+                    e
+                );
+                return;
+            }
+        }
+        _ => {}
+    }
 
-    try_add_connection(&self_ref, &mut state, client_addr, to_client).await;
+    try_add_connection(self_ref, state, client_addr, to_client).await;
 
     if let Some(client_pair) = state.client_map.get(&client_addr) {
         // Forward the packet to the remote server
-        client_pair
+        if let Err(e) = client_pair
             .to_server
             .send_to(&data, state.remote_addr)
             .await
-            .unwrap();
+        {
+            state.metrics.error();
+            log_to!(
+                state.log,
+                Warn,
+                "[router] Failed to forward packet from {} to remote server {}: {}",
+                client_addr,
+                state.remote_addr,
+                e
+            );
+            return;
+        }
+        state.metrics.forwarded_to_server(data.len());
+        client_pair.stats.client_sent(data.len());
 
-        debug!(
+        log_packet!(
+            state.log,
             "[router] Forwarded {} bytes from {} via {} to remote server {}",
             data.len(),
             client_addr,
@@ -76,7 +269,33 @@ async fn router_handler_message(
         );
     }
 
+    if classify(&data) == PacketKind::FrameSet && Datagram::is_disconnect(&data) {
+        remove_connection(self_ref, state, client_addr);
+    }
+}
+
+/// Ends the client's session, if it has one, closing its socket to the server
+fn remove_connection(
+    self_ref: &RouterRef,
+    state: &mut RouterState,
+    client_addr: SocketAddr,
+) -> bool {
+    let Some(client_pair) = state.client_map.remove(&client_addr) else {
+        return false;
+    };
+
+    self_ref.cancel_child(client_pair.read_loop);
+    state.metrics.client_disconnected();
+    log_to!(
+        state.log,
+        Info,
+        "[router] Client disconnected {}",
+        client_addr
+    );
     state
+        .events
+        .emit(PhantomEvent::client_disconnected(client_addr));
+    true
 }
 
 async fn try_add_connection(
@@ -87,53 +306,182 @@ async fn try_add_connection(
 ) {
     if !state.client_map.contains_key(&client_addr) {
         let to_server = Arc::new(UdpSocket::bind("0.0.0.0:0").await.unwrap());
-        info!(
+        log_to!(
+            state.log,
+            Info,
             "[router] New client connected {} -> {}",
             client_addr,
             to_server.local_addr().unwrap()
         );
 
+        let to_client_clone = to_client.clone();
+        let upstream_port = to_server.local_addr().map_or(0, |addr| addr.port());
+        let stats = Arc::new(SessionStats::new());
+
+        let read_loop = proxy_remote_read_loop(
+            router_ref.downgrade(),
+            state,
+            stats.clone(),
+            to_server.clone(),
+            to_client_clone,
+            client_addr,
+        );
+
+        let read_loop = router_ref.attach_child(read_loop);
         state.client_map.insert(
             client_addr,
             ClientConnectionPair {
-                to_server: to_server.clone(),
+                to_server,
+                read_loop,
+                upstream_port,
+                stats,
             },
         );
-
-        let to_client_clone = to_client.clone();
-        let proxy_port = state.proxy_port;
-
-        router_ref.attach_child(proxy_remote_read_loop(
-            to_server,
-            to_client_clone,
-            client_addr,
-            proxy_port,
-        ));
+        state.metrics.client_connected();
+        state.power.client_arrived();
+        state
+            .events
+            .emit(PhantomEvent::client_connected(client_addr));
     }
 }
 
+/// Holds the router weakly: the router owns this task as a child
 fn proxy_remote_read_loop(
+    router_ref: WeakActorRef<RouterMessage>,
+    state: &RouterState,
+    stats: Arc<SessionStats>,
     to_server: Arc<UdpSocket>,
     to_client: Arc<UdpSocket>,
     client_addr: SocketAddr,
-    proxy_port: u16,
 ) -> CancellablePacketReader {
-    info!(
+    let log = state.log.clone();
+    let metrics = state.metrics.clone();
+    let pong_rewrite = state.pong_rewrite.clone();
+    log_to!(
+        log,
+        Info,
         "[remote-read] Listening for data from remote server on {}",
         to_server.local_addr().unwrap()
     );
 
-    read_cancellable(to_server, move |packet| {
-        let to_client = to_client.clone();
-        async move {
-            if let Ok(original_pong) = UnconnectedPong::from_bytes(packet.data.clone()) {
-                let mut new_pong = original_pong.clone();
-                new_pong.pong.port4 = proxy_port.to_string();
-                let new_bytes = new_pong.build();
-                to_client.send_to(&new_bytes, client_addr).await.unwrap();
-            } else {
-                to_client.send_to(&packet.data, client_addr).await.unwrap();
+    let exit_router_ref = router_ref.clone();
+    let packet_log = log.clone();
+    read_cancellable(
+        to_server,
+        move |packet| {
+            let to_client = to_client.clone();
+            let router_ref = router_ref.clone();
+            let log = packet_log.clone();
+            let metrics = metrics.clone();
+            let stats = stats.clone();
+            let pong_rewrite = pong_rewrite.clone();
+            async move {
+                let data = match pong_rewrite.apply(&packet.data) {
+                    Some(new_bytes) => {
+                        metrics.pong_rewritten();
+                        new_bytes
+                    }
+                    None => packet.data.clone(),
+                };
+                match to_client.send_to(&data, client_addr).await {
+                    Ok(_) => {
+                        metrics.forwarded_to_client(data.len());
+                        stats.client_received(data.len());
+                        log_packet!(
+                            log,
+                            "[remote-read] Forwarded {} bytes from remote server to {}",
+                            data.len(),
+                            client_addr
+                        );
+                    }
+                    Err(e) => {
+                        metrics.error();
+                        log_to!(
+                            log,
+                            Warn,
+                            "[remote-read] Failed to forward packet to {}: {}",
+                            client_addr,
+                            e
+                        );
+                    }
+                }
+
+                if classify(&packet.data) == PacketKind::FrameSet
+                    && Datagram::is_disconnect(&packet.data)
+                {
+                    if let Some(router_ref) = router_ref.upgrade() {
+                        let _ = router_ref
+                            .send_async(RouterMessage::SessionClosed { client_addr })
+                            .await;
+                    }
+                }
+            }
+        },
+        move |exit| {
+            if matches!(exit, TaskExit::Cancelled) {
+                return;
+            }
+            // Nothing will reach the client from the server any more, so drop the
+            // session rather than leave it half open
+            log_to!(
+                log,
+                Warn,
+                "[remote-read] Read loop for {} {}",
+                client_addr,
+                exit
+            );
+            if let Some(router_ref) = exit_router_ref.upgrade() {
+                let _ = router_ref.send(RouterMessage::SessionClosed { client_addr });
+            }
+        },
+    )
+}
+
+/// How pongs from the server are changed on their way to clients
+#[derive(Debug, Clone)]
+pub struct PongRewrite {
+    /// Advertised as the IPv4 port, so clients join through the proxy
+    pub proxy_port: u16,
+    /// Replaces the server's MOTD when set. Shared with the instance, so a
+    /// change applies from the next pong on.
+    pub motd: Arc<RwLock<Option<String>>>,
+}
+
+impl PongRewrite {
+    /// Rewrites an unconnected pong or ADVERTISE_SYSTEM packet. Returns None
+    /// for any other packet.
+    fn apply(&self, data: &Bytes) -> Option<Bytes> {
+        match classify(data) {
+            PacketKind::Offline(OfflineMessageId::UnconnectedPong) => {
+                let mut pong =
+                    UnconnectedPong::from_bytes_with_mode(data.clone(), MagicMode::Strict).ok()?;
+                self.rewrite(&mut pong.pong);
+                pong.build().ok()
             }
+            PacketKind::Offline(OfflineMessageId::AdvertiseSystem) => {
+                let mut advertise =
+                    AdvertiseSystem::from_bytes_with_mode(data.clone(), MagicMode::Strict).ok()?;
+                self.rewrite(&mut advertise.pong);
+                advertise.build().ok()
+            }
+            _ => None,
         }
-    })
+    }
+
+    fn rewrite(&self, pong: &mut PongData) {
+        pong.port4 = self.proxy_port.to_string();
+        if let Some(motd) = &*self.motd.read() {
+            // Left alone if the server's other fields leave no room for it
+            let len = pong.encoded_len() - pong.motd.len() + motd.len();
+            if len <= max_pong_payload() {
+                pong.motd = motd.clone();
+            }
+        }
+    }
+}
+
+/// Most payload bytes a pong can carry and still fit in one datagram at the
+/// largest MTU
+pub(super) fn max_pong_payload() -> usize {
+    max_payload_size(MAX_MTU_SIZE) - UNCONNECTED_PONG_HEADER_SIZE
 }
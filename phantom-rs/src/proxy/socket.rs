@@ -1,10 +1,14 @@
 use std::{future::Future, net::SocketAddr, sync::Arc};
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use log::{debug, error};
 use tokio::net::UdpSocket;
+use tokio_util::sync::CancellationToken;
 
-use crate::task::TokioTask;
+use crate::actor::RestartPolicy;
+use crate::proto::mtu::RECV_BUFFER_SIZE;
+use crate::proto::packet::classify;
+use crate::task::{SupervisedTask, TaskExit, TokioTask};
 
 pub struct IncomingPacket {
     pub data: Bytes,
@@ -13,51 +17,104 @@ pub struct IncomingPacket {
 
 pub type CancellablePacketReader = TokioTask;
 
-pub fn read_cancellable<F: Send + 'static, Fut>(
+/// Reads packets from `socket` into `handler` until cancelled. `on_exit` is
+/// told how the loop ended, so a reader that dies on a socket error or a
+/// panicking handler doesn't go unnoticed.
+pub fn read_cancellable<F, Fut>(
     socket: Arc<UdpSocket>,
     handler: F,
+    on_exit: impl FnOnce(TaskExit) + Send + 'static,
 ) -> CancellablePacketReader
 where
-    F: Fn(IncomingPacket) -> Fut,
+    F: Fn(IncomingPacket) -> Fut + Send + Sync + 'static,
     Fut: Future<Output = ()> + Send + 'static,
 {
-    TokioTask::spawn(move |cancellation_token| async move {
-        let mut buf = vec![0; 1024];
-
-        loop {
-            tokio::select! {
-                _ = cancellation_token.cancelled() => {
-                    debug!("[socket-read] Cancellation signal received, stopping socket read loop.");
-                    break;
-                }
-                read_res = socket.recv_from(&mut buf) => {
-                    match read_res {
-                        Ok((len, client_addr)) => {
-                            let data = &buf[..len];
-                            debug!(
-                                "[socket-read] Received {} bytes from {} packet ID {}",
-                                len, client_addr, data[0]
-                            );
-                            handler(IncomingPacket {
-                                data: Bytes::from(data.to_vec()),
-                                client_addr,
-                            }).await;
-                        }
-                        Err(e) => {
-                            error!("Error receiving data: {}", e);
-                            break;
-                        }
+    TokioTask::spawn_with_exit(
+        move |cancellation_token| async move {
+            read_packets(&socket, &handler, cancellation_token).await
+        },
+        on_exit,
+    )
+}
+
+/// Like `read_cancellable`, but a loop that ends on a socket error or a
+/// panicking handler is restarted according to `policy`
+pub fn read_supervised<F, Fut>(
+    socket: Arc<UdpSocket>,
+    handler: F,
+    policy: RestartPolicy,
+    on_exit: impl FnOnce(TaskExit) + Send + 'static,
+) -> SupervisedTask
+where
+    F: Fn(IncomingPacket) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let handler = Arc::new(handler);
+    SupervisedTask::spawn_with_exit(
+        move |cancellation_token| {
+            let socket = socket.clone();
+            let handler = handler.clone();
+            async move { read_packets(&socket, &*handler, cancellation_token).await }
+        },
+        policy,
+        on_exit,
+    )
+}
+
+async fn read_packets<F, Fut>(
+    socket: &UdpSocket,
+    handler: &F,
+    cancellation_token: CancellationToken,
+) where
+    F: Fn(IncomingPacket) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut buf = BytesMut::with_capacity(RECV_BUFFER_SIZE);
+
+    loop {
+        tokio::select! {
+            _ = cancellation_token.cancelled() => {
+                debug!("[socket-read] Cancellation signal received, stopping socket read loop.");
+                break;
+            }
+            read_res = recv_packet(socket, &mut buf) => {
+                match read_res {
+                    Ok((data, client_addr)) => {
+                        debug!(
+                            "[socket-read] Received {} bytes from {} ({:?})",
+                            data.len(), client_addr, classify(&data)
+                        );
+                        handler(IncomingPacket {
+                            data,
+                            client_addr,
+                        }).await;
+                    }
+                    Err(e) => {
+                        error!("Error receiving data: {}", e);
+                        break;
                     }
                 }
             }
         }
+    }
+
+    debug!(
+        "Socket {} shut down",
+        socket
+            .local_addr()
+            .map(|a| a.to_string())
+            .unwrap_or_else(|_| "unknown".to_string())
+    );
+}
 
-        debug!(
-            "Socket {} shut down",
-            socket
-                .local_addr()
-                .map(|a| a.to_string())
-                .unwrap_or_else(|_| "unknown".to_string())
-        );
-    })
+/// Receives one datagram directly into `buf` and splits it off as `Bytes`, so
+/// the packet shares the receive allocation instead of being copied out of it
+pub async fn recv_packet(
+    socket: &UdpSocket,
+    buf: &mut BytesMut,
+) -> std::io::Result<(Bytes, SocketAddr)> {
+    buf.clear();
+    buf.reserve(RECV_BUFFER_SIZE);
+    let (_, addr) = socket.recv_buf_from(buf).await?;
+    Ok((buf.split().freeze(), addr))
 }
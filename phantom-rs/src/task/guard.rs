@@ -0,0 +1,108 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use tokio::task::AbortHandle;
+
+use super::{CancellableTask, TokioTask};
+
+/// Cancels its task when dropped, so a task that never makes it into a
+/// `TaskManager` doesn't run forever. The task is detached rather than
+/// joined: it stops at its next cancellation check. Registering the guard
+/// itself with a manager works like registering the task.
+pub struct TaskGuard {
+    /// Only `None` once `into_inner` or `join` has taken it
+    task: Option<TokioTask>,
+}
+
+impl TaskGuard {
+    pub fn new(task: TokioTask) -> Self {
+        Self { task: Some(task) }
+    }
+
+    /// Takes the task back out without cancelling it
+    pub fn into_inner(mut self) -> TokioTask {
+        self.task.take().expect("task already taken")
+    }
+}
+
+impl TokioTask {
+    /// Wraps the task in a `TaskGuard` that cancels it when dropped
+    pub fn guard(self) -> TaskGuard {
+        TaskGuard::new(self)
+    }
+}
+
+impl From<TokioTask> for TaskGuard {
+    fn from(task: TokioTask) -> Self {
+        Self::new(task)
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            if !task.is_finished() {
+                task.cancel();
+            }
+        }
+    }
+}
+
+impl CancellableTask for TaskGuard {
+    fn cancel(&self) {
+        if let Some(task) = &self.task {
+            task.cancel();
+        }
+    }
+
+    fn join(mut self: Box<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        match self.task.take() {
+            Some(task) => Box::new(task).join(),
+            None => Box::pin(async {}),
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.task.as_ref().is_none_or(|task| task.is_finished())
+    }
+
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        self.task.as_ref().and_then(|task| task.abort_handle())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskExit;
+    use tokio::sync::oneshot;
+
+    #[tokio::test]
+    async fn test_drop_cancels_task() {
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let guard = TokioTask::spawn_with_exit(
+            |_| std::future::pending(),
+            move |exit| {
+                let _ = exit_tx.send(exit);
+            },
+        )
+        .guard();
+        drop(guard);
+        assert!(matches!(exit_rx.await.unwrap(), TaskExit::Cancelled));
+
+        // Taking the task back out disarms the guard
+        let (exit_tx, mut exit_rx) = oneshot::channel();
+        let task = TokioTask::spawn_with_exit(
+            |_| std::future::pending(),
+            move |exit| {
+                let _ = exit_tx.send(exit);
+            },
+        )
+        .guard()
+        .into_inner();
+        tokio::task::yield_now().await;
+        assert!(exit_rx.try_recv().is_err());
+        task.cancel();
+        Box::new(task).join().await;
+    }
+}
@@ -4,26 +4,101 @@
 // tokio-util = "0.8"
 // futures = "0.3"
 
-use futures::Future;
+mod guard;
+mod scope;
+mod supervised;
+mod value;
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use futures::{Future, FutureExt};
+use log::warn;
+use parking_lot::Mutex;
+use std::any::Any;
+use std::marker::PhantomData;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
-use std::sync::{Arc, Mutex};
-use tokio::task::JoinHandle;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::{AbortHandle, JoinHandle};
 use tokio_util::sync::CancellationToken;
+use tracing::{debug_span, Instrument, Span};
+
+pub use guard::TaskGuard;
+pub use supervised::SupervisedTask;
+pub use value::ValueTask;
 
 /// An object‐safe trait for “something that can be cancelled and then awaited (joined)”.
 ///
 /// - `cancel(&self)`: Issues a cancellation signal (e.g. via channel, `CancellationToken`, etc).
-/// - `join(self)`: Consumes the task and returns a boxed Future you can `.await`.
+/// - `join(self)`: Consumes the task and returns a boxed Future you can `.await`,
+///   resolving to the task's result `T`. Most tasks just run until stopped, so
+///   `T` defaults to `()`.
 ///
 /// Because `async fn` in traits is not directly object‐safe, we manually box the future.
-pub trait CancellableTask: Send + 'static {
+pub trait CancellableTask<T = ()>: Send + 'static {
     /// Request cancellation (nonblocking). Implementers might, e.g., send on a channel
     /// or call `CancellationToken::cancel()`.
     fn cancel(&self);
 
-    /// Consume `self` and return a boxed Future that resolves when the task is done.
-    /// This must be object‐safe, so we return `Pin<Box<dyn Future<Output = ()> + Send>>`.
-    fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+    /// Consume `self` and return a boxed Future that resolves to the task's result
+    /// when it is done. This must be object‐safe, so we return
+    /// `Pin<Box<dyn Future<Output = T> + Send>>`.
+    fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = T> + Send>>;
+
+    /// The task as `Any`, so an owner holding many kinds of tasks can pick out
+    /// one kind (e.g. actors, to message them). `None` unless overridden.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
+
+    /// Whether the task has already exited. Tasks that can't tell report `false`.
+    fn is_finished(&self) -> bool {
+        false
+    }
+
+    /// A handle to force the task to stop if it ignores `cancel`. `None` if
+    /// it can't be aborted.
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        None
+    }
+}
+
+/// How a `TokioTask` ended, passed to its `spawn_with_exit` callback
+#[derive(Debug)]
+pub enum TaskExit {
+    /// The task's future returned on its own
+    Completed,
+    /// The task was cancelled before it returned
+    Cancelled,
+    /// The task's future panicked, with the panic payload
+    Panicked(Box<dyn Any + Send>),
+}
+
+impl TaskExit {
+    /// The panic message, if the task panicked with a string
+    pub fn panic_message(&self) -> Option<&str> {
+        let TaskExit::Panicked(panic) = self else {
+            return None;
+        };
+        panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+    }
+}
+
+impl std::fmt::Display for TaskExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskExit::Completed => write!(f, "completed"),
+            TaskExit::Cancelled => write!(f, "was cancelled"),
+            TaskExit::Panicked(_) => match self.panic_message() {
+                Some(message) => write!(f, "panicked: {}", message),
+                None => write!(f, "panicked"),
+            },
+        }
+    }
 }
 
 /// A concrete `CancellableTask` implementation built on Tokio’s `JoinHandle<()>` plus
@@ -33,6 +108,9 @@ pub trait CancellableTask: Send + 'static {
 pub struct TokioTask {
     handle: JoinHandle<()>,
     token: CancellationToken,
+    /// Disabled unless spawned with `spawn_named`
+    span: Span,
+    started: Instant,
 }
 
 impl TokioTask {
@@ -42,27 +120,94 @@ impl TokioTask {
     ///   check `token.cancelled().await` or `.is_cancelled()` (depending on your logic)
     ///   to exit early if cancellation was requested.
     pub fn spawn<Fn, Fut>(block: Fn) -> Self
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+        Fn: FnOnce(CancellationToken) -> Fut + Send + 'static,
+    {
+        Self::spawn_with_exit(block, |_| {})
+    }
+
+    /// Like `spawn`, and calls `on_exit` with how the task ended once it has
+    pub fn spawn_with_exit<Fn, Fut>(
+        block: Fn,
+        on_exit: impl FnOnce(TaskExit) + Send + 'static,
+    ) -> Self
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+        Fn: FnOnce(CancellationToken) -> Fut + Send + 'static,
+    {
+        Self::spawn_in_span(block, on_exit, Span::none())
+    }
+
+    /// Like `spawn`, running the task in a `task` span named `name`. Spawning,
+    /// cancellation and the task finishing are recorded as events in it, with
+    /// how long the task had been running.
+    pub fn spawn_named<Fn, Fut>(name: &str, block: Fn) -> Self
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+        Fn: FnOnce(CancellationToken) -> Fut + Send + 'static,
+    {
+        let span = debug_span!("task", name);
+        tracing::debug!(parent: &span, "task spawned");
+        Self::spawn_in_span(block, |_| {}, span)
+    }
+
+    fn spawn_in_span<Fn, Fut>(
+        block: Fn,
+        on_exit: impl FnOnce(TaskExit) + Send + 'static,
+        span: Span,
+    ) -> Self
     where
         Fut: Future<Output = ()> + Send + 'static,
         Fn: FnOnce(CancellationToken) -> Fut + Send + 'static,
     {
         let token = CancellationToken::new();
         let f = block(token.clone());
+        let started = Instant::now();
 
         let inner_token = token.clone();
-        let handle = tokio::spawn(async move {
-            tokio::select! {
-                _ = inner_token.cancelled() => {
-                    // The token was cancelled—exit early.
-                    // (You could do cleanup work here if needed, before returning.)
-                }
-                _ = f => {
-                    // The inner future finished normally.
+        let inner_span = span.clone();
+        let handle = tokio::spawn(
+            async move {
+                let exit = tokio::select! {
+                    _ = inner_token.cancelled() => {
+                        // The token was cancelled—exit early.
+                        // (You could do cleanup work here if needed, before returning.)
+                        TaskExit::Cancelled
+                    }
+                    result = AssertUnwindSafe(f).catch_unwind() => match result {
+                        // A future that watches the token itself may get there first
+                        Ok(()) if inner_token.is_cancelled() => TaskExit::Cancelled,
+                        // The inner future finished normally.
+                        Ok(()) => TaskExit::Completed,
+                        Err(panic) => TaskExit::Panicked(panic),
+                    }
+                };
+                if !inner_span.is_none() {
+                    tracing::debug!(
+                        parent: &inner_span,
+                        exit = %exit,
+                        duration_ms = started.elapsed().as_millis() as u64,
+                        "task finished"
+                    );
                 }
+                on_exit(exit);
             }
-        });
+            .instrument(span.clone()),
+        );
 
-        TokioTask { handle, token }
+        TokioTask {
+            handle,
+            token,
+            span,
+            started,
+        }
+    }
+
+    /// A clone of the task's cancellation token, for cancelling it after it has
+    /// been handed off (e.g. attached to an actor)
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.token.clone()
     }
 }
 
@@ -70,6 +215,21 @@ impl CancellableTask for TokioTask {
     fn cancel(&self) {
         // Signal cancellation. The running task is listening on `self.token`.
         self.token.cancel();
+        if !self.span.is_none() {
+            tracing::debug!(
+                parent: &self.span,
+                running_ms = self.started.elapsed().as_millis() as u64,
+                "task cancelled"
+            );
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        Some(self.handle.abort_handle())
     }
 
     fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
@@ -80,20 +240,102 @@ impl CancellableTask for TokioTask {
     }
 }
 
+/// What a listed task is doing. Finished tasks aren't listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum TaskStatus {
+    Running,
+    /// `TaskManager::cancel` was called and it hasn't stopped yet
+    Cancelling,
+}
+
+/// When a task is stopped during `TaskManager::shutdown`. Every task in a
+/// group is cancelled and joined before the next group is touched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum ShutdownGroup {
+    /// Tasks that take work in from outside, e.g. socket readers
+    Ingress,
+    /// Tasks that handle that work and may have some queued
+    #[default]
+    Processing,
+    /// Everything else
+    Auxiliary,
+}
+
+/// Options for `TaskManager::add_task_with_config`
+#[derive(Debug, Clone, Default)]
+pub struct TaskConfig {
+    /// Shown in `list` and shutdown warnings
+    pub name: Option<String>,
+    pub group: ShutdownGroup,
+}
+
+/// Identifies a task added to a `TaskManager`, for `cancel` and `join`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TaskHandle(u64);
+
+impl TaskHandle {
+    /// The id shown in `TaskInfo::id`
+    pub fn id(&self) -> u64 {
+        self.0
+    }
+}
+
+/// One entry of `TaskManager::list`
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct TaskInfo {
+    pub id: u64,
+    pub name: Option<String>,
+    pub status: TaskStatus,
+    /// Time since the task was added
+    pub age: Duration,
+}
+
+struct ManagedTask {
+    handle: TaskHandle,
+    name: Option<String>,
+    group: ShutdownGroup,
+    added: Instant,
+    cancelled: bool,
+    task: Box<dyn CancellableTask + Send>,
+}
+
 /// A “manager” that holds many `Box<dyn CancellableTask>`. Internally it uses
 /// `Arc<Mutex<Vec<…>>>` so that any clone of `TaskManager` can add tasks or
 /// later call `shutdown(&self)`. Because the `Vec` is wrapped in a `Mutex`,
-/// you never need a `&mut self` to modify it—just `&self`.
+/// you never need a `&mut self` to modify it—just `&self`. The lock is
+/// `parking_lot`'s, which doesn't poison when a task's `cancel` panics, and
+/// is only held for short, non-async sections, so adding tasks from async
+/// code doesn't stall the runtime.
 #[derive(Clone)]
 pub struct TaskManager {
-    inner: Arc<Mutex<Vec<Box<dyn CancellableTask + Send>>>>,
+    inner: Arc<Mutex<Vec<ManagedTask>>>,
+    next_id: Arc<AtomicU64>,
+    join_timeout: Duration,
+    /// Cancelled by `shutdown`, so a parent scope can drop its entry
+    closed: CancellationToken,
+    /// Set once a child scope starts shutting down, after which it takes no
+    /// more tasks. `None` for managers that can be started again.
+    closing: Option<Arc<AtomicBool>>,
 }
 
+/// How long `shutdown` waits for each task before aborting it
+pub const DEFAULT_JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+
 impl TaskManager {
     /// Create a new, empty TaskManager.
     pub fn new() -> Self {
+        Self::with_join_timeout(DEFAULT_JOIN_TIMEOUT)
+    }
+
+    /// Like `new`, but `shutdown` gives each task `join_timeout` to stop
+    /// before aborting it
+    pub fn with_join_timeout(join_timeout: Duration) -> Self {
         TaskManager {
             inner: Arc::new(Mutex::new(Vec::new())),
+            next_id: Arc::new(AtomicU64::new(0)),
+            join_timeout,
+            closed: CancellationToken::new(),
+            closing: None,
         }
     }
 
@@ -107,32 +349,546 @@ impl TaskManager {
     /// });
     /// manager.add_task(Box::new(my_task));
     /// ```
-    pub fn add_task(&self, task: impl CancellableTask) {
-        let mut guard = self.inner.lock().expect("Mutex poisoned");
-        guard.push(Box::new(task));
+    ///
+    /// Tasks with a result can be added too; it's dropped when the manager
+    /// joins them, so keep the task if you need its value.
+    ///
+    /// The returned handle stops just this task with `cancel` and `join`.
+    pub fn add_task<T: Send + 'static>(&self, task: impl CancellableTask<T>) -> TaskHandle {
+        self.add_task_with_config(task, TaskConfig::default())
+    }
+
+    /// Like `add_task`, with a name to show in `list`
+    pub fn add_named_task<T: Send + 'static>(
+        &self,
+        name: impl Into<String>,
+        task: impl CancellableTask<T>,
+    ) -> TaskHandle {
+        let config = TaskConfig {
+            name: Some(name.into()),
+            ..TaskConfig::default()
+        };
+        self.add_task_with_config(task, config)
+    }
+
+    /// Like `add_task`, with a name and shutdown group from `config`
+    pub fn add_task_with_config<T: Send + 'static>(
+        &self,
+        task: impl CancellableTask<T>,
+        config: TaskConfig,
+    ) -> TaskHandle {
+        let handle = TaskHandle(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let managed = ManagedTask {
+            handle,
+            name: config.name,
+            group: config.group,
+            added: Instant::now(),
+            cancelled: false,
+            task: Box::new(DiscardResult(task, PhantomData)),
+        };
+        let mut guard = self.inner.lock();
+        if self.is_closing() {
+            // Nothing would stop it otherwise, so stop it now
+            drop(guard);
+            warn!(
+                "[task-manager] task {} added after its scope shut down, cancelling it",
+                managed.name.as_deref().unwrap_or("unnamed")
+            );
+            managed.task.cancel();
+            let manager = self.clone();
+            tokio::spawn(async move { manager.join_managed(managed).await });
+            return handle;
+        }
+        reap(&mut guard);
+        guard.push(managed);
+        handle
+    }
+
+    /// Signals one task to stop. It stays listed until it finishes.
+    /// Returns `false` if the manager doesn't have it.
+    pub fn cancel(&self, handle: TaskHandle) -> bool {
+        // Taken out so the task's cancel hook runs without the lock, in case
+        // it calls back into the manager. Put back even if the hook panics.
+        let mut taken = {
+            let mut guard = self.inner.lock();
+            let Some(index) = guard.iter().position(|managed| managed.handle == handle) else {
+                return false;
+            };
+            let managed = guard.remove(index);
+            TakenTask {
+                tasks: &self.inner,
+                index,
+                managed: Some(managed),
+            }
+        };
+        if let Some(managed) = &mut taken.managed {
+            managed.task.cancel();
+            managed.cancelled = true;
+        }
+        true
+    }
+
+    /// Removes one task and waits for it to finish, aborting it after the join
+    /// timeout. Call `cancel` first unless it is expected to end by itself.
+    /// Returns `false` if the manager doesn't have it, which includes tasks
+    /// that already finished and were dropped.
+    pub async fn join(&self, handle: TaskHandle) -> bool {
+        let managed = {
+            let mut guard = self.inner.lock();
+            let Some(index) = guard.iter().position(|managed| managed.handle == handle) else {
+                return false;
+            };
+            guard.remove(index)
+        };
+        self.join_managed(managed).await;
+        true
+    }
+
+    /// Every unfinished task, in the order they were added
+    pub fn list(&self) -> Vec<TaskInfo> {
+        let mut guard = self.inner.lock();
+        reap(&mut guard);
+        guard
+            .iter()
+            .map(|managed| TaskInfo {
+                id: managed.handle.id(),
+                name: managed.name.clone(),
+                status: if managed.cancelled {
+                    TaskStatus::Cancelling
+                } else {
+                    TaskStatus::Running
+                },
+                age: managed.added.elapsed(),
+            })
+            .collect()
     }
 
     /// Shut everything down. This takes all tasks out of the internal Vec,
-    /// calls `cancel()` on each one, then `.await`s each `.join()`. Because
-    /// we drain the Vec in one go, we never hold the `MutexGuard` across `.await`.
+    /// then for each `ShutdownGroup` in order calls `cancel()` on its tasks and
+    /// `.await`s their `.join()`s concurrently, so stuck tasks cost one join
+    /// timeout per group rather than one each. A task still running after the
+    /// join timeout is aborted. Because we drain the Vec in one go, we never hold the
+    /// `MutexGuard` across `.await`.
     pub async fn shutdown(&self) {
+        self.shutdown_observed(|_| {}).await;
+    }
+
+    /// `shutdown`, calling `on_group` as each group with tasks in it starts
+    /// stopping
+    pub async fn shutdown_observed(&self, mut on_group: impl FnMut(ShutdownGroup)) {
         // 1. Grab the lock and replace the Vec with an empty one, so we can drop the lock.
-        let tasks_to_cancel: Vec<Box<dyn CancellableTask + Send>> = {
-            let mut guard = self.inner.lock().expect("Mutex poisoned");
+        let mut tasks_to_cancel: Vec<ManagedTask> = {
+            let mut guard = self.inner.lock();
+            if let Some(closing) = &self.closing {
+                closing.store(true, Ordering::SeqCst);
+            }
             // Use `std::mem::take` to replace `*guard` with a brand‐new Vec,
             // returning the old Vec. This ensures we do not hold the lock
             // while we `.await` on each task.
             std::mem::take(&mut *guard)
         };
 
-        // 2. Cancel and join each task. We know `tasks_to_cancel` now owns all the tasks.
-        for task in &tasks_to_cancel {
-            task.cancel();
-        }
+        // 2. Cancel and join each group's tasks. We know `tasks_to_cancel` now
+        // owns all the tasks; the sort is stable so a group keeps the order
+        // its tasks were added in.
+        tasks_to_cancel.sort_by_key(|managed| managed.group);
+        while let Some(group) = tasks_to_cancel.first().map(|managed| managed.group) {
+            let split = tasks_to_cancel
+                .iter()
+                .position(|managed| managed.group != group)
+                .unwrap_or(tasks_to_cancel.len());
+            let rest = tasks_to_cancel.split_off(split);
 
-        for task in tasks_to_cancel {
-            task.join().await;
+            on_group(group);
+            for managed in &tasks_to_cancel {
+                managed.task.cancel();
+            }
+            let mut joins: FuturesUnordered<_> = std::mem::replace(&mut tasks_to_cancel, rest)
+                .into_iter()
+                .map(|managed| self.join_managed(managed))
+                .collect();
+            while joins.next().await.is_some() {}
         }
         // At this point, all tasks have been signaled to cancel, and we have awaited them.
+        self.closed.cancel();
+    }
+
+    /// Whether this is a child scope that has started shutting down
+    fn is_closing(&self) -> bool {
+        self.closing
+            .as_ref()
+            .is_some_and(|closing| closing.load(Ordering::SeqCst))
+    }
+
+    /// Awaits `managed`, aborting it if it takes longer than the join timeout
+    async fn join_managed(&self, managed: ManagedTask) {
+        let abort = managed.task.abort_handle();
+        let joined = tokio::time::timeout(self.join_timeout, managed.task.join()).await;
+        if joined.is_ok() {
+            return;
+        }
+
+        let name = managed.name.as_deref().unwrap_or("unnamed");
+        match abort {
+            Some(abort) => {
+                warn!(
+                    "[task-manager] task {} ignored cancellation for {:?}, aborting it",
+                    name, self.join_timeout
+                );
+                abort.abort();
+            }
+            None => warn!(
+                "[task-manager] task {} ignored cancellation for {:?} and can't be aborted, leaving it running",
+                name, self.join_timeout
+            ),
+        }
+    }
+}
+
+/// Lets the manager hold a task with any result type, dropping the result
+/// once the task is joined
+struct DiscardResult<T, Task>(Task, PhantomData<fn() -> T>);
+
+impl<T: Send + 'static, Task: CancellableTask<T>> CancellableTask for DiscardResult<T, Task> {
+    fn cancel(&self) {
+        self.0.cancel();
+    }
+
+    fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let join = Box::new(self.0).join();
+        Box::pin(async move {
+            join.await;
+        })
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        self.0.as_any()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
+
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        self.0.abort_handle()
+    }
+}
+
+/// A task taken out of the manager's list, put back where it was on drop
+struct TakenTask<'a> {
+    tasks: &'a Mutex<Vec<ManagedTask>>,
+    index: usize,
+    managed: Option<ManagedTask>,
+}
+
+impl Drop for TakenTask<'_> {
+    fn drop(&mut self) {
+        if let Some(managed) = self.managed.take() {
+            let mut tasks = self.tasks.lock();
+            let index = self.index.min(tasks.len());
+            tasks.insert(index, managed);
+        }
+    }
+}
+
+/// Drops tasks that have finished on their own, so a long-running manager
+/// doesn't pile up dead entries. Tasks that can't tell stay until shutdown.
+fn reap(tasks: &mut Vec<ManagedTask>) {
+    tasks.retain(|managed| !managed.task.is_finished());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A task that only stops when aborted
+    struct IgnoresCancel(JoinHandle<()>);
+
+    impl CancellableTask for IgnoresCancel {
+        fn cancel(&self) {}
+
+        fn abort_handle(&self) -> Option<AbortHandle> {
+            Some(self.0.abort_handle())
+        }
+
+        fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+            Box::pin(async move {
+                let _ = self.0.await;
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_list_named_tasks() {
+        let manager = TaskManager::new();
+        manager.add_named_task(
+            "reader",
+            TokioTask::spawn(|token| async move { token.cancelled().await }),
+        );
+        let cancelled = manager.add_task(IgnoresCancel(tokio::spawn(std::future::pending())));
+        manager.cancel(cancelled);
+        // Finished tasks are reaped
+        manager.add_task(TokioTask::spawn(|_| async {}));
+        tokio::task::yield_now().await;
+
+        let tasks = manager.list();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].name.as_deref(), Some("reader"));
+        assert_eq!(tasks[0].status, TaskStatus::Running);
+        assert_eq!(tasks[1].name, None);
+        assert_eq!(tasks[1].status, TaskStatus::Cancelling);
+
+        manager.shutdown().await;
+        assert!(manager.list().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_aborts_stuck_task() {
+        let manager = TaskManager::with_join_timeout(Duration::from_secs(1));
+        let stuck = tokio::spawn(std::future::pending::<()>());
+        let abort = stuck.abort_handle();
+        manager.add_named_task("stuck", IgnoresCancel(stuck));
+
+        manager.shutdown().await;
+        tokio::task::yield_now().await;
+        assert!(abort.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_exit_notifications() {
+        let (tx, mut exits) = tokio::sync::mpsc::unbounded_channel();
+
+        let notify = |tx: &tokio::sync::mpsc::UnboundedSender<TaskExit>| {
+            let tx = tx.clone();
+            move |exit| {
+                let _ = tx.send(exit);
+            }
+        };
+        let completed = TokioTask::spawn_with_exit(|_| async {}, notify(&tx));
+        assert!(matches!(exits.recv().await, Some(TaskExit::Completed)));
+
+        let panicked = TokioTask::spawn_with_exit(|_| async { panic!("reader died") }, notify(&tx));
+        let exit = exits.recv().await.unwrap();
+        assert_eq!(exit.panic_message(), Some("reader died"));
+
+        let cancelled = TokioTask::spawn_with_exit(|_| std::future::pending(), notify(&tx));
+        cancelled.cancel();
+        assert!(matches!(exits.recv().await, Some(TaskExit::Cancelled)));
+
+        for task in [completed, panicked, cancelled] {
+            Box::new(task).join().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_hook_can_use_manager() {
+        struct ListsOnCancel(TaskManager, TokioTask);
+        impl CancellableTask for ListsOnCancel {
+            fn cancel(&self) {
+                self.0.list();
+                self.1.cancel();
+            }
+
+            fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+                Box::new(self.1).join()
+            }
+        }
+
+        let manager = TaskManager::new();
+        let task = TokioTask::spawn(|_| std::future::pending());
+        let handle = manager.add_task(ListsOnCancel(manager.clone(), task));
+
+        assert!(manager.cancel(handle));
+        assert_eq!(manager.list().len(), 1);
+        assert!(manager.join(handle).await);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_one_task() {
+        let manager = TaskManager::new();
+        let first = manager.add_task(TokioTask::spawn(|_| std::future::pending()));
+        let second = manager.add_named_task("kept", TokioTask::spawn(|_| std::future::pending()));
+
+        assert!(manager.cancel(first));
+        assert!(manager.join(first).await);
+        assert!(!manager.cancel(first));
+        assert!(!manager.join(first).await);
+
+        let tasks = manager.list();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, second.id());
+        assert_eq!(tasks[0].status, TaskStatus::Running);
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_groups_in_order() {
+        let manager = TaskManager::new();
+        let stopped = Arc::new(Mutex::new(Vec::new()));
+
+        let task = |name: &'static str| {
+            let stopped = stopped.clone();
+            TokioTask::spawn_with_exit(
+                |_| std::future::pending(),
+                move |_| stopped.lock().push(name),
+            )
+        };
+        let in_group = |group| TaskConfig {
+            group,
+            ..TaskConfig::default()
+        };
+        manager.add_task_with_config(task("auxiliary"), in_group(ShutdownGroup::Auxiliary));
+        manager.add_task(task("processing"));
+        manager.add_task_with_config(task("ingress"), in_group(ShutdownGroup::Ingress));
+
+        let mut groups = Vec::new();
+        manager.shutdown_observed(|group| groups.push(group)).await;
+        assert_eq!(*stopped.lock(), vec!["ingress", "processing", "auxiliary"]);
+        assert_eq!(
+            groups,
+            vec![
+                ShutdownGroup::Ingress,
+                ShutdownGroup::Processing,
+                ShutdownGroup::Auxiliary
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_joins_concurrently() {
+        let manager = TaskManager::with_join_timeout(Duration::from_secs(1));
+        for _ in 0..10 {
+            let stuck = tokio::spawn(std::future::pending::<()>());
+            manager.add_task(IgnoresCancel(stuck));
+        }
+
+        let started = tokio::time::Instant::now();
+        manager.shutdown().await;
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_panicking_cancel_does_not_poison() {
+        struct PanicsOnCancel(IgnoresCancel);
+        impl CancellableTask for PanicsOnCancel {
+            fn cancel(&self) {
+                panic!("cancel failed");
+            }
+
+            fn abort_handle(&self) -> Option<AbortHandle> {
+                self.0.abort_handle()
+            }
+
+            fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+                Box::new(self.0).join()
+            }
+        }
+
+        let manager = TaskManager::with_join_timeout(Duration::from_secs(1));
+        let stuck = IgnoresCancel(tokio::spawn(std::future::pending()));
+        let handle = manager.add_task(PanicsOnCancel(stuck));
+
+        let cancel = std::panic::catch_unwind(AssertUnwindSafe(|| manager.cancel(handle)));
+        assert!(cancel.is_err());
+        assert_eq!(manager.list().len(), 1);
+        manager.add_task(TokioTask::spawn(|_| std::future::pending()));
+        assert!(manager.join(handle).await);
+    }
+
+    /// One recorded event: the name of its span, its message and other fields
+    type Recorded = (Option<String>, String, Vec<(&'static str, String)>);
+
+    /// Records events with the name of the span they belong to
+    #[derive(Default)]
+    struct Recorder {
+        spans: Mutex<Vec<String>>,
+        events: Arc<Mutex<Vec<Recorded>>>,
+    }
+
+    #[derive(Default)]
+    struct Fields(Vec<(&'static str, String)>);
+
+    impl tracing::field::Visit for Fields {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0.push((field.name(), value.to_string()));
+        }
+
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.push((field.name(), format!("{:?}", value)));
+        }
+    }
+
+    impl tracing::Subscriber for Recorder {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut fields = Fields::default();
+            span.record(&mut fields);
+            let name = fields.0.into_iter().find(|(field, _)| *field == "name");
+            let mut spans = self.spans.lock();
+            spans.push(name.map(|(_, value)| value).unwrap_or_default());
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            let span = event
+                .parent()
+                .and_then(|id| self.spans.lock().get(id.into_u64() as usize - 1).cloned());
+            let message = fields
+                .0
+                .iter()
+                .position(|(field, _)| *field == "message")
+                .map(|index| fields.0.remove(index).1)
+                .unwrap_or_default();
+            self.events.lock().push((span, message, fields.0));
+        }
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[tokio::test]
+    async fn test_spawn_named() {
+        let recorder = Recorder::default();
+        let events = recorder.events.clone();
+        // The test runtime is single-threaded, so the task's events land here too
+        let _default = tracing::subscriber::set_default(recorder);
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let task = TokioTask::spawn_named("probe", |token| async move {
+            let _ = tx.send(());
+            token.cancelled().await;
+        });
+
+        rx.await.unwrap();
+        task.cancel();
+        Box::new(task).join().await;
+
+        let events = events.lock();
+        let messages: Vec<_> = events
+            .iter()
+            .map(|(span, message, _)| (span.as_deref(), message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (Some("probe"), "task spawned"),
+                (Some("probe"), "task cancelled"),
+                (Some("probe"), "task finished"),
+            ]
+        );
+        let (_, _, finished) = &events[2];
+        assert!(finished.contains(&("exit", "was cancelled".to_string())));
+        assert!(finished.iter().any(|(field, _)| *field == "duration_ms"));
     }
 }
@@ -0,0 +1,111 @@
+use parking_lot::Mutex;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use tokio::task::JoinHandle;
+
+use super::{CancellableTask, TaskConfig, TaskManager};
+
+impl TaskManager {
+    /// A new manager whose tasks are shut down along with this one's, e.g.
+    /// everything belonging to one client session. Shutting the scope down
+    /// directly stops just its tasks; it can't be reused afterwards, and tasks
+    /// added once it has started shutting down are cancelled straight away.
+    pub fn child_scope(&self, config: TaskConfig) -> TaskManager {
+        let mut child = TaskManager::with_join_timeout(self.join_timeout);
+        child.closing = Some(Arc::new(AtomicBool::new(false)));
+        let scope = Scope {
+            manager: child.clone(),
+            shutdown: Mutex::new(None),
+        };
+        self.add_task_with_config(scope, config);
+        child
+    }
+}
+
+/// A child scope as seen by its parent
+struct Scope {
+    manager: TaskManager,
+    shutdown: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl CancellableTask for Scope {
+    fn cancel(&self) {
+        let mut shutdown = self.shutdown.lock();
+        if shutdown.is_none() {
+            let manager = self.manager.clone();
+            *shutdown = Some(tokio::spawn(async move { manager.shutdown().await }));
+        }
+    }
+
+    fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        self.cancel();
+        Box::pin(async move {
+            let shutdown = self.shutdown.lock().take();
+            if let Some(shutdown) = shutdown {
+                let _ = shutdown.await;
+            }
+        })
+    }
+
+    fn is_finished(&self) -> bool {
+        self.manager.closed.is_cancelled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::{TaskExit, TokioTask};
+
+    #[tokio::test]
+    async fn test_scopes_shut_down_with_parent() {
+        let parent = TaskManager::new();
+        let stopped = Arc::new(Mutex::new(Vec::new()));
+        let task = |name: &'static str| {
+            let stopped = stopped.clone();
+            TokioTask::spawn_with_exit(
+                |_| std::future::pending(),
+                move |exit| {
+                    assert!(matches!(exit, TaskExit::Cancelled));
+                    stopped.lock().push(name);
+                },
+            )
+        };
+
+        let evicted = parent.child_scope(TaskConfig::default());
+        evicted.add_task(task("evicted"));
+        let kept = parent.child_scope(TaskConfig::default());
+        kept.add_task(task("kept"));
+        assert_eq!(parent.list().len(), 2);
+
+        // Dropping one session leaves the other alone
+        evicted.shutdown().await;
+        assert_eq!(*stopped.lock(), vec!["evicted"]);
+        assert_eq!(parent.list().len(), 1);
+
+        parent.shutdown().await;
+        assert_eq!(*stopped.lock(), vec!["evicted", "kept"]);
+    }
+
+    #[tokio::test]
+    async fn test_closed_scope_cancels_new_tasks() {
+        let parent = TaskManager::new();
+        let scope = parent.child_scope(TaskConfig::default());
+        scope.shutdown().await;
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let handle = scope.add_task(TokioTask::spawn_with_exit(
+            |_| std::future::pending(),
+            move |exit| {
+                let _ = tx.send(exit);
+            },
+        ));
+
+        assert!(matches!(rx.await, Ok(TaskExit::Cancelled)));
+        assert!(scope.list().is_empty());
+        assert!(!scope.cancel(handle));
+    }
+}
@@ -0,0 +1,187 @@
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+
+use futures::FutureExt;
+use log::{debug, warn};
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio::time::{sleep, Instant};
+use tokio_util::sync::CancellationToken;
+
+use super::{CancellableTask, TaskExit};
+use crate::actor::RestartPolicy;
+
+/// A task that runs a fresh future from its factory whenever the last one
+/// completes or panics without having been cancelled, waiting out
+/// `RestartPolicy`'s backoff in between. Once the restart budget is spent it
+/// stays down; a run that outlasts `max_backoff` refills it.
+pub struct SupervisedTask {
+    token: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+impl SupervisedTask {
+    pub fn spawn<F, Fut>(factory: F, policy: RestartPolicy) -> Self
+    where
+        F: FnMut(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self::spawn_with_exit(factory, policy, |_| {})
+    }
+
+    /// Like `spawn`, and calls `on_exit` with how the last run ended once the
+    /// task is cancelled or gives up
+    pub fn spawn_with_exit<F, Fut>(
+        mut factory: F,
+        policy: RestartPolicy,
+        on_exit: impl FnOnce(TaskExit) + Send + 'static,
+    ) -> Self
+    where
+        F: FnMut(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let token = CancellationToken::new();
+        let inner_token = token.clone();
+        let handle = tokio::spawn(async move {
+            let mut restarts = 0;
+            let exit = loop {
+                let started = Instant::now();
+                let run = AssertUnwindSafe(factory(inner_token.child_token())).catch_unwind();
+                let exit = tokio::select! {
+                    _ = inner_token.cancelled() => TaskExit::Cancelled,
+                    result = run => match result {
+                        Ok(()) if inner_token.is_cancelled() => TaskExit::Cancelled,
+                        Ok(()) => TaskExit::Completed,
+                        Err(panic) => TaskExit::Panicked(panic),
+                    },
+                };
+                if matches!(exit, TaskExit::Cancelled) {
+                    break exit;
+                }
+                if policy.healthy(started.elapsed()) {
+                    restarts = 0;
+                }
+                if policy.exhausted(restarts) {
+                    warn!(
+                        "[supervised-task] {}, giving up after {} restarts",
+                        exit, restarts
+                    );
+                    break exit;
+                }
+
+                let backoff = policy.backoff(restarts);
+                restarts += 1;
+                debug!("[supervised-task] {}, restarting in {:?}", exit, backoff);
+
+                tokio::select! {
+                    _ = inner_token.cancelled() => break TaskExit::Cancelled,
+                    _ = sleep(backoff) => {}
+                }
+            };
+            on_exit(exit);
+        });
+
+        Self { token, handle }
+    }
+}
+
+impl CancellableTask for SupervisedTask {
+    fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(async move {
+            let _ = self.handle.await;
+        })
+    }
+
+    fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        Some(self.handle.abort_handle())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use tokio::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_restarts_until_budget() {
+        let runs = Arc::new(AtomicU32::new(0));
+        let (tx, exited) = tokio::sync::oneshot::channel();
+
+        let counter = runs.clone();
+        let policy = RestartPolicy {
+            max_restarts: Some(3),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(2),
+        };
+        let task = SupervisedTask::spawn_with_exit(
+            move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async {}
+            },
+            policy,
+            move |exit| {
+                let _ = tx.send(exit);
+            },
+        );
+
+        assert!(matches!(exited.await.unwrap(), TaskExit::Completed));
+        assert_eq!(runs.load(Ordering::SeqCst), 4);
+        Box::new(task).join().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_healthy_run_resets_budget() {
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let counter = runs.clone();
+        let policy = RestartPolicy {
+            max_restarts: Some(1),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(2),
+        };
+        let task = SupervisedTask::spawn(
+            move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                // Fails, but only after a healthy stretch each time
+                sleep(Duration::from_secs(3))
+            },
+            policy,
+        );
+
+        sleep(Duration::from_secs(60)).await;
+        assert!(runs.load(Ordering::SeqCst) > 10);
+        assert!(!task.is_finished());
+
+        task.cancel();
+        Box::new(task).join().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancel_stops_restarts() {
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let counter = runs.clone();
+        let task = SupervisedTask::spawn(
+            move |token| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async move { token.cancelled().await }
+            },
+            RestartPolicy::default(),
+        );
+        tokio::task::yield_now().await;
+
+        task.cancel();
+        Box::new(task).join().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,76 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+use super::CancellableTask;
+
+/// A task that produces a value, e.g. a one-shot lookup. Joining it gives
+/// `Some(value)`, or `None` if it was cancelled before finishing or panicked.
+pub struct ValueTask<T> {
+    handle: JoinHandle<Option<T>>,
+    token: CancellationToken,
+}
+
+impl<T: Send + 'static> ValueTask<T> {
+    /// Like `TokioTask::spawn`, for a future with a result
+    pub fn spawn<Fn, Fut>(block: Fn) -> Self
+    where
+        Fut: Future<Output = T> + Send + 'static,
+        Fn: FnOnce(CancellationToken) -> Fut + Send + 'static,
+    {
+        let token = CancellationToken::new();
+        let f = block(token.clone());
+
+        let inner_token = token.clone();
+        let handle = tokio::spawn(async move {
+            tokio::select! {
+                _ = inner_token.cancelled() => None,
+                value = f => Some(value),
+            }
+        });
+
+        Self { handle, token }
+    }
+}
+
+impl<T: Send + 'static> CancellableTask<Option<T>> for ValueTask<T> {
+    fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    fn abort_handle(&self) -> Option<AbortHandle> {
+        Some(self.handle.abort_handle())
+    }
+
+    fn join(self: Box<Self>) -> Pin<Box<dyn Future<Output = Option<T>> + Send>> {
+        Box::pin(async move { self.handle.await.ok().flatten() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskManager;
+
+    #[tokio::test]
+    async fn test_join_returns_value() {
+        let task = ValueTask::spawn(|_| async { 42 });
+        assert_eq!(Box::new(task).join().await, Some(42));
+
+        let task = ValueTask::spawn(|_| std::future::pending::<u32>());
+        task.cancel();
+        assert_eq!(Box::new(task).join().await, None);
+
+        // The manager takes tasks with results like any other
+        let manager = TaskManager::new();
+        let handle = manager.add_task(ValueTask::spawn(|_| std::future::pending::<u32>()));
+        assert!(manager.cancel(handle));
+        assert!(manager.join(handle).await);
+    }
+}